@@ -0,0 +1,297 @@
+//! Drop-in replacements for the functions of `std::env`.
+//!
+//! Variables set in a task-local `ScopedEnv` of the current task or in a
+//! [`crate::VirtualEnvScope`] of the current thread take precedence over the
+//! process environment. Writes go to the innermost of those overlays, or to the
+//! process environment while holding the lock of this crate.
+//!
+//! Reading through these functions instead of `std::env` lets the active scopes
+//! of the current thread observe which variables the code under test depends on.
+//! Scopes tracking reads record every variable read. If any of those scopes is in
+//! strict mode, reading a variable which was not declared in one of them panics,
+//! which makes undeclared dependencies fail the test. Scopes of other threads,
+//! e.g. of tests running concurrently, are not affected.
+
+use std::{
+    collections::HashMap,
+    env::VarError,
+    ffi::{OsStr, OsString},
+};
+
+use crate::{lock, registry, sys, virtual_env};
+
+/// Same as [`std::env::var`], but checked against the active scopes.
+pub fn var<K: AsRef<OsStr>>(key: K) -> Result<String, VarError> {
+    match var_os(key) {
+        Some(value) => value.into_string().map_err(VarError::NotUnicode),
+        None => Err(VarError::NotPresent),
+    }
+}
+
+/// Same as [`std::env::var_os`], but checked against the active scopes.
+pub fn var_os<K: AsRef<OsStr>>(key: K) -> Option<OsString> {
+    let key = key.as_ref();
+    on_read(key);
+
+    #[cfg(feature = "tokio")]
+    if let Some(value) = crate::task_local::lookup(key) {
+        return value;
+    }
+    match virtual_env::lookup(key) {
+        Some(value) => value,
+        None => sys::var_os(key),
+    }
+}
+
+/// Sets a variable while holding the lock used by `#[temp_env_vars]`.
+///
+/// With Rust 2024, `std::env::set_var` is `unsafe`, as other threads might read
+/// or write the environment at the same time, e.g. through `getenv` in C code.
+/// This function is safe to call as long as all other code touching the
+/// environment in the process is coordinated through the lock of this crate,
+/// which is the case for tests using `#[temp_env_vars]`, [`crate::env_lock`] or
+/// the functions of this crate. Code running outside of that (e.g. a background
+/// thread reading the environment without the lock) still violates the contract.
+///
+/// Within a task-local `ScopedEnv` or a [`crate::VirtualEnvScope`], only the
+/// overlay is changed.
+///
+/// # Panics
+///
+/// Panics under the same conditions as `std::env::set_var`, if the current
+/// thread holds the lock shared, e.g. within a `#[temp_env_vars(read_only)]` test,
+/// or if an active scope of the current thread was frozen with
+/// [`crate::TempEnvScope::freeze`].
+pub fn set_var<K: AsRef<OsStr>, V: AsRef<OsStr>>(key: K, value: V) {
+    let (key, value) = (key.as_ref(), value.as_ref());
+    registry::check_not_frozen(key);
+    if !update_overlay(key, Some(value)) {
+        let _lock = lock::env_lock();
+        sys::set_var(key, value);
+    }
+}
+
+/// Removes a variable while holding the lock used by `#[temp_env_vars]`.
+///
+/// The same safety contract as for [`set_var`] applies.
+pub fn remove_var<K: AsRef<OsStr>>(key: K) {
+    let key = key.as_ref();
+    registry::check_not_frozen(key);
+    if !update_overlay(key, None) {
+        let _lock = lock::env_lock();
+        sys::remove_var(key);
+    }
+}
+
+/// Returns all variables as seen through [`var_os`], including the overlays.
+pub(crate) fn effective_vars() -> HashMap<OsString, OsString> {
+    let mut vars = sys::vars_os().collect::<HashMap<_, _>>();
+    virtual_env::apply_overlay(&mut vars);
+    #[cfg(feature = "tokio")]
+    crate::task_local::apply_overlay(&mut vars);
+    vars
+}
+
+/// Writes into the innermost overlay, if any.
+fn update_overlay(key: &OsStr, value: Option<&OsStr>) -> bool {
+    #[cfg(feature = "tokio")]
+    if crate::task_local::update(key, value) {
+        return true;
+    }
+    virtual_env::update(key, value)
+}
+
+fn on_read(key: &OsStr) {
+    let scopes = registry::owned_scopes();
+    let key = key.to_string_lossy();
+
+    for scope in &scopes {
+        let mut data = scope.data();
+        if data.track_reads {
+            data.reads.insert(key.to_string());
+        }
+        if data.declared.contains(key.as_ref()) {
+            data.used.insert(key.to_string());
+        }
+    }
+
+    let Some(strict) = scopes.iter().find(|scope| scope.data().strict) else {
+        return;
+    };
+
+    let declared = scopes
+        .iter()
+        .any(|scope| scope.data().declared.contains(key.as_ref()));
+    if !declared {
+        #[cfg(feature = "ci_annotations")]
+        crate::annotation::Annotation {
+            level: crate::annotation::Level::Error,
+            kind: "strict-mode",
+            vars: &[key.to_string()],
+            message: &format!("read of undeclared environment variable \"{key}\""),
+        }
+        .emit();
+        panic!("temp_env_vars: read of undeclared environment variable \"{key}\" in strict mode of scope {strict}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, EqualityAssertion, ResultAssertion};
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "cannot change \"ACCESS_FROZEN\"")]
+    fn test_writes_fail_while_a_scope_is_frozen() {
+        let env_scope = TempEnvScope::new();
+        env_scope.freeze();
+
+        super::set_var("ACCESS_FROZEN", "1");
+    }
+
+    #[test]
+    #[serial]
+    fn test_writes_are_restored_by_the_scope() {
+        std::env::remove_var("ACCESS_WRITE");
+
+        {
+            let _env_scope = TempEnvScope::new();
+            super::set_var("ACCESS_WRITE", "1");
+            assert_that!(super::var("ACCESS_WRITE")).has_ok("1".to_string());
+            super::remove_var("ACCESS_WRITE");
+            assert_that!(super::var("ACCESS_WRITE")).is_err();
+            super::set_var("ACCESS_WRITE", "2");
+        }
+
+        assert_that!(std::env::var("ACCESS_WRITE")).is_err();
+    }
+
+    #[test]
+    #[serial]
+    fn test_writes_can_be_done_while_holding_the_lock() {
+        let _lock = crate::env_lock();
+        let _env_scope = TempEnvScope::new();
+
+        super::set_var("ACCESS_WRITE", "1");
+
+        assert_that!(std::env::var("ACCESS_WRITE")).has_ok("1".to_string());
+    }
+
+    #[test]
+    #[serial]
+    fn test_undeclared_reads_are_allowed_without_strict_mode() {
+        let _env_scope = TempEnvScope::new();
+        std::env::set_var("ACCESS_FOO", "1");
+
+        assert_that!(super::var("ACCESS_FOO")).has_ok("1".to_string());
+    }
+
+    #[test]
+    #[serial]
+    fn test_strict_scopes_of_other_threads_are_ignored() {
+        let (created, created_rx) = std::sync::mpsc::channel();
+        let (done, done_rx) = std::sync::mpsc::channel::<()>();
+        let other = std::thread::spawn(move || {
+            let env_scope = TempEnvScope::journaled();
+            env_scope.track_reads();
+            env_scope.strict();
+            created.send(()).unwrap();
+            done_rx.recv().unwrap();
+            env_scope.reads()
+        });
+        created_rx.recv().unwrap();
+
+        let read = super::var("ACCESS_OTHER_THREAD");
+        done.send(()).unwrap();
+
+        assert_that!(read).is_err();
+        assert_that!(other.join().unwrap().len()).is_equal_to(0);
+    }
+
+    #[test]
+    #[serial]
+    fn test_frozen_scopes_of_other_threads_are_ignored() {
+        let (created, created_rx) = std::sync::mpsc::channel();
+        let (done, done_rx) = std::sync::mpsc::channel::<()>();
+        let other = std::thread::spawn(move || {
+            let env_scope = TempEnvScope::journaled();
+            env_scope.freeze();
+            created.send(()).unwrap();
+            done_rx.recv().unwrap();
+        });
+        created_rx.recv().unwrap();
+
+        super::set_var("ACCESS_OTHER_FROZEN", "1");
+        super::remove_var("ACCESS_OTHER_FROZEN");
+        done.send(()).unwrap();
+
+        assert_that!(other.join().is_ok()).is_equal_to(true);
+    }
+
+    #[test]
+    #[serial]
+    fn test_declared_reads_are_allowed_in_strict_mode() {
+        let env_scope = TempEnvScope::new();
+        env_scope.declare(["ACCESS_FOO"]);
+        env_scope.strict();
+        std::env::set_var("ACCESS_FOO", "1");
+
+        assert_that!(super::var("ACCESS_FOO")).has_ok("1".to_string());
+        assert_that!(super::var_os("ACCESS_FOO")).is_equal_to(Some("1".into()));
+    }
+
+    #[test]
+    #[serial]
+    fn test_unused_declarations_are_listed() {
+        let env_scope = TempEnvScope::new();
+        env_scope.declare(["ACCESS_USED", "ACCESS_UNUSED_B", "ACCESS_UNUSED_A"]);
+
+        let _ = super::var("ACCESS_USED");
+        let _ = std::env::var("ACCESS_UNUSED_A");
+
+        assert_that!(env_scope.unused_declarations()).is_equal_to(vec![
+            "ACCESS_UNUSED_A".to_string(),
+            "ACCESS_UNUSED_B".to_string(),
+        ]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_reads_are_tracked_when_enabled() {
+        let env_scope = TempEnvScope::new();
+        env_scope.track_reads();
+
+        let _ = super::var("ACCESS_READ_B");
+        let _ = super::var_os("ACCESS_READ_A");
+        let _ = std::env::var("ACCESS_READ_NOT_TRACKED");
+
+        assert_that!(env_scope.reads()).is_equal_to(vec![
+            "ACCESS_READ_A".to_string(),
+            "ACCESS_READ_B".to_string(),
+        ]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_reads_are_not_tracked_by_default() {
+        let env_scope = TempEnvScope::new();
+
+        let _ = super::var("ACCESS_READ_A");
+
+        assert_that!(env_scope.reads()).is_equal_to(Vec::<String>::new());
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "undeclared environment variable \"ACCESS_BAR\"")]
+    fn test_undeclared_reads_panic_in_strict_mode() {
+        let env_scope = TempEnvScope::new();
+        env_scope.declare(["ACCESS_FOO"]);
+        env_scope.strict();
+
+        let _ = super::var("ACCESS_BAR");
+    }
+}
@@ -0,0 +1,113 @@
+//! Annotations for CI systems, with the `ci_annotations` feature.
+//!
+//! Problems are written directly to stdout, bypassing the output capturing of
+//! the test harness, so they show up in the CI log even for passing tests. On
+//! GitHub Actions, `::warning`/`::error` workflow commands are written, which
+//! show up in the checks of a pull request. Elsewhere, a line like
+//! `temp_env_vars::annotation level=error kind=strict-mode test=... vars=FOO,BAR message=...`
+//! is written, which can be picked up by other tools.
+
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Level {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Annotation<'a> {
+    pub(crate) level: Level,
+    /// What kind of problem was detected, e.g. `leak`.
+    pub(crate) kind: &'a str,
+    pub(crate) vars: &'a [String],
+    pub(crate) message: &'a str,
+}
+
+impl Annotation<'_> {
+    /// Writes the annotation for the current test.
+    pub(crate) fn emit(&self) {
+        let github = crate::sys::var_os("GITHUB_ACTIONS").is_some_and(|value| value == "true");
+        let thread = std::thread::current();
+        let line = self.format(thread.name().unwrap_or("unknown"), github);
+        let _ = writeln!(std::io::stdout().lock(), "{line}");
+    }
+
+    fn format(&self, test: &str, github: bool) -> String {
+        let level = match self.level {
+            Level::Warning => "warning",
+            Level::Error => "error",
+        };
+        if github {
+            format!(
+                "::{level} title={}::{}",
+                escape_property(&format!("temp_env_vars {}", self.kind)),
+                escape_data(&format!("{test}: {}", self.message))
+            )
+        } else {
+            format!(
+                "temp_env_vars::annotation level={level} kind={} test={test} vars={} message={:?}",
+                self.kind,
+                self.vars.join(","),
+                self.message
+            )
+        }
+    }
+}
+
+/// Escapes the message of a workflow command.
+fn escape_data(text: &str) -> String {
+    text.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Escapes the value of a workflow command property, like `title=` or `file=`,
+/// where `:` and `,` separate the properties.
+fn escape_property(text: &str) -> String {
+    escape_data(text).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, EqualityAssertion};
+
+    use super::{escape_property, Annotation, Level};
+
+    fn annotation() -> Annotation<'static> {
+        const VARS: &[String] = &[];
+        Annotation {
+            level: Level::Error,
+            kind: "strict-mode",
+            vars: VARS,
+            message: "read of \"FOO\"\n100%",
+        }
+    }
+
+    #[test]
+    fn test_github_annotations_are_escaped() {
+        assert_that!(annotation().format("tests::test_some", true)).is_equal_to(
+            "::error title=temp_env_vars strict-mode::tests::test_some: read of \"FOO\"%0A100%25"
+                .to_string(),
+        );
+    }
+
+    #[test]
+    fn test_github_properties_escape_separators() {
+        assert_that!(escape_property("a:b,c%\n")).is_equal_to("a%3Ab%2Cc%25%0A".to_string());
+    }
+
+    #[test]
+    fn test_generic_annotations_are_single_lines() {
+        let vars = ["FOO".to_string(), "BAR".to_string()];
+        let annotation = Annotation {
+            vars: &vars,
+            ..annotation()
+        };
+
+        assert_that!(annotation.format("test_some", false)).is_equal_to(
+            "temp_env_vars::annotation level=error kind=strict-mode test=test_some vars=FOO,BAR message=\"read of \\\"FOO\\\"\\n100%\""
+                .to_string(),
+        );
+    }
+}
@@ -0,0 +1,261 @@
+//! Pluggable storage of the environment, for targets without a usable process
+//! environment.
+
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    sync::{Mutex, MutexGuard, OnceLock, PoisonError},
+};
+
+#[cfg(windows)]
+mod win32;
+
+#[cfg(windows)]
+pub use win32::Win32Backend;
+
+static BACKEND: OnceLock<Box<dyn EnvBackend>> = OnceLock::new();
+
+/// Storage of the environment used by this crate.
+///
+/// All reads and writes of the environment by this crate, its scopes and the
+/// `#[temp_env_vars]` macro go through the installed backend. Names and values
+/// passed to [`EnvBackend::set_var`] are validated before.
+pub trait EnvBackend: Send + Sync {
+    /// Returns the value of a variable.
+    fn var_os(&self, key: &OsStr) -> Option<OsString>;
+
+    /// Returns all variables.
+    fn vars_os(&self) -> Vec<(OsString, OsString)>;
+
+    /// Sets a variable.
+    fn set_var(&self, key: &OsStr, value: &OsStr);
+
+    /// Removes a variable.
+    fn remove_var(&self, key: &OsStr);
+}
+
+/// The process environment, accessed through `std::env`. This is the default
+/// on all targets with an operating system.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdBackend;
+
+impl EnvBackend for StdBackend {
+    fn var_os(&self, key: &OsStr) -> Option<OsString> {
+        std::env::var_os(key)
+    }
+
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        std::env::vars_os().collect()
+    }
+
+    /// Sets the variable through `std::env`, keeping empty values distinguishable
+    /// from unset variables.
+    ///
+    /// On Windows, empty values are set directly through Win32, except under
+    /// Miri, which cannot call Win32.
+    fn set_var(&self, key: &OsStr, value: &OsStr) {
+        #[cfg(all(windows, not(miri)))]
+        if value.is_empty() {
+            win32::set_empty_var(key);
+            return;
+        }
+        std::env::set_var(key, value);
+    }
+
+    fn remove_var(&self, key: &OsStr) {
+        std::env::remove_var(key);
+    }
+}
+
+/// An environment kept in memory. This is the default on wasm targets without
+/// an operating system, like `wasm32-unknown-unknown`.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    vars: Mutex<BTreeMap<OsString, OsString>>,
+}
+
+impl MemoryBackend {
+    /// Creates an empty environment.
+    pub fn new() -> MemoryBackend {
+        MemoryBackend::default()
+    }
+
+    fn vars(&self) -> MutexGuard<'_, BTreeMap<OsString, OsString>> {
+        self.vars.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl EnvBackend for MemoryBackend {
+    fn var_os(&self, key: &OsStr) -> Option<OsString> {
+        self.vars().get(key).cloned()
+    }
+
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        self.vars()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    fn set_var(&self, key: &OsStr, value: &OsStr) {
+        self.vars().insert(key.to_os_string(), value.to_os_string());
+    }
+
+    fn remove_var(&self, key: &OsStr) {
+        self.vars().remove(key);
+    }
+}
+
+/// The process environment, changed directly through `setenv` and `unsetenv`
+/// of the C library and read through `getenv`.
+///
+/// C libraries reading the environment with `getenv` or through `environ` see
+/// every change and every restore of a scope as soon as the call returns, like
+/// with [`StdBackend`]. This backend skips the standard library and its lock,
+/// so it is only sound if nothing else accesses the environment concurrently,
+/// see [`LibcBackend::new`].
+///
+/// ```rust,no_run
+/// // SAFETY: The tests run with `--test-threads=1` and start no threads
+/// temp_env_vars::set_backend(unsafe { temp_env_vars::LibcBackend::new() });
+/// ```
+#[cfg(all(unix, feature = "libc"))]
+#[derive(Debug, Clone, Copy)]
+pub struct LibcBackend {
+    _private: (),
+}
+
+#[cfg(all(unix, feature = "libc"))]
+impl LibcBackend {
+    /// Creates the backend, to be installed with [`set_backend`].
+    ///
+    /// # Safety
+    ///
+    /// While the backend is installed, no thread may read or change the
+    /// environment while another thread changes it through the backend. Neither
+    /// the lock of the standard library nor the lock of this crate cover all
+    /// accesses, e.g. `std::env` or C functions called without holding
+    /// [`crate::env_lock`], and `setenv` racing with them is undefined behavior.
+    /// E.g. run the tests with `--test-threads=1` and without threads accessing
+    /// the environment.
+    pub unsafe fn new() -> LibcBackend {
+        LibcBackend { _private: () }
+    }
+}
+
+#[cfg(all(unix, feature = "libc"))]
+impl EnvBackend for LibcBackend {
+    fn var_os(&self, key: &OsStr) -> Option<OsString> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let key = std::ffi::CString::new(key.as_bytes()).ok()?;
+        // SAFETY: The name is NUL terminated. The returned value is copied right
+        // away, and the caller of `LibcBackend::new` guarantees that no other
+        // thread changes the environment in between.
+        unsafe {
+            let value = libc::getenv(key.as_ptr());
+            (!value.is_null())
+                .then(|| OsStr::from_bytes(std::ffi::CStr::from_ptr(value).to_bytes()).to_owned())
+        }
+    }
+
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        std::env::vars_os().collect()
+    }
+
+    fn set_var(&self, key: &OsStr, value: &OsStr) {
+        let (key_c, value_c) = (c_string(key), c_string(value));
+        // SAFETY: Both strings are NUL terminated and copied by `setenv`. The
+        // caller of `LibcBackend::new` guarantees that no other thread accesses
+        // the environment concurrently.
+        if unsafe { libc::setenv(key_c.as_ptr(), value_c.as_ptr(), 1) } != 0 {
+            panic!(
+                "temp_env_vars: cannot set {key:?}: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
+    fn remove_var(&self, key: &OsStr) {
+        let key_c = c_string(key);
+        // SAFETY: The name is NUL terminated. The caller of `LibcBackend::new`
+        // guarantees that no other thread accesses the environment concurrently.
+        if unsafe { libc::unsetenv(key_c.as_ptr()) } != 0 {
+            panic!(
+                "temp_env_vars: cannot remove {key:?}: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+/// Converts validated names and values, which never contain NUL.
+#[cfg(all(unix, feature = "libc"))]
+fn c_string(value: &OsStr) -> std::ffi::CString {
+    use std::os::unix::ffi::OsStrExt;
+
+    std::ffi::CString::new(value.as_bytes())
+        .unwrap_or_else(|_| panic!("temp_env_vars: {value:?} contains NUL"))
+}
+
+/// Installs the storage of the environment, e.g. a key-value store of an
+/// embedded system. Must be called before this crate accesses the environment
+/// for the first time.
+///
+/// ```rust
+/// use temp_env_vars::{EnvBackend, MemoryBackend};
+///
+/// temp_env_vars::set_backend(MemoryBackend::new());
+///
+/// let env_scope = temp_env_vars::TempEnvScope::new();
+/// env_scope.set("IN_MEMORY", "1");
+/// assert_eq!(temp_env_vars::var("IN_MEMORY").unwrap(), "1");
+/// assert!(std::env::var("IN_MEMORY").is_err());
+/// ```
+///
+/// # Panics
+///
+/// Panics if a backend was installed before, or if the environment was already
+/// accessed through the default backend.
+pub fn set_backend(backend: impl EnvBackend + 'static) {
+    if BACKEND.set(Box::new(backend)).is_err() {
+        panic!("temp_env_vars: the backend must be set before the environment is used");
+    }
+}
+
+/// Returns the installed backend, or installs the default one.
+pub(crate) fn backend() -> &'static dyn EnvBackend {
+    BACKEND.get_or_init(default_backend).as_ref()
+}
+
+#[cfg(all(target_family = "wasm", target_os = "unknown"))]
+fn default_backend() -> Box<dyn EnvBackend> {
+    Box::new(MemoryBackend::new())
+}
+
+#[cfg(not(all(target_family = "wasm", target_os = "unknown")))]
+fn default_backend() -> Box<dyn EnvBackend> {
+    Box::new(StdBackend)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{OsStr, OsString};
+
+    use assertor::{assert_that, EqualityAssertion, OptionAssertion};
+
+    use super::{EnvBackend, MemoryBackend};
+
+    #[test]
+    fn test_memory_backend_keeps_vars_in_memory() {
+        let backend = MemoryBackend::new();
+        backend.set_var(OsStr::new("MEMORY_VAR"), OsStr::new("1"));
+
+        assert_that!(backend.var_os(OsStr::new("MEMORY_VAR"))).has_value(OsString::from("1"));
+        assert_that!(std::env::var_os("MEMORY_VAR")).is_none();
+        assert_that!(backend.vars_os().len()).is_equal_to(1);
+
+        backend.remove_var(OsStr::new("MEMORY_VAR"));
+        assert_that!(backend.var_os(OsStr::new("MEMORY_VAR"))).is_none();
+    }
+}
@@ -0,0 +1,214 @@
+//! The process environment, accessed directly through Win32.
+
+use std::{
+    ffi::{OsStr, OsString},
+    os::windows::ffi::{OsStrExt, OsStringExt},
+    ptr,
+};
+
+use windows_sys::Win32::{
+    Foundation::{GetLastError, SetLastError, ERROR_ENVVAR_NOT_FOUND},
+    System::Environment::{
+        FreeEnvironmentStringsW, GetEnvironmentStringsW, GetEnvironmentVariableW,
+        SetEnvironmentVariableW,
+    },
+};
+
+use super::EnvBackend;
+
+/// The process environment, accessed directly through `GetEnvironmentVariableW`,
+/// `GetEnvironmentStringsW` and `SetEnvironmentVariableW` on Windows.
+///
+/// Empty values are kept as empty values instead of removing the variable,
+/// values up to the Win32 limit of 32767 characters are supported, and names
+/// keep the casing they were first set with. Restoring a scope with this
+/// backend results in exactly the Win32 view of the environment from before.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Win32Backend;
+
+impl EnvBackend for Win32Backend {
+    fn var_os(&self, key: &OsStr) -> Option<OsString> {
+        var_os(&wide(key))
+    }
+
+    fn vars_os(&self) -> Vec<(OsString, OsString)> {
+        let mut vars = Vec::new();
+        // SAFETY: The block is a sequence of NUL terminated strings ending with an
+        // empty string, which is freed after all strings were copied
+        unsafe {
+            let block = GetEnvironmentStringsW();
+            if block.is_null() {
+                return vars;
+            }
+            let mut entry = block;
+            loop {
+                let mut len = 0;
+                while *entry.add(len) != 0 {
+                    len += 1;
+                }
+                if len == 0 {
+                    break;
+                }
+                let var = std::slice::from_raw_parts(entry, len);
+                // Names of hidden variables like `=C:` start with `=`
+                if let Some(pos) = var[1..].iter().position(|c| *c == u16::from(b'=')) {
+                    vars.push((
+                        OsString::from_wide(&var[..=pos]),
+                        OsString::from_wide(&var[pos + 2..]),
+                    ));
+                }
+                entry = entry.add(len + 1);
+            }
+            FreeEnvironmentStringsW(block);
+        }
+        vars
+    }
+
+    fn set_var(&self, key: &OsStr, value: &OsStr) {
+        let (name, value_w) = (wide(key), wide(value));
+        // SAFETY: Both strings are NUL terminated and outlive the call
+        if unsafe { SetEnvironmentVariableW(name.as_ptr(), value_w.as_ptr()) } == 0 {
+            panic!(
+                "temp_env_vars: cannot set {key:?}: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+
+    fn remove_var(&self, key: &OsStr) {
+        let name = wide(key);
+        // SAFETY: The name is NUL terminated, a null value removes the variable.
+        // The last error is cleared before, so only an error of this call is read.
+        let (removed, error) = unsafe {
+            SetLastError(0);
+            let removed = SetEnvironmentVariableW(name.as_ptr(), ptr::null()) != 0;
+            (removed, GetLastError())
+        };
+        if !removed && error != ERROR_ENVVAR_NOT_FOUND {
+            panic!(
+                "temp_env_vars: cannot remove {key:?}: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+    }
+}
+
+/// Converts to a NUL terminated wide string.
+fn wide(value: &OsStr) -> Vec<u16> {
+    value.encode_wide().chain(Some(0)).collect()
+}
+
+/// Reads a variable through `GetEnvironmentVariableW`, telling empty values
+/// apart from missing variables.
+fn var_os(name: &[u16]) -> Option<OsString> {
+    let mut buffer: Vec<u16> = Vec::new();
+    loop {
+        // SAFETY: The name is NUL terminated and the buffer has the given
+        // size. A successful read of an empty value doesn't reset the last
+        // error, so it is cleared before.
+        let (len, error) = unsafe {
+            SetLastError(0);
+            let len =
+                GetEnvironmentVariableW(name.as_ptr(), buffer.as_mut_ptr(), buffer.len() as u32);
+            (len, GetLastError())
+        };
+        if len == 0 {
+            return match error {
+                ERROR_ENVVAR_NOT_FOUND => None,
+                _ => Some(OsString::new()),
+            };
+        }
+        let len = len as usize;
+        if len < buffer.len() {
+            return Some(OsString::from_wide(&buffer[..len]));
+        }
+        // Too small, `len` is the size needed including the NUL
+        buffer.resize(len, 0);
+    }
+}
+
+/// Sets an empty value through Win32 for [`super::StdBackend`], as some ways of
+/// setting an empty value remove the variable instead. Panics if the variable
+/// still doesn't exist afterwards, instead of silently losing it.
+#[cfg(not(miri))]
+pub(crate) fn set_empty_var(key: &OsStr) {
+    let name = wide(key);
+    let empty: [u16; 1] = [0];
+
+    // SAFETY: Both strings are NUL terminated and outlive the call
+    unsafe { SetEnvironmentVariableW(name.as_ptr(), empty.as_ptr()) };
+    if var_os(&name).is_none() {
+        panic!("temp_env_vars: cannot set {key:?} to an empty value on this platform");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{OsStr, OsString};
+
+    use assertor::{assert_that, BooleanAssertion, EqualityAssertion, OptionAssertion};
+    use serial_test::serial;
+
+    use super::Win32Backend;
+    use crate::{EnvBackend, TempEnvScope};
+
+    fn var(key: &str) -> Option<OsString> {
+        Win32Backend.var_os(OsStr::new(key))
+    }
+
+    #[test]
+    #[serial]
+    fn test_empty_and_long_values_are_kept() {
+        let long = "x".repeat(32_000);
+        Win32Backend.set_var(OsStr::new("WIN32_EMPTY"), OsStr::new(""));
+        Win32Backend.set_var(OsStr::new("WIN32_LONG"), OsStr::new(&long));
+
+        assert_that!(var("WIN32_EMPTY")).has_value(OsString::new());
+        assert_that!(var("WIN32_LONG")).has_value(OsString::from(long));
+
+        Win32Backend.remove_var(OsStr::new("WIN32_EMPTY"));
+        Win32Backend.remove_var(OsStr::new("WIN32_LONG"));
+        assert_that!(var("WIN32_EMPTY")).is_none();
+    }
+
+    #[test]
+    #[serial]
+    fn test_stale_errors_dont_hide_empty_values() {
+        Win32Backend.set_var(OsStr::new("WIN32_STALE"), OsStr::new(""));
+        // Fails with `ERROR_ENVVAR_NOT_FOUND`, which stays the last error
+        Win32Backend.remove_var(OsStr::new("WIN32_MISSING"));
+
+        assert_that!(var("WIN32_STALE")).has_value(OsString::new());
+
+        Win32Backend.remove_var(OsStr::new("WIN32_STALE"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_names_keep_their_casing() {
+        Win32Backend.set_var(OsStr::new("Win32_Cased"), OsStr::new("1"));
+
+        let names: Vec<OsString> = Win32Backend.vars_os().into_iter().map(|(k, _)| k).collect();
+        assert_that!(names.contains(&OsString::from("Win32_Cased"))).is_true();
+        assert_that!(var("WIN32_CASED")).has_value(OsString::from("1"));
+
+        Win32Backend.remove_var(OsStr::new("Win32_Cased"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_restored_environment_matches_the_win32_view() {
+        Win32Backend.set_var(OsStr::new("WIN32_RESTORED"), OsStr::new(""));
+        let before = Win32Backend.vars_os();
+
+        {
+            let env_scope = TempEnvScope::new();
+            env_scope.set("WIN32_RESTORED", "changed");
+            env_scope.set("WIN32_ADDED", "");
+        }
+
+        assert_that!(Win32Backend.vars_os()).is_equal_to(before);
+        assert_that!(var("WIN32_RESTORED")).has_value(OsString::new());
+        Win32Backend.remove_var(OsStr::new("WIN32_RESTORED"));
+    }
+}
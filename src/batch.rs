@@ -0,0 +1,98 @@
+//! Changes applied as one unit, which other code holding the lock never sees
+//! partially applied.
+
+use std::ffi::{OsStr, OsString};
+
+use crate::{lock::env_lock, TempEnvScope};
+
+/// Changes collected by [`atomically`].
+#[derive(Debug, Default)]
+pub struct EnvBatch {
+    changes: Vec<(OsString, Option<OsString>)>,
+}
+
+impl EnvBatch {
+    /// Sets a variable when the batch is applied.
+    pub fn set(&mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> &mut EnvBatch {
+        self.changes.push((
+            key.as_ref().to_os_string(),
+            Some(value.as_ref().to_os_string()),
+        ));
+        self
+    }
+
+    /// Removes a variable when the batch is applied.
+    pub fn remove(&mut self, key: impl AsRef<OsStr>) -> &mut EnvBatch {
+        self.changes.push((key.as_ref().to_os_string(), None));
+        self
+    }
+}
+
+/// Collects the changes made to the batch by the closure and applies all of them
+/// while holding the lock once. Code holding the lock, like other tests using
+/// this crate, either sees none or all of the changes.
+///
+/// The returned scope restores the whole batch when it is dropped.
+///
+/// ```rust
+/// let env_scope = temp_env_vars::atomically(|batch| {
+///     batch.set("DB_HOST", "localhost").set("DB_PORT", "5432");
+///     batch.remove("DB_PASSWORD");
+/// });
+///
+/// assert_eq!(std::env::var("DB_PORT").unwrap(), "5432");
+/// drop(env_scope);
+/// assert!(std::env::var("DB_PORT").is_err());
+/// ```
+///
+/// # Panics
+///
+/// Panics if a name or value is invalid, before changing anything.
+#[track_caller]
+pub fn atomically(f: impl FnOnce(&mut EnvBatch)) -> TempEnvScope {
+    let mut batch = EnvBatch::default();
+    f(&mut batch);
+    let _lock = env_lock();
+    let scope = TempEnvScope::new();
+    scope.apply(batch.changes);
+    scope
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, ResultAssertion};
+    use serial_test::serial;
+
+    use super::atomically;
+
+    #[test]
+    #[serial]
+    fn test_batch_is_applied_and_restored_as_unit() {
+        std::env::set_var("BATCH_A", "original");
+        let env_scope = atomically(|batch| {
+            batch.set("BATCH_B", "1").remove("BATCH_A");
+        });
+
+        assert_that!(std::env::var("BATCH_A")).is_err();
+        assert_that!(std::env::var("BATCH_B")).has_ok("1".to_string());
+
+        drop(env_scope);
+
+        assert_that!(std::env::var("BATCH_A")).has_ok("original".to_string());
+        assert_that!(std::env::var("BATCH_B")).is_err();
+        std::env::remove_var("BATCH_A");
+    }
+
+    #[test]
+    #[serial]
+    fn test_invalid_batch_changes_nothing() {
+        let result = std::panic::catch_unwind(|| {
+            atomically(|batch| {
+                batch.set("BATCH_C", "1").set("BATCH=D", "2");
+            })
+        });
+
+        assert!(result.is_err());
+        assert_that!(std::env::var("BATCH_C")).is_err();
+    }
+}
@@ -0,0 +1,85 @@
+//! Benchmarking code paths depending on the environment with `criterion`, with
+//! the `criterion` feature.
+//!
+//! ```rust,no_run
+//! use criterion::Criterion;
+//! use temp_env_vars::bench;
+//!
+//! fn benchmarks(c: &mut Criterion) {
+//!     bench::group_with_env(c, "parse_config/small_pool", [("POOL_SIZE", "4")], |group| {
+//!         group.bench_function("parse", |b| b.iter(|| std::env::var("POOL_SIZE")));
+//!     });
+//! }
+//! ```
+
+use std::ffi::OsStr;
+
+use criterion::{measurement::WallTime, BenchmarkGroup, Criterion};
+
+use crate::{env_lock, TempEnvScope};
+
+/// Runs `f` with the variables set, holding the lock. The environment is
+/// restored afterwards, even if `f` panics.
+///
+/// # Panics
+///
+/// Panics if any of the variables is invalid. None of them is set then.
+pub fn with_env<I, K, V, R>(c: &mut Criterion, vars: I, f: impl FnOnce(&mut Criterion) -> R) -> R
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+{
+    let _lock = env_lock();
+    let env_scope = TempEnvScope::new();
+    if let Err(err) = env_scope.try_set_all(vars) {
+        panic!("temp_env_vars: {err}");
+    }
+    f(c)
+}
+
+/// Runs `f` with a new benchmark group and the variables set, holding the lock.
+/// The group is finished and the environment restored afterwards.
+///
+/// # Panics
+///
+/// Panics if any of the variables is invalid. None of them is set then.
+pub fn group_with_env<I, K, V, R>(
+    c: &mut Criterion,
+    name: &str,
+    vars: I,
+    f: impl FnOnce(&mut BenchmarkGroup<'_, WallTime>) -> R,
+) -> R
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+{
+    with_env(c, vars, |c| {
+        let mut group = c.benchmark_group(name);
+        let result = f(&mut group);
+        group.finish();
+        result
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, ResultAssertion};
+    use criterion::Criterion;
+    use serial_test::serial;
+
+    use super::with_env;
+
+    #[test]
+    #[serial]
+    fn test_vars_are_applied_around_the_benchmarks() {
+        std::env::remove_var("BENCH_VAR");
+        let mut c = Criterion::default();
+
+        let value = with_env(&mut c, [("BENCH_VAR", "1")], |_| std::env::var("BENCH_VAR"));
+
+        assert_that!(value).has_ok("1".to_string());
+        assert_that!(std::env::var("BENCH_VAR")).is_err();
+    }
+}
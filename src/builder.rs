@@ -0,0 +1,215 @@
+//! Building a scope together with its initial changes, all applied while
+//! holding the lock.
+
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+};
+
+use crate::{dotenv, error, lock::env_lock, EnvError, TempEnvScope};
+
+/// Builder for a [`TempEnvScope`] whose initial changes are applied right
+/// after capturing the environment, without another thread being able to
+/// change the environment in between.
+///
+/// The changes are applied in the order of the calls.
+///
+/// ```rust,no_run
+/// use temp_env_vars::TempEnvScope;
+///
+/// let env_scope = TempEnvScope::builder()
+///     .set("DB_HOST", "localhost")
+///     .unset("DB_PASSWORD")
+///     .clear_prefix("AWS_")
+///     .dotenv(".env.test")
+///     .strict()
+///     .named("db")
+///     .build();
+/// ```
+#[derive(Debug, Default)]
+pub struct TempEnvScopeBuilder {
+    name: Option<String>,
+    changes: Vec<Change>,
+    declared: Vec<String>,
+    strict: bool,
+}
+
+#[derive(Debug)]
+enum Change {
+    Set(OsString, OsString),
+    Unset(OsString),
+    ClearPrefix(String),
+    Dotenv(PathBuf),
+}
+
+impl TempEnvScope {
+    /// Returns a builder for a scope with initial changes, see
+    /// [`TempEnvScopeBuilder`].
+    pub fn builder() -> TempEnvScopeBuilder {
+        TempEnvScopeBuilder::default()
+    }
+}
+
+impl TempEnvScopeBuilder {
+    /// Names the scope like [`TempEnvScope::named`].
+    pub fn named(mut self, name: impl Into<String>) -> TempEnvScopeBuilder {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Sets a variable.
+    pub fn set(mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> TempEnvScopeBuilder {
+        self.changes.push(Change::Set(
+            key.as_ref().to_os_string(),
+            value.as_ref().to_os_string(),
+        ));
+        self
+    }
+
+    /// Removes a variable.
+    pub fn unset(mut self, key: impl AsRef<OsStr>) -> TempEnvScopeBuilder {
+        self.changes
+            .push(Change::Unset(key.as_ref().to_os_string()));
+        self
+    }
+
+    /// Removes all variables whose name starts with the prefix, e.g. `AWS_`.
+    pub fn clear_prefix(mut self, prefix: impl Into<String>) -> TempEnvScopeBuilder {
+        self.changes.push(Change::ClearPrefix(prefix.into()));
+        self
+    }
+
+    /// Sets the variables of a `.env` file.
+    pub fn dotenv(mut self, path: impl AsRef<Path>) -> TempEnvScopeBuilder {
+        self.changes
+            .push(Change::Dotenv(path.as_ref().to_path_buf()));
+        self
+    }
+
+    /// Declares variables like [`TempEnvScope::declare`].
+    pub fn declare<I, K>(mut self, keys: I) -> TempEnvScopeBuilder
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        self.declared.extend(keys.into_iter().map(Into::into));
+        self
+    }
+
+    /// Enables the strict mode like [`TempEnvScope::strict`].
+    pub fn strict(mut self) -> TempEnvScopeBuilder {
+        self.strict = true;
+        self
+    }
+
+    /// Captures the environment and applies the changes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a name or value is invalid, a `.env` file cannot be loaded,
+    /// or the environment cannot be captured. Use [`Self::try_build`] to
+    /// handle such errors.
+    #[track_caller]
+    pub fn build(self) -> TempEnvScope {
+        self.try_build()
+            .unwrap_or_else(|err| panic!("temp_env_vars: {err}"))
+    }
+
+    /// Captures the environment and applies the changes. Errors are reported
+    /// before the environment is changed.
+    #[track_caller]
+    pub fn try_build(mut self) -> Result<TempEnvScope, EnvError> {
+        let changes = self.load()?;
+        let _lock = env_lock();
+        let scope = TempEnvScope::capture(self.name)?;
+        for change in changes {
+            match change {
+                Change::Set(key, value) => scope.write(&key, Some(&value)),
+                Change::Unset(key) => scope.write(&key, None),
+                Change::ClearPrefix(prefix) => {
+                    scope.remove_matching(|key| key.starts_with(&prefix))
+                }
+                Change::Dotenv(_) => unreachable!("loaded before"),
+            }
+        }
+        scope.declare(self.declared);
+        if self.strict {
+            scope.strict();
+        }
+        Ok(scope)
+    }
+
+    /// Validates the changes and replaces `.env` files by their variables.
+    fn load(&mut self) -> Result<Vec<Change>, EnvError> {
+        let mut changes = Vec::with_capacity(self.changes.len());
+        for change in std::mem::take(&mut self.changes) {
+            match change {
+                Change::Set(ref key, ref value) => error::validate(key, value)?,
+                Change::Unset(ref key) => error::validate_key(key)?,
+                Change::ClearPrefix(_) => {}
+                Change::Dotenv(path) => {
+                    let vars = std::fs::read_to_string(&path)
+                        .map_err(|err| err.to_string())
+                        .and_then(|content| dotenv::parse(&content))
+                        .map_err(|message| EnvError::Dotenv { path, message })?;
+                    for (key, value) in vars {
+                        error::validate(key.as_ref(), value.as_ref())?;
+                        changes.push(Change::Set(key.into(), value.into()));
+                    }
+                    continue;
+                }
+            }
+            changes.push(change);
+        }
+        Ok(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, OptionAssertion, ResultAssertion};
+    use serial_test::serial;
+
+    use crate::{EnvError, TempEnvScope};
+
+    #[test]
+    #[serial]
+    fn test_build_applies_changes_in_order() {
+        std::env::set_var("BUILDER_A", "original");
+        std::env::set_var("BUILDER_PREFIX_X", "x");
+        {
+            let env_scope = TempEnvScope::builder()
+                .named("builder")
+                .set("BUILDER_B", "1")
+                .unset("BUILDER_A")
+                .set("BUILDER_PREFIX_Y", "y")
+                .clear_prefix("BUILDER_PREFIX_")
+                .set("BUILDER_PREFIX_Z", "z")
+                .build();
+
+            assert_that!(env_scope.name()).has_value("builder");
+            assert_that!(std::env::var("BUILDER_B")).has_ok("1".to_string());
+            assert_that!(std::env::var("BUILDER_A")).is_err();
+            assert_that!(std::env::var("BUILDER_PREFIX_X")).is_err();
+            assert_that!(std::env::var("BUILDER_PREFIX_Y")).is_err();
+            assert_that!(std::env::var("BUILDER_PREFIX_Z")).has_ok("z".to_string());
+        }
+        assert_that!(std::env::var("BUILDER_A")).has_ok("original".to_string());
+        assert_that!(std::env::var("BUILDER_PREFIX_X")).has_ok("x".to_string());
+        assert_that!(std::env::var("BUILDER_B")).is_err();
+        std::env::remove_var("BUILDER_A");
+        std::env::remove_var("BUILDER_PREFIX_X");
+    }
+
+    #[test]
+    #[serial]
+    fn test_try_build_fails_before_changing_the_environment() {
+        let result = TempEnvScope::builder()
+            .set("BUILDER_C", "1")
+            .dotenv("does/not/exist.env")
+            .try_build();
+
+        assert!(matches!(result, Err(EnvError::Dotenv { .. })));
+        assert_that!(std::env::var("BUILDER_C")).is_err();
+    }
+}
@@ -0,0 +1,92 @@
+//! Scopes nested in another scope, for setup, sub-cases and teardown within
+//! one test.
+
+use std::{marker::PhantomData, ops::Deref};
+
+use crate::{Baseline, TempEnvScope};
+
+/// A scope created by [`TempEnvScope::child`], which restores the state of
+/// its parent when dropped and cannot outlive it.
+///
+/// It dereferences to its [`TempEnvScope`].
+#[derive(Debug)]
+pub struct ChildScope<'a> {
+    scope: TempEnvScope,
+    parent: PhantomData<&'a TempEnvScope>,
+}
+
+impl TempEnvScope {
+    /// Creates a scope nested in this one. When the child is dropped, the
+    /// environment gets back to the state of this scope at the time the child
+    /// was created.
+    ///
+    /// The child captures the environment like its parent, sharing all names
+    /// and values not changed since, so it only costs the variables changed in
+    /// between. The child of a [`Self::journaled`] scope is journaled too.
+    ///
+    /// ```rust
+    /// use temp_env_vars::TempEnvScope;
+    ///
+    /// let env_scope = TempEnvScope::new();
+    /// env_scope.set("DB_HOST", "localhost");
+    /// for port in ["5432", "6432"] {
+    ///     let case = env_scope.child();
+    ///     case.set("DB_PORT", port);
+    ///     // ...
+    /// }
+    /// assert!(std::env::var("DB_PORT").is_err());
+    /// ```
+    #[track_caller]
+    pub fn child(&self) -> ChildScope<'_> {
+        let scope = match self.restorer.baseline {
+            Baseline::Snapshot(_) => TempEnvScope::new(),
+            Baseline::Journal => TempEnvScope::journaled(),
+        };
+        ChildScope {
+            scope,
+            parent: PhantomData,
+        }
+    }
+}
+
+impl Deref for ChildScope<'_> {
+    type Target = TempEnvScope;
+
+    fn deref(&self) -> &TempEnvScope {
+        &self.scope
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, ResultAssertion};
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_child_restores_the_state_of_its_parent() {
+        std::env::remove_var("CHILD_A");
+        std::env::remove_var("CHILD_B");
+        for new_scope in [
+            TempEnvScope::new as fn() -> TempEnvScope,
+            TempEnvScope::journaled,
+        ] {
+            let env_scope = new_scope();
+            env_scope.set("CHILD_A", "parent");
+            {
+                let child = env_scope.child();
+                child.set("CHILD_A", "child");
+                child.set("CHILD_B", "child");
+
+                assert_that!(std::env::var("CHILD_A")).has_ok("child".to_string());
+            }
+            assert_that!(std::env::var("CHILD_A")).has_ok("parent".to_string());
+            assert_that!(std::env::var("CHILD_B")).is_err();
+            drop(env_scope);
+
+            assert_that!(std::env::var("CHILD_A")).is_err();
+        }
+    }
+}
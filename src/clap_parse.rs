@@ -0,0 +1,72 @@
+//! Parsing command lines with `clap` against the environment of a scope.
+
+use std::ffi::OsString;
+
+use crate::{lock::env_lock, TempEnvScope};
+
+impl TempEnvScope {
+    /// Parses the command line with `clap` while holding the lock, so
+    /// arguments with `env = "..."` see the variables set through this scope
+    /// and no other test changes them while parsing.
+    ///
+    /// ```rust
+    /// use clap::Parser;
+    ///
+    /// #[derive(Parser)]
+    /// struct Cli {
+    ///     #[arg(long, env = "CLI_PORT")]
+    ///     port: u16,
+    /// }
+    ///
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// env_scope.set("CLI_PORT", "8080");
+    ///
+    /// let cli: Cli = env_scope.parse_with_env(["prog"]).unwrap();
+    /// assert_eq!(cli.port, 8080);
+    /// ```
+    pub fn parse_with_env<C, I, T>(&self, args: I) -> Result<C, clap::Error>
+    where
+        C: clap::Parser,
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let _lock = env_lock();
+        C::try_parse_from(args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, EqualityAssertion};
+    use clap::Parser;
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[derive(Debug, Parser, PartialEq)]
+    struct Cli {
+        #[arg(long, env = "CLAP_LEVEL", default_value = "info")]
+        level: String,
+        #[arg(long, env = "CLAP_VERBOSE")]
+        verbose: bool,
+    }
+
+    #[test]
+    #[serial]
+    fn test_env_of_scope_is_parsed() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("CLAP_LEVEL", "debug");
+        env_scope.set("CLAP_VERBOSE", "true");
+
+        let cli: Cli = env_scope.parse_with_env(["prog"]).unwrap();
+        let overridden: Cli = env_scope
+            .parse_with_env(["prog", "--level", "warn"])
+            .unwrap();
+
+        assert_that!(cli).is_equal_to(Cli {
+            level: "debug".to_string(),
+            verbose: true,
+        });
+        assert_that!(overridden.level).is_equal_to("warn".to_string());
+    }
+}
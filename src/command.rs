@@ -0,0 +1,111 @@
+//! Passing the effective environment to child processes.
+//!
+//! A child started by `std::process::Command` inherits the process environment,
+//! but not the overlays of a [`crate::VirtualEnvScope`] or a task-local
+//! `ScopedEnv`. Applying the environment explicitly gives the child exactly the
+//! variables the test sees through [`crate::var`].
+
+use std::{ffi::OsStr, process::Command};
+
+use crate::{access, TempEnvScope};
+
+/// Extension for commands to start a child with the effective environment.
+pub trait CommandEnvExt {
+    /// Replaces the environment of the child by the variables seen through
+    /// [`crate::var`] at the time of this call, including all overlays.
+    fn apply_scope(&mut self) -> &mut Self;
+}
+
+impl CommandEnvExt for Command {
+    fn apply_scope(&mut self) -> &mut Command {
+        self.env_clear().envs(access::effective_vars())
+    }
+}
+
+/// Within a task-local `ScopedEnv`, apply the environment inside of the task,
+/// so its variables are included.
+#[cfg(feature = "tokio")]
+impl CommandEnvExt for tokio::process::Command {
+    fn apply_scope(&mut self) -> &mut tokio::process::Command {
+        self.env_clear().envs(access::effective_vars())
+    }
+}
+
+impl TempEnvScope {
+    /// Creates a command for the program, whose child gets the effective
+    /// environment as applied by [`CommandEnvExt::apply_scope`].
+    ///
+    /// ```rust
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// env_scope.set("GREETING", "hello");
+    ///
+    /// let command = env_scope.command("my-binary");
+    ///
+    /// let greeting = command.get_envs().find(|(key, _)| *key == "GREETING");
+    /// assert_eq!(greeting, Some(("GREETING".as_ref(), Some("hello".as_ref()))));
+    /// ```
+    pub fn command(&self, program: impl AsRef<OsStr>) -> Command {
+        let mut command = Command::new(program);
+        command.apply_scope();
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ffi::OsStr, process::Command};
+
+    use assertor::{assert_that, EqualityAssertion};
+    use serial_test::serial;
+
+    use super::CommandEnvExt;
+    use crate::{TempEnvScope, VirtualEnvScope};
+
+    fn child_env(command: &Command, key: &str) -> Option<Option<String>> {
+        command
+            .get_envs()
+            .find(|(name, _)| *name == OsStr::new(key))
+            .map(|(_, value)| value.map(|value| value.to_string_lossy().into_owned()))
+    }
+
+    #[test]
+    #[serial]
+    fn test_scope_vars_are_passed_to_the_child() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("COMMAND_VAR", "1");
+
+        let command = env_scope.command("true");
+
+        assert_that!(child_env(&command, "COMMAND_VAR")).is_equal_to(Some(Some("1".to_string())));
+    }
+
+    #[test]
+    #[serial]
+    fn test_virtual_vars_are_passed_to_the_child() {
+        let env_scope = VirtualEnvScope::new();
+        env_scope.set("COMMAND_VIRTUAL", "1");
+
+        let mut command = Command::new("true");
+        command.apply_scope();
+
+        assert_that!(child_env(&command, "COMMAND_VIRTUAL"))
+            .is_equal_to(Some(Some("1".to_string())));
+        assert_that!(std::env::var_os("COMMAND_VIRTUAL")).is_equal_to(None);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_task_local_vars_are_passed_to_async_child() {
+        let command = crate::ScopedEnv::task_local()
+            .set("COMMAND_TASK", "1")
+            .run(async {
+                let mut command = tokio::process::Command::new("true");
+                command.apply_scope();
+                command
+            })
+            .await;
+
+        assert_that!(child_env(command.as_std(), "COMMAND_TASK"))
+            .is_equal_to(Some(Some("1".to_string())));
+    }
+}
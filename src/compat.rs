@@ -0,0 +1,4 @@
+//! Shims matching the APIs of other crates, so projects can migrate to this
+//! crate by changing one import.
+
+pub mod temp_env;
@@ -0,0 +1,110 @@
+//! The API of the `temp-env` crate, built on the lock and scopes of this crate.
+//!
+//! Replace `use temp_env::...` with `use temp_env_vars::compat::temp_env::...`
+//! to migrate. The closures run while holding the lock of this crate, so they
+//! are coordinated with tests using `#[temp_env_vars]`. Unlike `temp-env`, every
+//! change made within the closure is restored afterwards, not only the given
+//! variables.
+//!
+//! ```rust
+//! use temp_env_vars::compat::temp_env;
+//!
+//! temp_env::with_var("COMPAT_VAR", Some("1"), || {
+//!     assert_eq!(std::env::var("COMPAT_VAR").unwrap(), "1");
+//! });
+//! assert!(std::env::var("COMPAT_VAR").is_err());
+//! ```
+
+use std::{ffi::OsStr, hash::Hash};
+
+use crate::{env_lock, TempEnvScope};
+
+/// Sets a variable, or removes it for `None`, while running the closure.
+pub fn with_var<K, V, F, R>(key: K, value: Option<V>, closure: F) -> R
+where
+    K: AsRef<OsStr> + Clone + Eq + Hash,
+    V: AsRef<OsStr> + Clone,
+    F: FnOnce() -> R,
+{
+    with_vars([(key, value)], closure)
+}
+
+/// Removes a variable while running the closure.
+pub fn with_var_unset<K, F, R>(key: K, closure: F) -> R
+where
+    K: AsRef<OsStr> + Clone + Eq + Hash,
+    F: FnOnce() -> R,
+{
+    with_var(key, None::<&str>, closure)
+}
+
+/// Sets variables, or removes them for `None`, while running the closure.
+pub fn with_vars<K, V, F, R>(kvs: impl AsRef<[(K, Option<V>)]>, closure: F) -> R
+where
+    K: AsRef<OsStr> + Clone + Eq + Hash,
+    V: AsRef<OsStr> + Clone,
+    F: FnOnce() -> R,
+{
+    let _lock = env_lock();
+    let env_scope = TempEnvScope::new();
+    for (key, value) in kvs.as_ref() {
+        match value {
+            Some(value) => env_scope.set(key, value),
+            None => env_scope.remove(key),
+        }
+    }
+    closure()
+}
+
+/// Removes variables while running the closure.
+pub fn with_vars_unset<K, F, R>(keys: impl AsRef<[K]>, closure: F) -> R
+where
+    K: AsRef<OsStr> + Clone + Eq + Hash,
+    F: FnOnce() -> R,
+{
+    let kvs: Vec<(K, Option<&str>)> = keys
+        .as_ref()
+        .iter()
+        .map(|key| (key.clone(), None))
+        .collect();
+    with_vars(kvs, closure)
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, EqualityAssertion, ResultAssertion};
+    use serial_test::serial;
+
+    use super::{with_var_unset, with_vars, with_vars_unset};
+
+    #[test]
+    #[serial]
+    fn test_vars_are_restored_after_the_closure() {
+        std::env::set_var("COMPAT_UNSET", "1");
+
+        let result = with_vars([("COMPAT_SET", Some("1")), ("COMPAT_UNSET", None)], || {
+            assert_that!(std::env::var("COMPAT_SET")).has_ok("1".to_string());
+            assert_that!(std::env::var("COMPAT_UNSET")).is_err();
+            with_var_unset("COMPAT_SET", || std::env::var("COMPAT_SET").is_err())
+        });
+
+        assert_that!(result).is_equal_to(true);
+        assert_that!(std::env::var("COMPAT_SET")).is_err();
+        assert_that!(std::env::var("COMPAT_UNSET")).has_ok("1".to_string());
+        std::env::remove_var("COMPAT_UNSET");
+    }
+
+    #[test]
+    #[serial]
+    fn test_vars_are_restored_after_a_panic() {
+        std::env::set_var("COMPAT_PANIC", "1");
+
+        let result = std::panic::catch_unwind(|| {
+            with_vars_unset(["COMPAT_PANIC"], || panic!("failing closure"));
+        });
+
+        assert_that!(result.is_err()).is_equal_to(true);
+        assert_that!(std::env::var("COMPAT_PANIC")).has_ok("1".to_string());
+        std::env::remove_var("COMPAT_PANIC");
+    }
+}
@@ -0,0 +1,94 @@
+//! Sources for the `config` crate backed by captured variables instead of the
+//! process environment.
+
+use config::{Environment, Map};
+
+use crate::{EnvProvider, EnvSnapshot, MockEnv, TempEnvScope};
+
+impl TempEnvScope {
+    /// Returns a `config` source of the variables set by this scope, taken when
+    /// this is called. Building the configuration does not read the process
+    /// environment.
+    ///
+    /// The source is a `config::Environment`, so prefix, separator and parsing
+    /// are configured as usual.
+    ///
+    /// ```rust
+    /// use config::Config;
+    ///
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// env_scope.set("APP_PORT", "8080");
+    ///
+    /// let config = Config::builder()
+    ///     .add_source(env_scope.config_source().prefix("APP").try_parsing(true))
+    ///     .build()
+    ///     .unwrap();
+    /// assert_eq!(config.get::<u16>("port").unwrap(), 8080);
+    /// ```
+    pub fn config_source(&self) -> Environment {
+        source(self.overridden_values())
+    }
+}
+
+impl EnvSnapshot {
+    /// Returns a `config` source of all captured variables which are valid
+    /// unicode.
+    pub fn config_source(&self) -> Environment {
+        source(
+            self.iter()
+                .filter_map(|(key, value)| Some((key.to_str()?.into(), value.to_str()?.into()))),
+        )
+    }
+}
+
+impl MockEnv {
+    /// Returns a `config` source of the variables of this environment, without
+    /// recording them as read.
+    pub fn config_source(&self) -> Environment {
+        source(self.vars())
+    }
+}
+
+fn source(vars: impl IntoIterator<Item = (String, String)>) -> Environment {
+    Environment::default().source(Some(vars.into_iter().collect::<Map<_, _>>()))
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, EqualityAssertion, ResultAssertion};
+    use config::Config;
+    use serde::Deserialize;
+
+    use crate::MockEnv;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Db {
+        url: String,
+        pool: u32,
+    }
+
+    #[test]
+    fn test_mock_env_is_used_as_source() {
+        let env = MockEnv::new()
+            .with("APP_DB__URL", "postgres://localhost")
+            .with("APP_DB__POOL", "4");
+
+        let config = Config::builder()
+            .add_source(
+                env.config_source()
+                    .prefix("APP")
+                    .prefix_separator("_")
+                    .separator("__")
+                    .try_parsing(true),
+            )
+            .build()
+            .unwrap();
+
+        assert_that!(config.get::<Db>("db").unwrap()).is_equal_to(Db {
+            url: "postgres://localhost".to_string(),
+            pool: 4,
+        });
+        assert_that!(env.reads().len()).is_equal_to(0);
+        assert_that!(std::env::var("APP_DB__URL")).is_err();
+    }
+}
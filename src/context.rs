@@ -0,0 +1,139 @@
+//! A test context owning a scope, for `test-context` style setup and teardown.
+
+use std::ops::Deref;
+
+use crate::{env_lock, EnvLockGuard, TempEnvScope};
+
+/// Holds the lock and a scope between [`Self::setup`] and [`Self::teardown`].
+///
+/// With the `test-context` feature, the context can be used with
+/// `#[test_context(EnvContext)]`, see the implementation of `AsyncTestContext`.
+///
+/// The context dereferences to its [`TempEnvScope`].
+#[derive(Debug)]
+pub struct EnvContext {
+    /// Dropped before the guard, so it restores under the lock.
+    scope: TempEnvScope,
+    _lock: EnvLockGuard,
+}
+
+impl EnvContext {
+    /// Acquires the lock and captures the environment.
+    pub fn setup() -> EnvContext {
+        let lock = env_lock();
+        EnvContext {
+            scope: TempEnvScope::new(),
+            _lock: lock,
+        }
+    }
+
+    /// Same as [`Self::setup`], but waits for the lock without blocking the thread.
+    #[cfg(feature = "tokio")]
+    pub async fn setup_async() -> EnvContext {
+        let lock = crate::env_lock_async().await;
+        EnvContext {
+            scope: TempEnvScope::new(),
+            _lock: lock,
+        }
+    }
+
+    /// Restores the environment and releases the lock.
+    pub fn teardown(self) {
+        drop(self);
+    }
+}
+
+/// An `rstest` fixture providing an [`EnvContext`], with the `rstest` feature.
+///
+/// The context holds the lock, so cases using it never run concurrently with
+/// each other or with tests using `#[temp_env_vars]`, even though `rstest`
+/// runs cases in parallel.
+///
+/// ```rust
+/// use rstest::rstest;
+/// use temp_env_vars::{temp_env, EnvContext};
+///
+/// #[rstest]
+/// #[case("8080")]
+/// #[case("9090")]
+/// fn test_port(temp_env: EnvContext, #[case] port: &str) {
+///     temp_env.set("PORT", port);
+///     assert_eq!(std::env::var("PORT").unwrap(), port);
+/// }
+/// ```
+#[cfg(feature = "rstest")]
+#[rstest::fixture]
+pub fn temp_env() -> EnvContext {
+    EnvContext::setup()
+}
+
+/// Sets up the context for `#[test_context(EnvContext)]` tests, with the
+/// `test-context` feature. `test-context` derives `TestContext` from this
+/// implementation, so the context works for sync and async tests.
+///
+/// ```rust
+/// use temp_env_vars::EnvContext;
+/// use test_context::test_context;
+///
+/// #[test_context(EnvContext)]
+/// #[test]
+/// fn test_some(env: &mut EnvContext) {
+///     env.set("FOO", "BAR");
+/// }
+/// ```
+///
+/// With the `tokio` feature, async tests wait for the lock without blocking
+/// the executor.
+#[cfg(feature = "test-context")]
+impl test_context::AsyncTestContext for EnvContext {
+    async fn setup() -> EnvContext {
+        #[cfg(feature = "tokio")]
+        let context = EnvContext::setup_async().await;
+        #[cfg(not(feature = "tokio"))]
+        let context = EnvContext::setup();
+        context
+    }
+
+    async fn teardown(self) {
+        EnvContext::teardown(self);
+    }
+}
+
+impl Deref for EnvContext {
+    type Target = TempEnvScope;
+
+    fn deref(&self) -> &TempEnvScope {
+        &self.scope
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, ResultAssertion};
+    use serial_test::serial;
+
+    use super::EnvContext;
+
+    #[test]
+    #[serial]
+    fn test_context_restores_on_teardown() {
+        std::env::remove_var("CONTEXT_VAR");
+
+        let context = EnvContext::setup();
+        context.set("CONTEXT_VAR", "1");
+        assert_that!(std::env::var("CONTEXT_VAR")).has_ok("1".to_string());
+        context.teardown();
+
+        assert_that!(std::env::var("CONTEXT_VAR")).is_err();
+    }
+
+    #[cfg(feature = "test-context")]
+    #[test_context::test_context(EnvContext)]
+    #[test]
+    #[serial]
+    fn test_context_is_set_up_by_test_context(context: &mut EnvContext) {
+        context.set("CONTEXT_VAR", "1");
+
+        assert_that!(std::env::var("CONTEXT_VAR")).has_ok("1".to_string());
+    }
+}
@@ -0,0 +1,181 @@
+//! A watchdog restoring the environment of hung tests, so they cannot wedge the
+//! rest of the serialized suite.
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    thread::{self, ThreadId},
+    time::{Duration, Instant},
+};
+
+use crate::{lock, restore::Restorer, Baseline, TempEnvScope};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Running,
+    Stopped,
+    Expired,
+}
+
+/// Shared between a scope and its watchdog thread.
+#[derive(Debug)]
+pub(crate) struct Watchdog {
+    status: Mutex<Status>,
+    stopped: Condvar,
+}
+
+impl TempEnvScope {
+    /// Starts a watchdog which restores the environment and releases the lock
+    /// if this scope is still alive after `deadline`, e.g. because the test hangs.
+    ///
+    /// The lock is released if the thread which created the scope holds it
+    /// exclusively, e.g. within a `#[temp_env_vars]` test. The hung test keeps
+    /// running, but other tests can continue. The environment is restored like
+    /// when dropping the scope, so observers, restore callbacks and the
+    /// [`crate::RestorePolicy`] apply. If the scope is dropped after the
+    /// deadline, it panics without restoring the environment again.
+    ///
+    /// # Panics
+    ///
+    /// Panics for [`Self::journaled`] scopes, or if a deadline was set before.
+    pub fn with_deadline(&self, deadline: Duration) {
+        if !matches!(self.restorer.baseline, Baseline::Snapshot(_)) {
+            panic!("temp_env_vars: deadlines are not supported by journaled scopes");
+        }
+        let watchdog = Arc::new(Watchdog {
+            status: Mutex::new(Status::Running),
+            stopped: Condvar::new(),
+        });
+        let mut current = self.watchdog.lock().unwrap_or_else(PoisonError::into_inner);
+        assert!(
+            current.is_none(),
+            "temp_env_vars: the deadline of scope {} was already set",
+            self.state
+        );
+        *current = Some(Arc::clone(&watchdog));
+
+        let restorer = Arc::clone(&self.restorer);
+        let owner = thread::current().id();
+        thread::Builder::new()
+            .name(format!("temp_env_vars watchdog of scope {}", self.state))
+            .spawn(move || watch(&watchdog, deadline, &restorer, owner))
+            .expect("temp_env_vars: cannot start the watchdog thread");
+    }
+
+    /// Stops the watchdog, if any, and returns whether it expired already.
+    pub(crate) fn stop_watchdog(&self) -> bool {
+        let watchdog = self
+            .watchdog
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        let Some(watchdog) = watchdog else {
+            return false;
+        };
+        let mut status = watchdog.status();
+        if *status == Status::Running {
+            *status = Status::Stopped;
+            watchdog.stopped.notify_all();
+        }
+        *status == Status::Expired
+    }
+}
+
+impl Watchdog {
+    fn status(&self) -> std::sync::MutexGuard<'_, Status> {
+        self.status.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+fn watch(watchdog: &Watchdog, deadline: Duration, restorer: &Restorer, owner: ThreadId) {
+    let expires = Instant::now() + deadline;
+    let mut status = watchdog.status();
+    while *status == Status::Running {
+        let now = Instant::now();
+        if now >= expires {
+            // Restore while holding the status, so the scope cannot restore concurrently
+            *status = Status::Expired;
+            let restored = panic::catch_unwind(AssertUnwindSafe(|| {
+                restorer.notify_external_changes();
+                restorer.restore();
+                restorer.run_restore_hooks();
+            }));
+            let status = lock::env_lock_status();
+            let released = lock::release_exclusive_of(owner);
+            eprintln!(
+                "temp_env_vars: scope {} is still alive after its deadline of {deadline:?}, restored the environment{}",
+                restorer.state,
+                if released { format!(" and released the lock ({status})") } else { String::new() }
+            );
+            // A panicking restore policy applies only after the lock was released
+            if let Err(panic) = restored {
+                panic::resume_unwind(panic);
+            }
+            return;
+        }
+        status = watchdog
+            .stopped
+            .wait_timeout(status, expires - now)
+            .unwrap_or_else(PoisonError::into_inner)
+            .0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use assertor::{assert_that, BooleanAssertion, ResultAssertion};
+    use serial_test::serial;
+
+    use crate::{env_lock, TempEnvScope};
+
+    #[test]
+    #[serial]
+    fn test_hung_scope_is_restored_and_releases_the_lock() {
+        let lock = env_lock();
+        let env_scope = TempEnvScope::new();
+        env_scope.with_deadline(Duration::from_millis(50));
+        env_scope.set("DEADLINE_VAR", "1");
+
+        // Another test gets the lock and sees the restored environment
+        let other_test = std::thread::spawn(|| {
+            let _lock = env_lock();
+            std::env::var("DEADLINE_VAR")
+        });
+        assert_that!(other_test.join().unwrap()).is_err();
+
+        let dropped = std::panic::catch_unwind(move || drop(env_scope));
+        drop(lock);
+
+        assert_that!(dropped.is_err()).is_true();
+        assert_that!(std::env::var("DEADLINE_VAR")).is_err();
+    }
+
+    #[test]
+    #[serial]
+    fn test_hung_scope_runs_its_restore_callbacks() {
+        let env_scope = TempEnvScope::new();
+        env_scope.with_deadline(Duration::from_millis(50));
+        env_scope.set("DEADLINE_VAR", "1");
+        let (sender, restored) = std::sync::mpsc::channel();
+        env_scope.on_restore(move || sender.send(std::env::var("DEADLINE_VAR")).unwrap());
+
+        let value = restored.recv_timeout(Duration::from_secs(10)).unwrap();
+
+        assert_that!(value).is_err();
+        assert_that!(std::panic::catch_unwind(move || drop(env_scope)).is_err()).is_true();
+    }
+
+    #[test]
+    #[serial]
+    fn test_scope_dropped_in_time_restores_itself() {
+        let env_scope = TempEnvScope::new();
+        env_scope.with_deadline(Duration::from_secs(60));
+        env_scope.set("DEADLINE_VAR", "1");
+
+        drop(env_scope);
+
+        assert_that!(std::env::var("DEADLINE_VAR")).is_err();
+    }
+}
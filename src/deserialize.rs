@@ -0,0 +1,263 @@
+//! Reading prefixed variables into a deserializable value, with the `serde` feature.
+//!
+//! The inverse of [`TempEnvScope::set_struct`]: `APP_PORT` becomes the field
+//! `port`, `APP_DB__URL` the field `url` of the nested field `db`. Values are
+//! parsed into the type the field expects, sequences are split at `,`.
+
+use std::collections::BTreeMap;
+
+use serde::{
+    de::{
+        self,
+        value::{Error, MapDeserializer, SeqDeserializer},
+        DeserializeOwned, IntoDeserializer, Visitor,
+    },
+    forward_to_deserialize_any,
+};
+
+use crate::{access, EnvError, TempEnvScope};
+
+impl TempEnvScope {
+    /// Deserializes the variables starting with the prefix and `_`, as seen
+    /// through [`crate::var`], so overlays like a [`crate::VirtualEnvScope`]
+    /// are respected.
+    ///
+    /// ```rust
+    /// #[derive(serde::Deserialize, Debug, PartialEq)]
+    /// struct Config {
+    ///     port: u16,
+    ///     hosts: Vec<String>,
+    ///     user: Option<String>,
+    /// }
+    ///
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// env_scope.set("APP_PORT", "8080");
+    /// env_scope.set("APP_HOSTS", "a,b");
+    ///
+    /// let config: Config = env_scope.to_struct("APP").unwrap();
+    ///
+    /// assert_eq!(config, Config { port: 8080, hosts: vec!["a".into(), "b".into()], user: None });
+    /// ```
+    pub fn to_struct<T: DeserializeOwned>(&self, prefix: &str) -> Result<T, EnvError> {
+        let prefix_ = format!("{prefix}_");
+        let mut root = BTreeMap::new();
+        for (key, value) in access::effective_vars() {
+            let (Some(key), Some(value)) = (key.to_str(), value.to_str()) else {
+                continue;
+            };
+            if let Some(path) = key.strip_prefix(&prefix_) {
+                insert(&mut root, path, value.to_string());
+            }
+        }
+
+        T::deserialize(Node::Map(root)).map_err(|err| EnvError::Deserialize {
+            prefix: prefix.to_string(),
+            message: err.to_string(),
+        })
+    }
+}
+
+/// The variables below a prefix, nested at `__`.
+#[derive(Debug)]
+enum Node {
+    Value(String),
+    Map(BTreeMap<String, Node>),
+}
+
+fn insert(map: &mut BTreeMap<String, Node>, path: &str, value: String) {
+    match path.split_once("__") {
+        Some((name, rest)) => {
+            let node = map
+                .entry(name.to_lowercase())
+                .or_insert_with(|| Node::Map(BTreeMap::new()));
+            if let Node::Map(map) = node {
+                insert(map, rest, value);
+            }
+        }
+        None => {
+            map.entry(path.to_lowercase()).or_insert(Node::Value(value));
+        }
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Node {
+    type Deserializer = Node;
+
+    fn into_deserializer(self) -> Node {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                let value = self.value()?;
+                let parsed = value.trim().parse().map_err(|_| {
+                    de::Error::invalid_value(de::Unexpected::Str(&value), &visitor)
+                })?;
+                visitor.$visit(parsed)
+            }
+        )*
+    };
+}
+
+impl Node {
+    fn value(self) -> Result<String, Error> {
+        match self {
+            Node::Value(value) => Ok(value),
+            Node::Map(_) => Err(de::Error::invalid_type(
+                de::Unexpected::Map,
+                &"a single value",
+            )),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Node {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Node::Value(value) => visitor.visit_string(value),
+            Node::Map(map) => visitor.visit_map(MapDeserializer::new(map.into_iter())),
+        }
+    }
+
+    deserialize_parsed!(
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    );
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let value = self.value()?;
+        let items = value
+            .split(',')
+            .filter(|item| !item.is_empty())
+            .map(|item| Node::Value(item.to_string()));
+        visitor.visit_seq(SeqDeserializer::new(items))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.value()?.into_deserializer())
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, BooleanAssertion, ResultAssertion, StringAssertion};
+    use serial_test::serial;
+
+    use crate::{EnvError, TempEnvScope};
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Db {
+        url: String,
+        pool: u32,
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Fast,
+        Safe,
+    }
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Config {
+        port: u16,
+        debug: bool,
+        hosts: Vec<String>,
+        mode: Mode,
+        db: Db,
+        user: Option<String>,
+    }
+
+    #[test]
+    #[serial]
+    fn test_prefixed_vars_are_deserialized() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("DESERIALIZE_PORT", "8080");
+        env_scope.set("DESERIALIZE_DEBUG", "true");
+        env_scope.set("DESERIALIZE_HOSTS", "a,b");
+        env_scope.set("DESERIALIZE_MODE", "fast");
+        env_scope.set("DESERIALIZE_DB__URL", "postgres://localhost");
+        env_scope.set("DESERIALIZE_DB__POOL", "4");
+
+        let config = env_scope.to_struct::<Config>("DESERIALIZE");
+
+        assert_that!(config).has_ok(Config {
+            port: 8080,
+            debug: true,
+            hosts: vec!["a".to_string(), "b".to_string()],
+            mode: Mode::Fast,
+            db: Db {
+                url: "postgres://localhost".to_string(),
+                pool: 4,
+            },
+            user: None,
+        });
+    }
+
+    #[test]
+    #[serial]
+    fn test_invalid_values_are_reported() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("DESERIALIZE_POOL", "many");
+        env_scope.set("DESERIALIZE_URL", "postgres://localhost");
+
+        let error = env_scope.to_struct::<Db>("DESERIALIZE").unwrap_err();
+
+        assert_that!(matches!(error, EnvError::Deserialize { .. })).is_true();
+        assert_that!(error.to_string()).contains("many");
+    }
+
+    #[test]
+    #[serial]
+    fn test_struct_round_trips_through_set_struct() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set_struct(
+            "DESERIALIZE",
+            &serde_json::json!({"url": "postgres://localhost", "pool": 4}),
+        );
+
+        assert_that!(env_scope.to_struct::<Db>("DESERIALIZE")).has_ok(Db {
+            url: "postgres://localhost".to_string(),
+            pool: 4,
+        });
+    }
+}
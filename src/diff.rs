@@ -0,0 +1,137 @@
+//! Differences between two captured environments.
+
+use std::fmt;
+
+use crate::{
+    snapshot::{EnvKey, EnvMap},
+    EnvSnapshot,
+};
+
+/// The variables which differ between two snapshots, see [`diff_env`].
+/// All lists are sorted by name.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct EnvDiff {
+    /// Variables which are only set in the later snapshot.
+    pub added: Vec<String>,
+    /// Variables which are only set in the earlier snapshot.
+    pub removed: Vec<String>,
+    /// Variables which are set in both snapshots, with different values.
+    pub changed: Vec<String>,
+}
+
+impl EnvDiff {
+    /// Compares all variables, including the ones which are never restored,
+    /// like the hidden per-drive working directories of Windows.
+    pub(crate) fn between(before: &EnvMap, after: &EnvMap) -> EnvDiff {
+        let name = |key: &EnvKey| key.as_os_str().to_string_lossy().into_owned();
+
+        let mut diff = EnvDiff::default();
+        for (key, value) in after {
+            match before.get_key_value(key) {
+                None => diff.added.push(name(key)),
+                // A differently-cased name of a variable in both snapshots is a change
+                Some((before_key, before_value))
+                    if before_key.as_os_str() != key.as_os_str() || before_value != value =>
+                {
+                    diff.changed.push(name(key))
+                }
+                Some(_) => {}
+            }
+        }
+        diff.removed = before
+            .keys()
+            .filter(|key| !after.contains_key(*key))
+            .map(name)
+            .collect();
+
+        diff.added.sort();
+        diff.removed.sort();
+        diff.changed.sort();
+        diff
+    }
+
+    /// Returns whether both snapshots are the same.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Lists the names only, so values of secrets never show up.
+impl fmt::Display for EnvDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "added: {:?}, removed: {:?}, changed: {:?}",
+            self.added, self.removed, self.changed
+        )
+    }
+}
+
+/// Compares two snapshots, e.g. taken before and after calling a third-party
+/// initializer, without creating a scope.
+///
+/// ```rust
+/// let env_scope = temp_env_vars::TempEnvScope::new();
+/// let before = temp_env_vars::capture();
+/// env_scope.set("INITIALIZED", "1");
+///
+/// let diff = temp_env_vars::diff_env(&before, &temp_env_vars::capture());
+///
+/// assert_eq!(diff.added, ["INITIALIZED"]);
+/// ```
+pub fn diff_env(before: &EnvSnapshot, after: &EnvSnapshot) -> EnvDiff {
+    EnvDiff::between(&before.vars, &after.vars)
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, BooleanAssertion, EqualityAssertion};
+    use serial_test::serial;
+
+    use super::{diff_env, EnvDiff};
+    use crate::{
+        capture,
+        snapshot::{EnvKey, EnvMap},
+        TempEnvScope,
+    };
+
+    #[test]
+    #[serial]
+    fn test_differences_are_listed() {
+        std::env::set_var("DIFF_REMOVED", "1");
+        std::env::set_var("DIFF_CHANGED", "1");
+        let env_scope = TempEnvScope::new();
+        let before = capture();
+        assert_that!(diff_env(&before, &capture()).is_empty()).is_true();
+
+        env_scope.set("DIFF_ADDED", "1");
+        env_scope.remove("DIFF_REMOVED");
+        env_scope.set("DIFF_CHANGED", "2");
+
+        assert_that!(diff_env(&before, &capture())).is_equal_to(EnvDiff {
+            added: vec!["DIFF_ADDED".to_string()],
+            removed: vec!["DIFF_REMOVED".to_string()],
+            changed: vec!["DIFF_CHANGED".to_string()],
+        });
+        drop(env_scope);
+        std::env::remove_var("DIFF_REMOVED");
+        std::env::remove_var("DIFF_CHANGED");
+    }
+
+    #[test]
+    fn test_unrestorable_variables_are_listed() {
+        let var = |key: &str, value: &str| (EnvKey::new(key), std::ffi::OsStr::new(value).into());
+        let before: EnvMap = [var("=C:", "C:\\"), var("=D:", "D:\\")]
+            .into_iter()
+            .collect();
+        let after: EnvMap = [var("=C:", "C:\\temp"), var("=E:", "E:\\")]
+            .into_iter()
+            .collect();
+
+        assert_that!(EnvDiff::between(&before, &after)).is_equal_to(EnvDiff {
+            added: vec!["=E:".to_string()],
+            removed: vec!["=D:".to_string()],
+            changed: vec!["=C:".to_string()],
+        });
+    }
+}
@@ -0,0 +1,119 @@
+//! A parser for `.env` files.
+//!
+//! Supports `KEY=VALUE` lines with an optional `export ` prefix, comments
+//! starting with `#`, single quoted values taken literally and double quoted
+//! values with the escapes `\n`, `\r`, `\t`, `\"` and `\\`. Values spanning
+//! several lines and variable expansion are not supported.
+
+/// Parses the content of a `.env` file into its variables, in order.
+///
+/// Errors name the line number and what is wrong with the line.
+pub(crate) fn parse(content: &str) -> Result<Vec<(String, String)>, String> {
+    content
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            parse_line(line)
+                .map_err(|err| format!("line {}: {err}", index + 1))
+                .transpose()
+        })
+        .collect()
+}
+
+fn parse_line(line: &str) -> Result<Option<(String, String)>, String> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+    let line = line.strip_prefix("export ").unwrap_or(line);
+    let (key, value) = line
+        .split_once('=')
+        .ok_or_else(|| "expected KEY=VALUE".to_string())?;
+    let key = key.trim();
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return Err(format!("invalid name {key:?}"));
+    }
+
+    let value = value.trim_start();
+    let (value, rest) = if let Some(quoted) = value.strip_prefix('\'') {
+        let end = quoted
+            .find('\'')
+            .ok_or_else(|| "missing closing quote".to_string())?;
+        (quoted[..end].to_string(), &quoted[end + 1..])
+    } else if let Some(quoted) = value.strip_prefix('"') {
+        unescape(quoted)?
+    } else {
+        let end = value.find(" #").unwrap_or(value.len());
+        (value[..end].trim_end().to_string(), "")
+    };
+
+    let rest = rest.trim_start();
+    if !rest.is_empty() && !rest.starts_with('#') {
+        return Err(format!("unexpected {rest:?} after the closing quote"));
+    }
+    Ok(Some((key.to_string(), value)))
+}
+
+/// Unescapes a double quoted value, returning the value and the rest of the
+/// line after the closing quote.
+fn unescape(quoted: &str) -> Result<(String, &str), String> {
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &quoted[index + 1..])),
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some(c @ ('"' | '\\')) => value.push(c),
+                Some(c) => return Err(format!("unknown escape \\{c}")),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err("missing closing quote".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, ResultAssertion};
+
+    use super::parse;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_variables_are_parsed() {
+        let content = r#"
+# comment
+HOST=localhost
+export PORT = 8080
+EMPTY=
+GREETING="hello\n\"world\"" # comment
+RAW='a\nb'
+URL=http://localhost#anchor # comment
+"#;
+
+        assert_that!(parse(content)).has_ok(vars(&[
+            ("HOST", "localhost"),
+            ("PORT", "8080"),
+            ("EMPTY", ""),
+            ("GREETING", "hello\n\"world\""),
+            ("RAW", "a\\nb"),
+            ("URL", "http://localhost#anchor"),
+        ]));
+    }
+
+    #[test]
+    fn test_errors_name_the_line() {
+        assert_that!(parse("A=1\nB")).has_err("line 2: expected KEY=VALUE".to_string());
+        assert_that!(parse("A=\"1")).has_err("line 1: missing closing quote".to_string());
+        assert_that!(parse("A B=1")).has_err("line 1: invalid name \"A B\"".to_string());
+    }
+}
@@ -0,0 +1,92 @@
+//! Loading `.env` files with `dotenvy` through a scope.
+
+use std::path::Path;
+
+use crate::{error, lock::env_lock, sys, EnvError, TempEnvScope};
+
+impl TempEnvScope {
+    /// Loads a `.env` file with `dotenvy` like `dotenvy::from_path`, but sets
+    /// the variables through this scope, so they get restored when it is
+    /// dropped.
+    ///
+    /// Like `dotenvy`, variables which are already set keep their value. They
+    /// are recorded by the scope anyway, so even [`Self::journaled`] scopes
+    /// restore them if the code under test changes them afterwards.
+    ///
+    /// Nothing is set if the file cannot be read or parsed.
+    pub fn dotenvy(&self, path: impl AsRef<Path>) -> Result<(), EnvError> {
+        self.load_dotenvy(path.as_ref(), false)
+    }
+
+    /// Like [`Self::dotenvy`], but replaces variables which are already set,
+    /// like `dotenvy::from_path_override`.
+    pub fn dotenvy_override(&self, path: impl AsRef<Path>) -> Result<(), EnvError> {
+        self.load_dotenvy(path.as_ref(), true)
+    }
+
+    fn load_dotenvy(&self, path: &Path, overwrite: bool) -> Result<(), EnvError> {
+        // Substitutions like `${HOME}` read the environment
+        let _lock = env_lock();
+        let vars = dotenvy::from_path_iter(path)
+            .and_then(Iterator::collect::<Result<Vec<_>, _>>)
+            .map_err(|err| EnvError::Dotenv {
+                path: path.to_path_buf(),
+                message: err.to_string(),
+            })?;
+        for (key, value) in &vars {
+            error::validate(key.as_ref(), value.as_ref())?;
+        }
+        for (key, value) in vars {
+            if overwrite || sys::var_os(&key).is_none() {
+                self.write(key.as_ref(), Some(value.as_ref()));
+            } else {
+                self.record(key.as_ref());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, ResultAssertion};
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_loaded_vars_are_restored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env");
+        std::fs::write(&path, "DOTENVY_NEW=1\nDOTENVY_KEPT=2\n").unwrap();
+        std::env::set_var("DOTENVY_KEPT", "original");
+
+        for overwrite in [false, true] {
+            let env_scope = TempEnvScope::journaled();
+            if overwrite {
+                env_scope.dotenvy_override(&path).unwrap();
+            } else {
+                env_scope.dotenvy(&path).unwrap();
+            }
+
+            let kept = if overwrite { "2" } else { "original" };
+            assert_that!(std::env::var("DOTENVY_NEW")).has_ok("1".to_string());
+            assert_that!(std::env::var("DOTENVY_KEPT")).has_ok(kept.to_string());
+            std::env::set_var("DOTENVY_KEPT", "changed");
+            drop(env_scope);
+
+            assert_that!(std::env::var("DOTENVY_NEW")).is_err();
+            assert_that!(std::env::var("DOTENVY_KEPT")).has_ok("original".to_string());
+        }
+        std::env::remove_var("DOTENVY_KEPT");
+    }
+
+    #[test]
+    #[serial]
+    fn test_missing_file_is_an_error() {
+        let env_scope = TempEnvScope::new();
+
+        assert_that!(env_scope.dotenvy("does/not/exist.env")).is_err();
+    }
+}
@@ -0,0 +1,152 @@
+//! Binary values transported in environment variables as base64 or hex text.
+
+use std::ffi::OsStr;
+
+use crate::{sys, EnvError, TempEnvScope};
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl TempEnvScope {
+    /// Sets a variable to the standard base64 encoding of the bytes, with
+    /// padding, e.g. for certificates or keys.
+    ///
+    /// ```rust
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// env_scope.set_base64("CERT_DER", [0xde, 0xad, 0xbe, 0xef]);
+    ///
+    /// assert_eq!(std::env::var("CERT_DER").unwrap(), "3q2+7w==");
+    /// assert_eq!(env_scope.get_base64("CERT_DER").unwrap(), Some(vec![0xde, 0xad, 0xbe, 0xef]));
+    /// ```
+    pub fn set_base64(&self, key: impl AsRef<OsStr>, bytes: impl AsRef<[u8]>) {
+        self.set(key, encode_base64(bytes.as_ref()));
+    }
+
+    /// Sets a variable to the lowercase hex encoding of the bytes.
+    pub fn set_hex(&self, key: impl AsRef<OsStr>, bytes: impl AsRef<[u8]>) {
+        let hex: String = bytes
+            .as_ref()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        self.set(key, hex);
+    }
+
+    /// Returns the bytes of a variable encoded in standard base64, or `None` if
+    /// it is not set. Fails if the value is not valid base64.
+    pub fn get_base64(&self, key: impl AsRef<OsStr>) -> Result<Option<Vec<u8>>, EnvError> {
+        decode(key.as_ref(), decode_base64)
+    }
+
+    /// Returns the bytes of a hex encoded variable, or `None` if it is not
+    /// set. Fails if the value is not valid hex.
+    pub fn get_hex(&self, key: impl AsRef<OsStr>) -> Result<Option<Vec<u8>>, EnvError> {
+        decode(key.as_ref(), decode_hex)
+    }
+}
+
+fn decode(
+    key: &OsStr,
+    decode: fn(&str) -> Result<Vec<u8>, String>,
+) -> Result<Option<Vec<u8>>, EnvError> {
+    let Some(value) = sys::var_os(key) else {
+        return Ok(None);
+    };
+    let invalid = |message: String| EnvError::InvalidValue {
+        key: key.to_os_string(),
+        message,
+    };
+    let value = value
+        .to_str()
+        .ok_or_else(|| invalid("not valid unicode".to_string()))?;
+    decode(value).map(Some).map_err(invalid)
+}
+
+fn encode_base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(char::from(BASE64_ALPHABET[index as usize]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn decode_base64(value: &str) -> Result<Vec<u8>, String> {
+    if value.len() % 4 != 0 {
+        return Err("base64 length is not a multiple of 4".to_string());
+    }
+    let data = value.trim_end_matches('=');
+    if value.len() - data.len() > 2 {
+        return Err("too much base64 padding".to_string());
+    }
+    let mut bytes = Vec::with_capacity(data.len() * 3 / 4);
+    let (mut group, mut bits) = (0u32, 0);
+    for char in data.bytes() {
+        let index = BASE64_ALPHABET
+            .iter()
+            .position(|&c| c == char)
+            .ok_or_else(|| format!("invalid base64 character {:?}", char::from(char)))?;
+        group = group << 6 | index as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((group >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+fn decode_hex(value: &str) -> Result<Vec<u8>, String> {
+    if value.len() % 2 != 0 {
+        return Err("hex has an odd number of digits".to_string());
+    }
+    (0..value.len())
+        .step_by(2)
+        .map(|i| {
+            value
+                .get(i..i + 2)
+                .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                .ok_or_else(|| format!("invalid hex digits at {i}"))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, EqualityAssertion, ResultAssertion};
+
+    use super::{decode_base64, decode_hex, encode_base64};
+
+    #[test]
+    fn test_base64_round_trips() {
+        for (bytes, encoded) in [
+            (&b""[..], ""),
+            (b"f", "Zg=="),
+            (b"fo", "Zm8="),
+            (b"foo", "Zm9v"),
+            (b"foob", "Zm9vYg=="),
+            (&[0xff, 0xfe, 0xfd], "//79"),
+        ] {
+            assert_that!(encode_base64(bytes)).is_equal_to(encoded.to_string());
+            assert_that!(decode_base64(encoded)).has_ok(bytes.to_vec());
+        }
+        assert_that!(decode_base64("Zm9v!A==")).is_err();
+        assert_that!(decode_base64("Zm9")).is_err();
+    }
+
+    #[test]
+    fn test_hex_is_decoded() {
+        assert_that!(decode_hex("deadBEEF")).has_ok(vec![0xde, 0xad, 0xbe, 0xef]);
+        assert_that!(decode_hex("abc")).is_err();
+        assert_that!(decode_hex("zz")).is_err();
+    }
+}
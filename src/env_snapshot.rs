@@ -0,0 +1,304 @@
+//! Snapshots of the whole environment, for assertions against a consistent view
+//! or for saving to a file and applying in another process.
+
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+    path::Path,
+    sync::Arc,
+};
+
+use crate::{
+    lock, redact,
+    snapshot::{self, Change, EnvKey, EnvMap},
+    Baseline, EnvError, TempEnvScope,
+};
+
+/// First line of saved snapshots, identifying the format.
+const HEADER: &[u8] = b"temp_env_vars snapshot 1\n";
+
+/// Captures the current environment while holding the lock shared, so no test
+/// using this crate changes it at the same time.
+///
+/// Assertions against the snapshot see one consistent environment, unlike
+/// repeated calls of `std::env::var`.
+///
+/// ```rust
+/// let env_scope = temp_env_vars::TempEnvScope::new();
+/// env_scope.set("CAPTURED_VAR", "1");
+///
+/// let snapshot = temp_env_vars::capture();
+///
+/// assert_eq!(snapshot.get_str("CAPTURED_VAR"), Some("1"));
+/// ```
+pub fn capture() -> EnvSnapshot {
+    let _lock = lock::env_read_lock();
+    EnvSnapshot::capture()
+}
+
+/// A captured environment, which never changes after capturing.
+///
+/// Clones are cheap and share the captured variables, so a snapshot can be
+/// captured once, e.g. in the setup of a test suite, and used by many tests.
+///
+/// ```rust,no_run
+/// use temp_env_vars::EnvSnapshot;
+///
+/// // In a setup binary
+/// EnvSnapshot::capture().save("target/env.snap").unwrap();
+///
+/// // In the test process
+/// let _env_scope = EnvSnapshot::load("target/env.snap").unwrap().apply();
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct EnvSnapshot {
+    pub(crate) vars: Arc<EnvMap>,
+}
+
+impl EnvSnapshot {
+    /// Captures the current environment.
+    pub fn capture() -> EnvSnapshot {
+        EnvSnapshot {
+            vars: Arc::new(snapshot::capture()),
+        }
+    }
+
+    /// Returns the captured value of a variable.
+    pub fn get(&self, key: impl AsRef<OsStr>) -> Option<&OsStr> {
+        self.vars
+            .get(&EnvKey::new(key.as_ref()))
+            .map(|value| &**value)
+    }
+
+    /// Returns the captured value of a variable, if it is valid unicode.
+    pub fn get_str(&self, key: impl AsRef<OsStr>) -> Option<&str> {
+        self.get(key).and_then(OsStr::to_str)
+    }
+
+    /// Returns whether the variable was set.
+    pub fn contains(&self, key: impl AsRef<OsStr>) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Returns the number of captured variables.
+    pub fn len(&self) -> usize {
+        self.vars.len()
+    }
+
+    /// Returns whether the environment was empty.
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// Returns all captured variables, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&OsStr, &OsStr)> {
+        let mut vars: Vec<_> = self.vars.iter().collect();
+        vars.sort();
+        vars.into_iter()
+            .map(|(key, value)| (key.as_os_str(), &**value))
+    }
+
+    /// Saves the snapshot to a file, replacing it if it exists, e.g. for another
+    /// process of an integration test, which loads it with [`Self::load`].
+    ///
+    /// On Windows, all names and values must be valid unicode.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), EnvError> {
+        let path = path.as_ref();
+        let error = |message: String| EnvError::Snapshot {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let mut vars: Vec<_> = self.vars.iter().collect();
+        vars.sort();
+        let mut content = HEADER.to_vec();
+        for (key, value) in vars {
+            for part in [key.as_os_str(), value] {
+                content.extend(to_bytes(part).map_err(error)?);
+                content.push(0);
+            }
+        }
+        std::fs::write(path, content).map_err(|err| error(err.to_string()))
+    }
+
+    /// Loads a snapshot saved by [`Self::save`].
+    pub fn load(path: impl AsRef<Path>) -> Result<EnvSnapshot, EnvError> {
+        let path = path.as_ref();
+        let error = |message: String| EnvError::Snapshot {
+            path: path.to_path_buf(),
+            message,
+        };
+
+        let content = std::fs::read(path).map_err(|err| error(err.to_string()))?;
+        let content = content
+            .strip_prefix(HEADER)
+            .ok_or_else(|| error("not a snapshot of temp_env_vars".to_string()))?;
+        let parts: Vec<&[u8]> = match content.strip_suffix(&[0]) {
+            Some(content) => content.split(|byte| *byte == 0).collect(),
+            None if content.is_empty() => Vec::new(),
+            None => return Err(error("the snapshot is truncated".to_string())),
+        };
+        if parts.len() % 2 != 0 {
+            return Err(error("the snapshot is truncated".to_string()));
+        }
+        let vars = parts
+            .chunks(2)
+            .map(|var| Ok((EnvKey::new(from_bytes(var[0])?), from_bytes(var[1])?.into())))
+            .collect::<Result<EnvMap, String>>()
+            .map_err(error)?;
+        Ok(EnvSnapshot {
+            vars: Arc::new(vars),
+        })
+    }
+
+    /// Changes the environment to the captured one within a new scope, which
+    /// restores the environment as it was before when dropped.
+    pub fn apply(&self) -> TempEnvScope {
+        let env_scope = TempEnvScope::new();
+        let now = snapshot::capture();
+        for change in snapshot::changes(&self.vars, &now) {
+            match change {
+                Change::Remove(key) => env_scope.remove(key.as_os_str()),
+                Change::Set(key, value) => env_scope.set(key.as_os_str(), value),
+            }
+        }
+        env_scope
+    }
+
+    /// Changes the environment to the captured one within a new scope, which
+    /// restores the captured environment again when dropped.
+    ///
+    /// Unlike [`Self::apply`], the environment is not captured again, so this
+    /// is cheap for many tests starting from the same baseline.
+    ///
+    /// ```rust
+    /// use std::sync::LazyLock;
+    ///
+    /// use temp_env_vars::EnvSnapshot;
+    ///
+    /// static BASELINE: LazyLock<EnvSnapshot> = LazyLock::new(temp_env_vars::capture);
+    ///
+    /// let _lock = temp_env_vars::env_lock();
+    /// let env_scope = BASELINE.apply_scoped();
+    /// env_scope.set("FOO", "BAR");
+    /// ```
+    #[track_caller]
+    pub fn apply_scoped(&self) -> TempEnvScope {
+        let _lock = lock::env_lock();
+        let env_scope =
+            TempEnvScope::with_baseline(Baseline::Snapshot(Arc::clone(&self.vars)), None);
+        env_scope.restorer.restore();
+        env_scope
+    }
+}
+
+#[cfg(unix)]
+fn to_bytes(value: &OsStr) -> Result<&[u8], String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Ok(value.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn to_bytes(value: &OsStr) -> Result<&[u8], String> {
+    value
+        .to_str()
+        .map(str::as_bytes)
+        .ok_or_else(|| format!("{value:?} is not valid unicode"))
+}
+
+#[cfg(unix)]
+fn from_bytes(bytes: &[u8]) -> Result<OsString, String> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Ok(OsStr::from_bytes(bytes).to_os_string())
+}
+
+#[cfg(not(unix))]
+fn from_bytes(bytes: &[u8]) -> Result<OsString, String> {
+    String::from_utf8(bytes.to_vec())
+        .map(OsString::from)
+        .map_err(|err| err.to_string())
+}
+
+/// Shows the captured variables, with the values of secrets like `*_TOKEN` redacted.
+impl fmt::Debug for EnvSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EnvSnapshot")
+            .field(
+                "vars",
+                &redact::Vars::new(self.vars.iter().map(|(k, v)| (k.as_os_str(), v))),
+            )
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{
+        assert_that, BooleanAssertion, EqualityAssertion, OptionAssertion, ResultAssertion,
+    };
+    use serial_test::serial;
+
+    use super::{capture, EnvSnapshot};
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_captured_snapshot_does_not_change() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("SNAPSHOT_QUERY", "1");
+
+        let snapshot = capture();
+        env_scope.set("SNAPSHOT_QUERY", "2");
+
+        assert_that!(snapshot.get_str("SNAPSHOT_QUERY")).has_value("1");
+        assert_that!(snapshot.contains("SNAPSHOT_MISSING")).is_false();
+        assert_that!(snapshot.iter().count()).is_equal_to(snapshot.len());
+    }
+
+    #[test]
+    #[serial]
+    fn test_saved_snapshot_is_applied() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("env.snap");
+        let env_scope = TempEnvScope::new();
+        env_scope.set("SNAPSHOT_FOO", "multi\nline");
+        EnvSnapshot::capture().save(&path).unwrap();
+        env_scope.remove("SNAPSHOT_FOO");
+        env_scope.set("SNAPSHOT_BAR", "1");
+
+        let loaded = EnvSnapshot::load(&path).unwrap();
+        {
+            let _applied = loaded.apply();
+
+            assert_that!(std::env::var("SNAPSHOT_FOO")).has_ok("multi\nline".to_string());
+            assert_that!(std::env::var("SNAPSHOT_BAR")).is_err();
+        }
+
+        assert_that!(std::env::var("SNAPSHOT_FOO")).is_err();
+        assert_that!(std::env::var("SNAPSHOT_BAR")).has_ok("1".to_string());
+    }
+
+    #[test]
+    #[serial]
+    fn test_scoped_snapshot_restores_to_the_snapshot() {
+        let _env_scope = TempEnvScope::new();
+        std::env::set_var("SNAPSHOT_BASE", "1");
+        let snapshot = EnvSnapshot::capture();
+        let shared = snapshot.clone();
+        std::env::set_var("SNAPSHOT_BASE", "2");
+
+        for _ in 0..2 {
+            let applied = shared.apply_scoped();
+            assert_that!(std::env::var("SNAPSHOT_BASE")).has_ok("1".to_string());
+            applied.set("SNAPSHOT_BASE", "3");
+            applied.set("SNAPSHOT_OTHER", "3");
+            drop(applied);
+
+            assert_that!(std::env::var("SNAPSHOT_BASE")).has_ok("1".to_string());
+            assert_that!(std::env::var("SNAPSHOT_OTHER")).is_err();
+        }
+        assert_that!(snapshot == shared).is_true();
+    }
+}
@@ -0,0 +1,157 @@
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+    path::PathBuf,
+};
+
+/// Errors of the fallible operations of this crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EnvError {
+    EmptyKey,
+    KeyContainsEquals {
+        key: OsString,
+    },
+    KeyContainsNul {
+        key: OsString,
+    },
+    ValueContainsNul {
+        key: OsString,
+    },
+    MalformedKey {
+        key: OsString,
+    },
+    ValueTooLong {
+        key: OsString,
+        len: usize,
+        max: usize,
+    },
+    Dotenv {
+        path: PathBuf,
+        message: String,
+    },
+    MissingValues {
+        path: PathBuf,
+        keys: Vec<String>,
+    },
+    InvalidValue {
+        key: OsString,
+        message: String,
+    },
+    Snapshot {
+        path: PathBuf,
+        message: String,
+    },
+    #[cfg(feature = "serde")]
+    Deserialize {
+        prefix: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for EnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvError::EmptyKey => write!(f, "environment variable name is empty"),
+            EnvError::KeyContainsEquals { key } => {
+                write!(f, "environment variable name {key:?} contains '='")
+            }
+            EnvError::KeyContainsNul { key } => {
+                write!(f, "environment variable name {key:?} contains a NUL character")
+            }
+            EnvError::ValueContainsNul { key } => {
+                write!(f, "value of environment variable {key:?} contains a NUL character")
+            }
+            EnvError::MalformedKey { key } => {
+                write!(f, "environment contains the malformed variable name {key:?}")
+            }
+            EnvError::ValueTooLong { key, len, max } => write!(
+                f,
+                "value of environment variable {key:?} has {len} characters, at most {max} are supported"
+            ),
+            EnvError::Dotenv { path, message } => write!(f, "cannot load {path:?}: {message}"),
+            EnvError::MissingValues { path, keys } => {
+                write!(f, "{path:?} has no values for the placeholders {keys:?}")
+            }
+            EnvError::InvalidValue { key, message } => {
+                write!(f, "value of environment variable {key:?} is invalid: {message}")
+            }
+            EnvError::Snapshot { path, message } => {
+                write!(f, "cannot save or load the snapshot {path:?}: {message}")
+            }
+            #[cfg(feature = "serde")]
+            EnvError::Deserialize { prefix, message } => write!(
+                f,
+                "cannot deserialize the variables with prefix {prefix:?}: {message}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EnvError {}
+
+/// Maximum length of values on Windows, in UTF-16 code units.
+#[cfg(windows)]
+const MAX_VALUE_LEN: usize = 32_767;
+
+/// Checks that a variable found in the environment can be restored faithfully.
+///
+/// Hidden variables like `=C:` on Windows are accepted, as they are never
+/// touched when restoring.
+pub(crate) fn validate_existing(key: &OsStr, value: &OsStr) -> Result<(), EnvError> {
+    let name = key.to_string_lossy();
+    let hidden = cfg!(windows) && name.starts_with('=') && name.len() > 1;
+    if !hidden && validate_key(key).is_err() {
+        return Err(EnvError::MalformedKey {
+            key: key.to_os_string(),
+        });
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::ffi::OsStrExt;
+
+        let len = value.encode_wide().count();
+        if len > MAX_VALUE_LEN {
+            return Err(EnvError::ValueTooLong {
+                key: key.to_os_string(),
+                len,
+                max: MAX_VALUE_LEN,
+            });
+        }
+    }
+    #[cfg(not(windows))]
+    let _ = value;
+
+    Ok(())
+}
+
+/// Checks that `std::env::set_var` accepts the variable without panicking.
+pub(crate) fn validate(key: &OsStr, value: &OsStr) -> Result<(), EnvError> {
+    validate_key(key)?;
+    if value.to_string_lossy().contains('\0') {
+        return Err(EnvError::ValueContainsNul {
+            key: key.to_os_string(),
+        });
+    }
+    Ok(())
+}
+
+/// Checks that `std::env::set_var` and `std::env::remove_var` accept the name
+/// without panicking.
+pub(crate) fn validate_key(key: &OsStr) -> Result<(), EnvError> {
+    let name = key.to_string_lossy();
+    if name.is_empty() {
+        Err(EnvError::EmptyKey)
+    } else if name.contains('=') {
+        Err(EnvError::KeyContainsEquals {
+            key: key.to_os_string(),
+        })
+    } else if name.contains('\0') {
+        Err(EnvError::KeyContainsNul {
+            key: key.to_os_string(),
+        })
+    } else {
+        Ok(())
+    }
+}
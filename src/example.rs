@@ -0,0 +1,134 @@
+//! Fixtures from the `.env.example` files teams maintain for documentation.
+
+use std::{collections::HashMap, ffi::OsStr, path::Path};
+
+use crate::{dotenv, EnvError, TempEnvScope};
+
+impl TempEnvScope {
+    /// Creates a scope with all variables of an example `.env` file set.
+    ///
+    /// See [`TempEnvScope::from_example_with`] for the details.
+    pub fn from_example(path: impl AsRef<Path>) -> Result<TempEnvScope, EnvError> {
+        TempEnvScope::from_example_with(path, [] as [(&str, &str); 0])
+    }
+
+    /// Creates a scope with all variables of an example `.env` file set, with
+    /// the values of `overrides` taking precedence.
+    ///
+    /// Variables with an empty value or a value like `<your token>` are
+    /// placeholders, which must get a value from `overrides`. Nothing is set
+    /// if any of them is missing or a variable is invalid.
+    ///
+    /// ```rust,no_run
+    /// use temp_env_vars::TempEnvScope;
+    ///
+    /// let _env_scope =
+    ///     TempEnvScope::from_example_with(".env.example", [("API_TOKEN", "test-token")]).unwrap();
+    /// ```
+    pub fn from_example_with<I, K, V>(
+        path: impl AsRef<Path>,
+        overrides: I,
+    ) -> Result<TempEnvScope, EnvError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        let path = path.as_ref();
+        let vars = std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|content| dotenv::parse(&content))
+            .map_err(|message| EnvError::Dotenv {
+                path: path.to_path_buf(),
+                message,
+            })?;
+        let overrides: HashMap<_, _> = overrides
+            .into_iter()
+            .map(|(key, value)| (key.as_ref().to_os_string(), value.as_ref().to_os_string()))
+            .collect();
+
+        let keys: Vec<String> = vars
+            .iter()
+            .filter(|(key, value)| {
+                is_placeholder(value) && !overrides.contains_key(OsStr::new(key))
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        if !keys.is_empty() {
+            return Err(EnvError::MissingValues {
+                path: path.to_path_buf(),
+                keys,
+            });
+        }
+
+        let env_scope = TempEnvScope::new();
+        let vars = vars
+            .iter()
+            .filter(|(key, _)| !overrides.contains_key(OsStr::new(key)))
+            .map(|(key, value)| (OsStr::new(key), OsStr::new(value)));
+        let overrides = overrides
+            .iter()
+            .map(|(key, value)| (key.as_os_str(), value.as_os_str()));
+        env_scope.try_set_all(vars.chain(overrides))?;
+        Ok(env_scope)
+    }
+}
+
+fn is_placeholder(value: &str) -> bool {
+    value.is_empty() || (value.starts_with('<') && value.ends_with('>'))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use assertor::{assert_that, ResultAssertion};
+    use serial_test::serial;
+
+    use crate::{EnvError, TempEnvScope};
+
+    fn example_file() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.example");
+        std::fs::write(
+            &path,
+            "# Copy to .env\nEXAMPLE_HOST=localhost\nEXAMPLE_TOKEN=<your token>\nEXAMPLE_SECRET=\n",
+        )
+        .unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    #[serial]
+    fn test_missing_placeholders_are_listed() {
+        let (_dir, path) = example_file();
+
+        let result = TempEnvScope::from_example_with(&path, [("EXAMPLE_SECRET", "1")]);
+
+        assert_that!(result.map(|_| ())).has_err(EnvError::MissingValues {
+            path,
+            keys: vec!["EXAMPLE_TOKEN".to_string()],
+        });
+        assert_that!(std::env::var("EXAMPLE_HOST")).is_err();
+    }
+
+    #[test]
+    #[serial]
+    fn test_example_is_applied_with_overrides() {
+        let (_dir, path) = example_file();
+
+        {
+            let _env_scope = TempEnvScope::from_example_with(
+                &path,
+                [("EXAMPLE_TOKEN", "abc"), ("EXAMPLE_SECRET", "def")],
+            )
+            .unwrap();
+
+            assert_that!(std::env::var("EXAMPLE_HOST")).has_ok("localhost".to_string());
+            assert_that!(std::env::var("EXAMPLE_TOKEN")).has_ok("abc".to_string());
+            assert_that!(std::env::var("EXAMPLE_SECRET")).has_ok("def".to_string());
+        }
+
+        assert_that!(std::env::var("EXAMPLE_HOST")).is_err();
+    }
+}
@@ -0,0 +1,160 @@
+//! The variables set through a scope as `figment` provider.
+
+use figment::{
+    value::{Dict, Map, Tag, Value},
+    Metadata, Profile, Provider,
+};
+
+use crate::TempEnvScope;
+
+/// A `figment` provider of the variables a scope overrides, created with
+/// [`TempEnvScope::figment_provider`].
+///
+/// Like `figment::providers::Env`, names are lowercased and values are parsed
+/// into booleans, numbers or strings. The values are taken when the provider is
+/// created, so extracting configuration does not read the process environment.
+#[derive(Debug, Clone)]
+pub struct FigmentEnv {
+    name: String,
+    vars: Vec<(String, String)>,
+    prefix: Option<String>,
+    split: Option<String>,
+}
+
+impl TempEnvScope {
+    /// Returns a `figment` provider of the variables set by this scope, e.g. to
+    /// assert on the configuration merged from them.
+    ///
+    /// ```rust
+    /// use figment::Figment;
+    ///
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// env_scope.set("APP_PORT", "8080");
+    ///
+    /// let figment = Figment::from(env_scope.figment_provider().prefixed("APP_"));
+    /// assert_eq!(figment.extract_inner::<u16>("port").unwrap(), 8080);
+    /// ```
+    pub fn figment_provider(&self) -> FigmentEnv {
+        FigmentEnv {
+            name: format!("temp_env_vars scope {}", self.state),
+            vars: self.overridden_values(),
+            prefix: None,
+            split: None,
+        }
+    }
+}
+
+impl FigmentEnv {
+    /// Only provides the variables starting with `prefix`, which gets removed
+    /// from their names.
+    pub fn prefixed(mut self, prefix: impl Into<String>) -> FigmentEnv {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Nests the values at the separator, e.g. `DB__URL` as `url` in `db` with
+    /// the separator `__`.
+    pub fn split(mut self, separator: impl Into<String>) -> FigmentEnv {
+        self.split = Some(separator.into());
+        self
+    }
+}
+
+impl Provider for FigmentEnv {
+    fn metadata(&self) -> Metadata {
+        Metadata::named(self.name.clone())
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, figment::Error> {
+        let mut dict = Dict::new();
+        for (key, value) in &self.vars {
+            let key = match &self.prefix {
+                Some(prefix) => match key.strip_prefix(prefix.as_str()) {
+                    Some(key) => key,
+                    None => continue,
+                },
+                None => key,
+            };
+            let key = key.to_lowercase();
+            let path: Vec<&str> = match &self.split {
+                Some(separator) => key.split(separator.as_str()).collect(),
+                None => vec![&key],
+            };
+            let Ok(value) = value.parse::<Value>();
+            insert(&mut dict, &path, value);
+        }
+        let mut data = Map::new();
+        data.insert(Profile::Default, dict);
+        Ok(data)
+    }
+}
+
+/// Inserts the value into the nested dictionaries along the path.
+fn insert(dict: &mut Dict, path: &[&str], value: Value) {
+    match path {
+        [] => {}
+        [key] => {
+            dict.insert(key.to_string(), value);
+        }
+        [key, rest @ ..] => {
+            let entry = dict
+                .entry(key.to_string())
+                .or_insert_with(|| Value::Dict(Tag::Default, Dict::new()));
+            if !matches!(entry, Value::Dict(..)) {
+                *entry = Value::Dict(Tag::Default, Dict::new());
+            }
+            if let Value::Dict(_, nested) = entry {
+                insert(nested, rest, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, EqualityAssertion};
+    use figment::Figment;
+    use serde::Deserialize;
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Config {
+        debug: bool,
+        db: Db,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Db {
+        url: String,
+        pool: u32,
+    }
+
+    #[test]
+    #[serial]
+    fn test_overridden_vars_are_provided() {
+        std::env::set_var("FIGMENT_UNRELATED", "1");
+        let env_scope = TempEnvScope::new();
+        env_scope.set("FIGMENT_DEBUG", "true");
+        env_scope.set("FIGMENT_DB__URL", "postgres://localhost");
+        env_scope.set("FIGMENT_DB__POOL", "4");
+        env_scope.set("OTHER_PORT", "80");
+
+        let provider = env_scope
+            .figment_provider()
+            .prefixed("FIGMENT_")
+            .split("__");
+        drop(env_scope);
+        let config: Config = Figment::from(provider).extract().unwrap();
+
+        assert_that!(config).is_equal_to(Config {
+            debug: true,
+            db: Db {
+                url: "postgres://localhost".to_string(),
+                pool: 4,
+            },
+        });
+        std::env::remove_var("FIGMENT_UNRELATED");
+    }
+}
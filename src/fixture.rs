@@ -0,0 +1,127 @@
+//! Fixtures pointing variables at temporary directories owned by a scope.
+
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use crate::TempEnvScope;
+
+impl TempEnvScope {
+    /// Points the home directory variables at a new, empty temporary directory
+    /// and returns its path, isolating the test from the real dotfiles.
+    ///
+    /// Sets `HOME`, `XDG_CONFIG_HOME`, `XDG_CACHE_HOME`, `XDG_DATA_HOME` and
+    /// `XDG_STATE_HOME`, and on Windows also `USERPROFILE`, `APPDATA` and
+    /// `LOCALAPPDATA`. All of these directories are created. The directory is
+    /// removed after the environment got restored when the scope is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory cannot be created.
+    pub fn fake_home(&self) -> PathBuf {
+        let home = self.create_temp_dir("home");
+        let dirs: &[(&str, &[&str])] = &[
+            ("HOME", &[]),
+            ("XDG_CONFIG_HOME", &[".config"]),
+            ("XDG_CACHE_HOME", &[".cache"]),
+            ("XDG_DATA_HOME", &[".local", "share"]),
+            ("XDG_STATE_HOME", &[".local", "state"]),
+            #[cfg(windows)]
+            ("USERPROFILE", &[]),
+            #[cfg(windows)]
+            ("APPDATA", &["AppData", "Roaming"]),
+            #[cfg(windows)]
+            ("LOCALAPPDATA", &["AppData", "Local"]),
+        ];
+        for (key, relative) in dirs {
+            let dir = relative
+                .iter()
+                .fold(home.clone(), |dir, part| dir.join(part));
+            create_dir(&dir);
+            self.set(key, dir);
+        }
+        home
+    }
+
+    /// Sets a variable to the path of a new, empty temporary directory and
+    /// returns the path. The directory is removed when the scope is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the directory cannot be created.
+    pub fn set_tempdir(&self, key: impl AsRef<OsStr>) -> PathBuf {
+        let dir = self.create_temp_dir("dir");
+        self.set(key, &dir);
+        dir
+    }
+
+    fn create_temp_dir(&self, purpose: &str) -> PathBuf {
+        let dir = tempfile::Builder::new()
+            .prefix(&format!("temp_env_vars_{purpose}_"))
+            .tempdir()
+            .unwrap_or_else(|err| {
+                panic!("temp_env_vars: cannot create a temporary directory: {err}")
+            });
+        let path = dir.path().to_path_buf();
+        self.temp_dirs
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(dir);
+        path
+    }
+}
+
+fn create_dir(dir: &Path) {
+    std::fs::create_dir_all(dir)
+        .unwrap_or_else(|err| panic!("temp_env_vars: cannot create the directory {dir:?}: {err}"));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use assertor::{assert_that, BooleanAssertion, EqualityAssertion, OptionAssertion};
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_fake_home_is_set_and_removed() {
+        let original = std::env::var_os("HOME");
+        let home;
+
+        {
+            let env_scope = TempEnvScope::new();
+            home = env_scope.fake_home();
+
+            assert_that!(std::env::var_os("HOME")).is_equal_to(Some(home.clone().into()));
+            let config = PathBuf::from(std::env::var_os("XDG_CONFIG_HOME").unwrap());
+            assert_that!(config.starts_with(&home)).is_true();
+            assert_that!(config.is_dir()).is_true();
+        }
+
+        assert_that!(home.exists()).is_false();
+        assert_that!(std::env::var_os("HOME")).is_equal_to(original);
+    }
+
+    #[test]
+    #[serial]
+    fn test_tempdir_is_set_and_removed() {
+        std::env::remove_var("FIXTURE_DATA_DIR");
+        let dir;
+
+        {
+            let env_scope = TempEnvScope::new();
+            dir = env_scope.set_tempdir("FIXTURE_DATA_DIR");
+
+            assert_that!(std::env::var_os("FIXTURE_DATA_DIR"))
+                .is_equal_to(Some(dir.clone().into()));
+            assert_that!(dir.is_dir()).is_true();
+        }
+
+        assert_that!(dir.exists()).is_false();
+        assert_that!(std::env::var_os("FIXTURE_DATA_DIR")).is_none();
+    }
+}
@@ -0,0 +1,51 @@
+//! Batteries of values for robustness testing, see [`TempEnvScope::fuzz`].
+//!
+//! [`TempEnvScope::fuzz`]: crate::TempEnvScope::fuzz
+
+use std::ffi::OsString;
+
+/// The values a variable is cycled through by [`TempEnvScope::fuzz`].
+///
+/// [`TempEnvScope::fuzz`]: crate::TempEnvScope::fuzz
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuzzProfile {
+    /// Empty and whitespace only values.
+    Blank,
+    /// The blank values, plus a 1 MiB value, invalid UTF-8 (on unix), emoji and
+    /// null-like values such as `null` or `undefined`.
+    Hostile,
+}
+
+impl FuzzProfile {
+    /// Returns the values of this profile.
+    pub fn values(self) -> Vec<OsString> {
+        let mut values: Vec<OsString> = ["", " ", "\t", "\n", " \t\r\n "]
+            .into_iter()
+            .map(OsString::from)
+            .collect();
+        if self == FuzzProfile::Hostile {
+            values.push("x".repeat(1024 * 1024).into());
+            values.extend(
+                [
+                    "🦀",
+                    "👩‍👩‍👧‍👦🔥",
+                    "null",
+                    "NULL",
+                    "nil",
+                    "None",
+                    "undefined",
+                    "NaN",
+                    "-1",
+                ]
+                .into_iter()
+                .map(OsString::from),
+            );
+            #[cfg(unix)]
+            {
+                use std::os::unix::ffi::OsStringExt;
+                values.push(OsString::from_vec(vec![0x66, 0x6f, 0xff, 0xfe]));
+            }
+        }
+        values
+    }
+}
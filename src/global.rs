@@ -0,0 +1,137 @@
+//! A base environment for the whole test binary.
+//!
+//! [`global_setup`] applies variables once per process. Scopes created later
+//! capture the base environment like any other, so per-test changes are layered
+//! on top of it. The original environment is restored when the process exits.
+
+use std::{
+    ffi::OsStr,
+    path::Path,
+    sync::{Mutex, Once, PoisonError},
+};
+
+use crate::{
+    dotenv, env_lock, error, leak_detection, restore_policy,
+    snapshot::{self, EnvMap},
+    sys,
+};
+
+static SETUP: Once = Once::new();
+static ORIGINAL: Mutex<Option<EnvMap>> = Mutex::new(None);
+
+// Targets without a C runtime, like `wasm32-unknown-unknown`, have no `atexit`
+#[cfg(all(any(unix, windows), not(miri)))]
+extern "C" {
+    fn atexit(callback: extern "C" fn()) -> std::ffi::c_int;
+}
+
+/// The base environment being set up by [`global_setup`].
+#[derive(Debug)]
+pub struct GlobalEnv {
+    _private: (),
+}
+
+/// Applies a base environment for the whole test binary. Only the first call
+/// runs `setup`, so every test can call it, e.g. through a shared helper, or it
+/// can be called once from a constructor function like the ones of `ctor`.
+///
+/// ```rust
+/// fn setup() {
+///     temp_env_vars::global_setup(|env| {
+///         env.set("APP_ENV", "test");
+///     });
+/// }
+///
+/// #[test]
+/// fn test_some() {
+///     setup();
+///     assert_eq!(std::env::var("APP_ENV").unwrap(), "test");
+/// }
+/// # setup();
+/// # assert_eq!(std::env::var("APP_ENV").unwrap(), "test");
+/// ```
+pub fn global_setup(setup: impl FnOnce(&GlobalEnv)) {
+    SETUP.call_once(|| {
+        let _lock = env_lock();
+        *original() = Some(snapshot::capture());
+        // Miri cannot call C functions, and its process ends with the test anyway
+        #[cfg(all(any(unix, windows), not(miri)))]
+        // SAFETY: `restore_at_exit` is a plain function without preconditions
+        if unsafe { atexit(restore_at_exit) } != 0 {
+            panic!("temp_env_vars: cannot register the restore of the environment at exit");
+        }
+
+        setup(&GlobalEnv { _private: () });
+        leak_detection::rebase();
+    });
+}
+
+impl GlobalEnv {
+    /// Sets a variable of the base environment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is empty or contains `=` or NUL, or if the value contains
+    /// NUL.
+    pub fn set(&self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> &Self {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        if let Err(err) = error::validate(key, value) {
+            panic!("temp_env_vars: {err}");
+        }
+        sys::set_var(key, value);
+        self
+    }
+
+    /// Removes a variable from the base environment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is empty or contains `=` or NUL.
+    pub fn remove(&self, key: impl AsRef<OsStr>) -> &Self {
+        let key = key.as_ref();
+        if let Err(err) = error::validate_key(key) {
+            panic!("temp_env_vars: {err}");
+        }
+        sys::remove_var(key);
+        self
+    }
+
+    /// Sets all variables of a `.env` file. Variables which are already set
+    /// are overridden.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the file cannot be read or parsed.
+    pub fn load_dotenv(&self, path: impl AsRef<Path>) -> &Self {
+        let path = path.as_ref();
+        let vars = std::fs::read_to_string(path)
+            .map_err(|err| err.to_string())
+            .and_then(|content| dotenv::parse(&content))
+            .unwrap_or_else(|err| panic!("temp_env_vars: cannot load {path:?}: {err}"));
+        for (key, value) in vars {
+            self.set(key, value);
+        }
+        self
+    }
+}
+
+#[cfg_attr(not(all(any(unix, windows), not(miri))), allow(dead_code))]
+extern "C" fn restore_at_exit() {
+    let Some(original) = original().take() else {
+        return;
+    };
+    let now = snapshot::capture();
+    let vars = snapshot::changes(&original, &now)
+        .iter()
+        .map(|change| change.to_var())
+        .collect();
+    // Panicking is not possible while the process exits
+    let failed = restore_policy::restore_all(vars);
+    if !failed.is_empty() {
+        eprintln!("temp_env_vars: failed restoring {failed:?} at exit");
+    }
+}
+
+fn original() -> std::sync::MutexGuard<'static, Option<EnvMap>> {
+    ORIGINAL.lock().unwrap_or_else(PoisonError::into_inner)
+}
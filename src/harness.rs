@@ -0,0 +1,108 @@
+//! Support for custom test harnesses built with `libtest-mimic`, with the
+//! `libtest_mimic` feature.
+//!
+//! The `#[temp_env_vars]` attribute cannot be applied to the trials of such a
+//! harness. Trials created by [`trial`] get the same treatment instead: each one
+//! holds the lock exclusively and runs within its own [`TempEnvScope`]. [`run`]
+//! only accepts such trials, so none of them can run unguarded by mistake.
+//!
+//! ```rust,no_run
+//! use temp_env_vars::harness;
+//!
+//! fn main() {
+//!     harness::run([harness::trial("sets_foo", || {
+//!         std::env::set_var("FOO", "BAR");
+//!         Ok(())
+//!     })])
+//!     .exit();
+//! }
+//! ```
+
+use libtest_mimic::{Arguments, Conclusion, Failed, Trial};
+
+use crate::{env_lock, TempEnvScope};
+
+/// A test trial created by [`trial`], which holds the lock and runs within its
+/// own scope. Converts into a plain [`Trial`] for running it together with
+/// other trials.
+#[derive(Debug)]
+pub struct EnvTrial(Trial);
+
+impl EnvTrial {
+    /// Sets the kind of the trial, like [`Trial::with_kind`].
+    pub fn with_kind(self, kind: impl Into<String>) -> EnvTrial {
+        EnvTrial(self.0.with_kind(kind))
+    }
+
+    /// Marks the trial as ignored, like [`Trial::with_ignored_flag`].
+    pub fn with_ignored_flag(self, is_ignored: bool) -> EnvTrial {
+        EnvTrial(self.0.with_ignored_flag(is_ignored))
+    }
+}
+
+impl From<EnvTrial> for Trial {
+    fn from(trial: EnvTrial) -> Trial {
+        trial.0
+    }
+}
+
+/// Creates a test trial which holds the lock and runs within its own scope.
+pub fn trial<R>(name: impl Into<String>, runner: R) -> EnvTrial
+where
+    R: FnOnce() -> Result<(), Failed> + Send + 'static,
+{
+    EnvTrial(Trial::test(name, move || {
+        let _lock = env_lock();
+        let _env_scope = TempEnvScope::new();
+        runner()
+    }))
+}
+
+/// Runs the trials with the arguments of the command line.
+///
+/// Only trials created by [`trial`] are accepted, so all of them are guarded by
+/// the lock and a scope.
+pub fn run(trials: impl IntoIterator<Item = EnvTrial>) -> Conclusion {
+    let trials = trials.into_iter().map(Trial::from).collect();
+    libtest_mimic::run(&Arguments::from_args(), trials)
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, BooleanAssertion, ResultAssertion};
+    use libtest_mimic::{Arguments, Failed};
+    use serial_test::serial;
+
+    use super::trial;
+
+    fn arguments() -> Arguments {
+        Arguments {
+            test_threads: Some(2),
+            quiet: true,
+            ..Arguments::default()
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_trials_are_run_within_a_scope() {
+        std::env::remove_var("HARNESS_VAR");
+        let trials = (0..4)
+            .map(|index| {
+                trial(format!("trial_{index}"), move || {
+                    if std::env::var("HARNESS_VAR").is_ok() {
+                        return Err(Failed::from("changes of another trial are visible"));
+                    }
+                    std::env::set_var("HARNESS_VAR", index.to_string());
+                    Ok(())
+                })
+                .into()
+            })
+            .collect();
+
+        let conclusion = libtest_mimic::run(&arguments(), trials);
+
+        assert_that!(conclusion.has_failed()).is_false();
+        assert_that!(std::env::var("HARNESS_VAR")).is_err();
+    }
+}
@@ -0,0 +1,165 @@
+//! Running test code in a child process with its own environment.
+//!
+//! The current test binary is executed again, filtered to the currently running
+//! test. In the child process, the closure runs and the process exits right
+//! afterwards. The parent waits for the child and fails if the child failed.
+
+use std::{
+    ffi::{OsStr, OsString},
+    io::Write,
+    process::Command,
+};
+
+/// Set in the child process to the name of the test which runs isolated.
+const CHILD_MARKER: &str = "TEMP_ENV_VARS_ISOLATED";
+/// Printed by the child process when the closure completed.
+const COMPLETED_MARKER: &str = "temp_env_vars: isolated closure completed";
+
+/// Runs the closure in a child process, so all changes to the environment and
+/// other global process state are isolated from the current process.
+///
+/// Panics and exit codes of the child process are propagated as panic of the
+/// current test. Must be called from within a `#[test]` function.
+///
+/// ```rust,no_run
+/// #[test]
+/// fn test_some() {
+///     temp_env_vars::isolated(|| {
+///         std::env::set_var("FOO", "BAR");
+///         assert_eq!(std::env::var("FOO").unwrap(), "BAR");
+///     });
+///
+///     assert!(std::env::var("FOO").is_err());
+/// }
+/// ```
+pub fn isolated<F: FnOnce()>(f: F) {
+    Isolated::new().run(f)
+}
+
+/// Builder for running a closure in a child process with a controlled environment.
+#[derive(Debug, Clone, Default)]
+pub struct Isolated {
+    clear: bool,
+    vars: Vec<(OsString, Option<OsString>)>,
+    test_name: Option<String>,
+}
+
+impl Isolated {
+    pub fn new() -> Isolated {
+        Isolated::default()
+    }
+
+    /// Sets a variable in the child process.
+    pub fn env(mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> Isolated {
+        self.vars.push((
+            key.as_ref().to_os_string(),
+            Some(value.as_ref().to_os_string()),
+        ));
+        self
+    }
+
+    /// Removes a variable in the child process.
+    pub fn env_remove(mut self, key: impl AsRef<OsStr>) -> Isolated {
+        self.vars.push((key.as_ref().to_os_string(), None));
+        self
+    }
+
+    /// Starts the child process with an empty environment.
+    pub fn env_clear(mut self) -> Isolated {
+        self.clear = true;
+        self.vars.clear();
+        self
+    }
+
+    /// Sets the full name of the running test, e.g. `tests::test_some`.
+    ///
+    /// Only needed if the test harness does not name the test thread after the test.
+    pub fn test_name(mut self, name: impl Into<String>) -> Isolated {
+        self.test_name = Some(name.into());
+        self
+    }
+
+    /// Runs the closure in the child process.
+    ///
+    /// Under Miri, which cannot start processes, the closure runs in the current
+    /// process instead, under the lock and within a scope with the configured
+    /// environment.
+    pub fn run<F: FnOnce()>(self, f: F) {
+        if cfg!(miri) {
+            return self.run_in_process(f);
+        }
+        let test_name = self.test_name.clone().unwrap_or_else(current_test_name);
+
+        if std::env::var_os(CHILD_MARKER).is_some_and(|marker| marker == test_name.as_str()) {
+            f();
+            println!("{COMPLETED_MARKER}");
+            let _ = std::io::stdout().flush();
+            std::process::exit(0);
+        }
+
+        self.run_child(&test_name);
+    }
+
+    fn run_in_process<F: FnOnce()>(self, f: F) {
+        let _lock = crate::env_lock();
+        let env_scope = crate::TempEnvScope::new();
+        if self.clear {
+            for (key, _) in crate::sys::vars_os() {
+                env_scope.remove(key);
+            }
+        }
+        for (key, value) in &self.vars {
+            match value {
+                Some(value) => env_scope.set(key, value),
+                None => env_scope.remove(key),
+            }
+        }
+        f();
+    }
+
+    fn run_child(self, test_name: &str) {
+        let exe = std::env::current_exe()
+            .unwrap_or_else(|err| panic!("temp_env_vars: cannot locate test binary: {err}"));
+
+        let mut command = Command::new(exe);
+        command.args([test_name, "--exact", "--nocapture", "--test-threads=1"]);
+        if self.clear {
+            command.env_clear();
+        }
+        for (key, value) in &self.vars {
+            match value {
+                Some(value) => command.env(key, value),
+                None => command.env_remove(key),
+            };
+        }
+        command.env(CHILD_MARKER, test_name);
+
+        let output = command
+            .output()
+            .unwrap_or_else(|err| panic!("temp_env_vars: cannot start isolated child: {err}"));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        print!("{stdout}");
+        eprint!("{stderr}");
+
+        if !output.status.success() {
+            panic!(
+                "temp_env_vars: isolated child of `{test_name}` failed with {}",
+                output.status
+            );
+        }
+        if !stdout.contains(COMPLETED_MARKER) {
+            panic!("temp_env_vars: isolated child did not run `{test_name}`, is the test name correct?");
+        }
+    }
+}
+
+/// The default test harness names the thread of each test after the test.
+fn current_test_name() -> String {
+    match std::thread::current().name() {
+        Some(name) if name != "main" => name.to_string(),
+        _ => panic!(
+            "temp_env_vars: cannot determine the running test, use `Isolated::test_name` instead"
+        ),
+    }
+}
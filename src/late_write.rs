@@ -0,0 +1,88 @@
+//! Detection of writes to restored variables shortly after a scope was dropped,
+//! typically by a background thread the test started.
+
+use std::{
+    ffi::OsString,
+    time::{Duration, Instant},
+};
+
+use crate::{sys, TempEnvScope};
+
+/// Interval in which the restored variables are polled.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+
+impl TempEnvScope {
+    /// Makes dropping this scope poll the restored variables for `window`, and
+    /// panic if any of them is changed again in the meantime.
+    ///
+    /// This turns pollution by background threads started during the test into
+    /// a failure of that test. Dropping the scope takes `window` longer. Writes
+    /// through [`crate::set_var`] wait for the lock held by the test, so they are
+    /// only detected if the lock was released before.
+    pub fn detect_late_writes(&self, window: Duration) {
+        self.state.data().late_write_window = Some(window);
+    }
+}
+
+/// Polls the variables for `window` and returns the sorted names of those
+/// which changed their value.
+pub(crate) fn poll(keys: Vec<OsString>, window: Duration) -> Vec<String> {
+    let restored: Vec<(OsString, Option<OsString>)> = keys
+        .into_iter()
+        .map(|key| {
+            let value = sys::var_os(&key);
+            (key, value)
+        })
+        .collect();
+    let deadline = Instant::now() + window;
+    let mut changed = Vec::new();
+    loop {
+        for (key, value) in &restored {
+            let name = key.to_string_lossy().into_owned();
+            if sys::var_os(key) != *value && !changed.contains(&name) {
+                changed.push(name);
+            }
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            break;
+        }
+        std::thread::sleep(POLL_INTERVAL.min(deadline - now));
+    }
+    changed.sort();
+    changed
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    /// Removes the late write, also when the test panics as expected.
+    struct RemoveOnDrop(&'static str);
+
+    impl Drop for RemoveOnDrop {
+        fn drop(&mut self) {
+            std::env::remove_var(self.0);
+        }
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "[\"LATE_WRITE\"] were changed again within")]
+    fn test_late_writes_panic() {
+        let _cleanup = RemoveOnDrop("LATE_WRITE");
+        let env_scope = TempEnvScope::new();
+        env_scope.detect_late_writes(Duration::from_millis(500));
+        env_scope.set("LATE_WRITE", "1");
+        std::thread::spawn(|| {
+            std::thread::sleep(Duration::from_millis(50));
+            std::env::set_var("LATE_WRITE", "2");
+        });
+
+        drop(env_scope);
+    }
+}
@@ -0,0 +1,209 @@
+//! Environments built from layered sources with a fixed precedence, like the
+//! configuration of applications in production.
+
+use std::{
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    ops::Deref,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    dotenv,
+    snapshot::{self, EnvKey},
+    EnvError, TempEnvScope,
+};
+
+/// The source of an effective value of [`Layers`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Layer {
+    /// Set by [`Layers::defaults`].
+    Defaults,
+    /// Already set in the process environment.
+    Process,
+    /// Loaded from the given `.env` file by [`Layers::dotenv`].
+    Dotenv(PathBuf),
+    /// Set by [`Layers::overrides`].
+    Overrides,
+}
+
+/// Builder for a scope applying variables from several sources.
+///
+/// The precedence is fixed, whatever the order of the calls: defaults, the
+/// process environment, `.env` files in the order they were added, and
+/// overrides. A value of a later layer wins over all earlier ones.
+///
+/// ```rust,no_run
+/// use temp_env_vars::{Layer, Layers};
+///
+/// let env_scope = Layers::new()
+///     .defaults([("LOG_LEVEL", "info"), ("PORT", "80")])
+///     .dotenv(".env.test")
+///     .overrides([("PORT", "8080")])
+///     .apply()
+///     .unwrap();
+///
+/// assert_eq!(env_scope.origin("PORT"), Some(&Layer::Overrides));
+/// ```
+#[derive(Debug, Default)]
+pub struct Layers {
+    defaults: Vec<(OsString, OsString)>,
+    dotenv_files: Vec<PathBuf>,
+    overrides: Vec<(OsString, OsString)>,
+}
+
+impl Layers {
+    pub fn new() -> Layers {
+        Layers::default()
+    }
+
+    /// Adds values used only if the variable is not set by any other layer.
+    pub fn defaults<I, K, V>(mut self, vars: I) -> Layers
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.defaults.extend(owned(vars));
+        self
+    }
+
+    /// Adds a `.env` file, taking precedence over the process environment and
+    /// files added before.
+    pub fn dotenv(mut self, path: impl AsRef<Path>) -> Layers {
+        self.dotenv_files.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Adds values taking precedence over all other layers.
+    pub fn overrides<I, K, V>(mut self, vars: I) -> Layers
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        self.overrides.extend(owned(vars));
+        self
+    }
+
+    /// Sets the effective values within a new scope. Nothing is set if a file
+    /// cannot be loaded or a variable is invalid.
+    pub fn apply(self) -> Result<LayeredScope, EnvError> {
+        let mut effective: HashMap<EnvKey, (Layer, OsString)> = HashMap::new();
+        for (key, value) in self.defaults {
+            effective.insert(EnvKey::new(key), (Layer::Defaults, value));
+        }
+        for (key, value) in snapshot::capture() {
+            effective.insert(key, (Layer::Process, value.to_os_string()));
+        }
+        for path in self.dotenv_files {
+            let vars = std::fs::read_to_string(&path)
+                .map_err(|err| err.to_string())
+                .and_then(|content| dotenv::parse(&content))
+                .map_err(|message| EnvError::Dotenv {
+                    path: path.clone(),
+                    message,
+                })?;
+            for (key, value) in vars {
+                effective.insert(
+                    EnvKey::new(key),
+                    (Layer::Dotenv(path.clone()), value.into()),
+                );
+            }
+        }
+        for (key, value) in self.overrides {
+            effective.insert(EnvKey::new(key), (Layer::Overrides, value));
+        }
+
+        let scope = TempEnvScope::new();
+        scope.try_set_all(
+            effective
+                .iter()
+                .filter(|(_, (layer, _))| *layer != Layer::Process)
+                .map(|(key, (_, value))| (key.as_os_str(), value)),
+        )?;
+        let origins = effective
+            .into_iter()
+            .map(|(key, (layer, _))| (key, layer))
+            .collect();
+        Ok(LayeredScope { scope, origins })
+    }
+}
+
+fn owned<I, K, V>(vars: I) -> impl Iterator<Item = (OsString, OsString)>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+{
+    vars.into_iter()
+        .map(|(key, value)| (key.as_ref().to_os_string(), value.as_ref().to_os_string()))
+}
+
+/// A scope created by [`Layers::apply`], which knows the layer of each value.
+///
+/// It dereferences to its [`TempEnvScope`].
+#[derive(Debug)]
+pub struct LayeredScope {
+    scope: TempEnvScope,
+    origins: HashMap<EnvKey, Layer>,
+}
+
+impl LayeredScope {
+    /// Returns the layer the value of the variable came from when the layers
+    /// were applied, or `None` if no layer set it.
+    pub fn origin(&self, key: impl AsRef<OsStr>) -> Option<&Layer> {
+        self.origins.get(&EnvKey::new(key.as_ref()))
+    }
+
+    /// Returns the scope, forgetting the layers of the values.
+    pub fn into_scope(self) -> TempEnvScope {
+        self.scope
+    }
+}
+
+impl Deref for LayeredScope {
+    type Target = TempEnvScope;
+
+    fn deref(&self) -> &TempEnvScope {
+        &self.scope
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, EqualityAssertion, OptionAssertion, ResultAssertion};
+    use serial_test::serial;
+
+    use super::{Layer, Layers};
+
+    #[test]
+    #[serial]
+    fn test_later_layers_take_precedence() {
+        std::env::set_var("LAYER_PROCESS", "process");
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".env.test");
+        std::fs::write(&path, "LAYER_PROCESS=dotenv\nLAYER_DOTENV=dotenv\n").unwrap();
+
+        {
+            let env_scope = Layers::new()
+                .overrides([("LAYER_OVERRIDE", "override")])
+                .defaults([("LAYER_DEFAULT", "default"), ("LAYER_OVERRIDE", "default")])
+                .dotenv(&path)
+                .apply()
+                .unwrap();
+
+            assert_that!(std::env::var("LAYER_DEFAULT")).has_ok("default".to_string());
+            assert_that!(std::env::var("LAYER_PROCESS")).has_ok("dotenv".to_string());
+            assert_that!(std::env::var("LAYER_OVERRIDE")).has_ok("override".to_string());
+            assert_that!(env_scope.origin("LAYER_DEFAULT")).has_value(&Layer::Defaults);
+            assert_that!(env_scope.origin("LAYER_PROCESS")).has_value(&Layer::Dotenv(path));
+            assert_that!(env_scope.origin("LAYER_OVERRIDE")).has_value(&Layer::Overrides);
+            assert_that!(env_scope.origin("LAYER_UNKNOWN")).is_equal_to(None);
+        }
+
+        assert_that!(std::env::var("LAYER_PROCESS")).has_ok("process".to_string());
+        assert_that!(std::env::var("LAYER_DEFAULT")).is_err();
+        std::env::remove_var("LAYER_PROCESS");
+    }
+}
@@ -0,0 +1,202 @@
+//! Optional detection of environment changes that happen *between* scopes.
+//!
+//! When enabled, the environment is recorded whenever the last active
+//! `TempEnvScope` is dropped. When the next scope is created, the current
+//! environment is compared against that record. Every difference was caused
+//! by code running outside of any scope, typically a test which forgot the
+//! `#[temp_env_vars]` attribute and silently pollutes the following tests.
+
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex, PoisonError,
+    },
+};
+
+use crate::{
+    snapshot::{self, EnvMap},
+    EnvDiff,
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static REGISTRY: LazyLock<Mutex<Registry>> = LazyLock::new(Mutex::default);
+
+#[derive(Debug, Default)]
+struct Registry {
+    active_scopes: usize,
+    last_drop: Option<EnvMap>,
+    last_report: Option<LeakReport>,
+}
+
+/// Variables which changed between the drop of the last scope and the
+/// creation of the next one.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct LeakReport {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl From<EnvDiff> for LeakReport {
+    fn from(diff: EnvDiff) -> LeakReport {
+        LeakReport {
+            added: diff.added,
+            removed: diff.removed,
+            changed: diff.changed,
+        }
+    }
+}
+
+impl LeakReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for LeakReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "environment changed outside of any scope (added: {:?}, removed: {:?}, changed: {:?})",
+            self.added, self.removed, self.changed
+        )
+    }
+}
+
+/// Starts recording the environment between scopes. Detected leaks are
+/// printed to stderr and can be inspected with [`take_leak_report`].
+pub fn enable_leak_detection() {
+    ENABLED.store(true, Ordering::SeqCst);
+}
+
+/// Stops recording the environment between scopes and forgets the last record.
+pub fn disable_leak_detection() {
+    ENABLED.store(false, Ordering::SeqCst);
+    let mut registry = registry();
+    registry.last_drop = None;
+    registry.last_report = None;
+}
+
+/// Returns the most recently detected leak, if any, and clears it.
+pub fn take_leak_report() -> Option<LeakReport> {
+    registry().last_report.take()
+}
+
+/// Called when a new scope is created, after it captured the environment if it
+/// does so.
+pub(crate) fn scope_created() {
+    let mut registry = registry();
+    registry.active_scopes += 1;
+    if registry.active_scopes > 1 || !ENABLED.load(Ordering::SeqCst) {
+        return;
+    }
+
+    if let Some(before) = registry.last_drop.take() {
+        let report = LeakReport::from(EnvDiff::between(&before, &snapshot::capture()));
+        if !report.is_empty() {
+            eprintln!("temp_env_vars: {report}");
+            #[cfg(feature = "ci_annotations")]
+            {
+                let vars: Vec<String> = [&report.added, &report.removed, &report.changed]
+                    .into_iter()
+                    .flatten()
+                    .cloned()
+                    .collect();
+                crate::annotation::Annotation {
+                    level: crate::annotation::Level::Warning,
+                    kind: "leak",
+                    vars: &vars,
+                    message: &report.to_string(),
+                }
+                .emit();
+            }
+            registry.last_report = Some(report);
+        }
+    }
+}
+
+/// Called after a scope has restored the environment.
+pub(crate) fn scope_dropped() {
+    let mut registry = registry();
+    registry.active_scopes = registry.active_scopes.saturating_sub(1);
+    if registry.active_scopes == 0 && ENABLED.load(Ordering::SeqCst) {
+        registry.last_drop = Some(snapshot::capture());
+    }
+}
+
+/// Called after the environment was changed on purpose outside of any scope,
+/// so the change is not reported as leak.
+pub(crate) fn rebase() {
+    let mut registry = registry();
+    if registry.last_drop.is_some() {
+        registry.last_drop = Some(snapshot::capture());
+    }
+}
+
+fn registry() -> std::sync::MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, BooleanAssertion, OptionAssertion};
+    use serial_test::serial;
+
+    use super::{disable_leak_detection, enable_leak_detection, take_leak_report, LeakReport};
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_changes_between_scopes_are_reported() {
+        std::env::remove_var("LEAK_ADDED");
+        std::env::set_var("LEAK_CHANGED", "1");
+        enable_leak_detection();
+
+        drop(TempEnvScope::new());
+        std::env::set_var("LEAK_ADDED", "1");
+        std::env::set_var("LEAK_CHANGED", "2");
+        drop(TempEnvScope::new());
+
+        assert_that!(take_leak_report()).has_value(LeakReport {
+            added: vec!["LEAK_ADDED".to_string()],
+            removed: vec![],
+            changed: vec!["LEAK_CHANGED".to_string()],
+        });
+
+        disable_leak_detection();
+        std::env::remove_var("LEAK_ADDED");
+        std::env::remove_var("LEAK_CHANGED");
+    }
+
+    #[test]
+    #[serial]
+    fn test_changes_within_scopes_are_not_reported() {
+        enable_leak_detection();
+
+        drop(TempEnvScope::new());
+        {
+            let _outer = TempEnvScope::new();
+            std::env::set_var("LEAK_SCOPED", "1");
+            let _inner = TempEnvScope::new();
+        }
+        drop(TempEnvScope::new());
+
+        assert_that!(take_leak_report()).is_none();
+        disable_leak_detection();
+    }
+
+    #[test]
+    #[serial]
+    fn test_nothing_is_recorded_when_disabled() {
+        disable_leak_detection();
+
+        drop(TempEnvScope::new());
+        std::env::set_var("LEAK_DISABLED", "1");
+        drop(TempEnvScope::new());
+
+        assert_that!(take_leak_report()).is_none();
+        assert_that!(super::registry().last_drop.is_none()).is_true();
+        std::env::remove_var("LEAK_DISABLED");
+    }
+}
@@ -32,6 +32,35 @@
 //! }
 //! ```
 //!
+//! Tests which only read the environment can be marked with `#[temp_env_vars(read_only)]`.
+//! Read-only tests run concurrently with each other, while all other tests still get
+//! exclusive access. A read-only test fails if it changes the environment.
+//!
+//! With the `parking_lot` feature, the lock coordinating the tests is built on `parking_lot`
+//! primitives instead of the ones from `std`, which avoids poisoning and reduces the
+//! locking overhead for large test suites.
+//!
+//! With the `tokio` feature, async test functions wait for the lock without blocking the
+//! executor. For this, `#[temp_env_vars]` must be placed above `#[tokio::test]`.
+//!
+//! A process-wide lock does not help when tests of several binaries run at the same time,
+//! e.g. with nextest. With the `file_lock` feature, the lock additionally holds an advisory
+//! lock on `target/temp_env_vars.lock` (or the file given in `TEMP_ENV_VARS_LOCK_FILE`),
+//! which serializes env-mutating tests across processes.
+//!
+//! Custom test harnesses built with `libtest-mimic` cannot use the attribute. With the
+//! `libtest_mimic` feature, `harness::trial` creates trials which hold the lock and run within
+//! their own scope, and `harness::run` runs only such trials.
+//!
+//! For BDD frameworks like `cucumber`, [`TempEnvWorld`] holds the lock and a scope from the
+//! before hook of a scenario until its after hook.
+//! With the `test-context` feature, [`EnvContext`] does the same for
+//! `#[test_context(EnvContext)]` tests.
+//! With the `rstest` feature, the `temp_env` fixture injects an `EnvContext` into `#[rstest]` cases.
+//!
+//! `#[serial]` of `serial_test` can be applied before or after `#[temp_env_vars]`. In both orders,
+//! the lock of `serial_test` is acquired first, so tests using either order don't deadlock each other.
+//!
 //! ### Use with TempEnvScope
 //!
 //!
@@ -67,51 +96,1066 @@
 //!     // "FOO" is not longer set here.
 //! }
 //! ```
+//!
+//! [`TempEnvScope::set`] and [`TempEnvScope::remove`] change variables like `std::env` does.
+//! [`TempEnvScope::try_set`] and [`TempEnvScope::try_set_all`] report invalid names or values
+//! (e.g. containing `=` or NUL) as [`EnvError`] instead of panicking, so fixture-loading code
+//! can surface bad input as test failure with context.
+//!
+//! With the `tempfile` feature, `TempEnvScope::fake_home` points `HOME` and the XDG base directories at a new
+//! temporary directory, which is removed together with the scope, like the one created by `TempEnvScope::set_tempdir`.
+//! [`TempEnvScope::path_var`] edits list variables like `PATH` entry by entry.
+//! [`TempEnvScope::sanitize_ci`] removes the variables of CI systems, for tests of the behavior outside of CI.
+//! [`TempEnvScope::set_timezone`] and [`TempEnvScope::set_locale`] set `TZ` and the locale variables for time and locale sensitive tests.
+//! Threads started with [`TempEnvScope::spawn`] are joined before the scope restores the environment.
+//! For tests sharing external systems keyed by the environment, [`TempEnvScope::namespace`]
+//! generates a unique prefix for variables.
+//! [`TempEnvScope::rename`] moves a value to another name for the lifetime of the scope, e.g. to
+//! adapt real credentials to the names the code under test expects.
+//!
+//! [`TempEnvScope::builder`] creates a scope together with its initial changes, applied right after
+//! capturing the environment.
+//! Within one test, [`TempEnvScope::child`] creates nested scopes for setup, sub-cases and
+//! teardown, each restoring the state of its parent when dropped.
+//! To change many variables while holding the lock once, so other threads using the lock never
+//! see them partially applied, use [`TempEnvScope::set_many`] or [`TempEnvScope::apply`].
+//! [`atomically`] applies a batch of changes the same way and returns a scope restoring it.
+//!
+//! For huge environments, [`TempEnvScope::journaled`] avoids capturing the whole environment. It only
+//! records the original values of variables changed through the scope itself and restores those.
+//!
+//! Restoring happens in a deterministic order: variables changed through the scope come first,
+//! in reverse order of their first change, followed by all other variables sorted by name.
+//!
+//! Scopes of a thread must be dropped in reverse order of their creation. Otherwise, dropping a
+//! scope panics with the locations of both scopes, or only warns after
+//! `set_drop_order_policy(DropOrderPolicy::Warn)`.
+//! A scope dropped on another thread restores at a racy time, so it panics after restoring, unless
+//! [`TempEnvScope::allow_drop_on_other_thread`] was called, as done for async tests.
+//! If restoring some variables fails, the remaining ones are restored anyway, and the
+//! [`RestorePolicy`] decides whether to panic, log or call a function.
+//!
+//! With several scopes active at once, [`TempEnvScope::named`] gives a scope a name which identifies it in panic
+//! messages and lock diagnostics. With the `tracing` feature, capturing and restoring the environment
+//! is logged as `tracing` debug events including the name.
+//!
+//! The `Debug` output of scopes and mocks never shows the values of variables which look like secrets,
+//! i.e. whose name is or ends with `TOKEN`, `SECRET`, `PASSWORD`, `PASSWD`, `API_KEY`, `PRIVATE_KEY`
+//! or `CREDENTIALS`. Such values are shown as `***`. Other variables can be marked as secret with
+//! [`mark_secret`], or set and marked at once with [`TempEnvScope::set_secret`].
+//! With the `secrecy` feature, `TempEnvScope::set_secret` also accepts a `secrecy::SecretString`,
+//! which is zeroized once the variable is set, without copying the value except into the environment.
+//!
+//! With the `serde` feature, `TempEnvScope::set_json` sets a variable to the JSON representation of a value,
+//! and `TempEnvScope::set_struct` sets one variable per field of a struct, e.g. `APP_PORT` and `APP_DB__URL`.
+//! `TempEnvScope::to_struct` reads such variables back into a struct.
+//!
+//! [`TempEnvScope::set_base64`] and [`TempEnvScope::set_hex`] set variables to encoded binary
+//! values, which [`TempEnvScope::get_base64`] and [`TempEnvScope::get_hex`] decode again.
+//! [`TempEnvScope::set_duration`] and [`TempEnvScope::set_bytes`] set durations like `30s` and
+//! sizes like `64MiB`, in other formats with [`DurationFormat`] and [`ByteFormat`].
+//! With the `url` feature, `TempEnvScope::set_url` checks that a value is a URL with a host, so
+//! malformed endpoints fail during the setup of a test.
+//!
+//! ### Loading fixtures
+//!
+//! With the `dotenvy` feature, `TempEnvScope::dotenvy` loads a `.env` file with `dotenvy` through a
+//! scope, so everything it sets is restored when the scope is dropped.
+//! [`TempEnvScope::from_example`] applies a `.env.example` file, failing if placeholders are left
+//! without a value.
+//! When values come from several sources, [`Layers`] applies defaults, `.env` files and overrides
+//! with a fixed precedence, and tells which layer each effective value came from.
+//!
+//! `#[derive(TempEnv)]` turns a struct into a typed fixture, whose `apply` sets a variable for
+//! each field and returns the scope.
+//! Before a test relies on its environment, [`TempEnvScope::require`] checks that all variables
+//! it depends on are set, listing all missing ones at once.
+//!
+//! ### Observing changes
+//!
+//! [`TempEnvScope::on_change`] registers observers for every change made through a scope, and for
+//! changes made by other means, detected when the scope restores the environment.
+//! [`TempEnvScope::on_restore`] and [`TempEnvScope::on_restore_error`] run callbacks right after
+//! that.
+//! [`watch()`] follows a single variable across all scopes, which helps finding the test or fixture
+//! flipping it.
+//!
+//! After the arrange phase of a test, [`TempEnvScope::freeze`] makes every further change through
+//! the scope or the accessors of this crate panic with the location of the freeze.
+//! [`TempEnvScope::detect_late_writes`] makes dropping a scope fail if a background thread started
+//! by the test changes the restored variables again.
+//!
+//! ### Inspecting the environment
+//!
+//! Assertions can use [`capture`], which returns an immutable [`EnvSnapshot`] taken under the
+//! lock, instead of racing repeated `std::env::var` calls.
+//! [`diff_env`] lists the variables added, removed or changed between two snapshots, e.g. around a
+//! third-party initializer.
+//! Snapshots can be saved to a file, so another process, e.g. of an integration test, can load
+//! and apply them within a scope.
+//! They are cheap to clone, and [`EnvSnapshot::apply_scoped`] restores one without capturing the
+//! environment again, e.g. for a baseline shared by many tests.
+//!
+//! Which variables a scope currently sets, removes or changes is told by
+//! [`TempEnvScope::is_modified`] and [`TempEnvScope::overrides`].
+//! [`TempEnvScope::to_shell_exports`] and [`TempEnvScope::to_powershell`] turn the changes of a
+//! scope into a script, so the environment of a failing test can be reproduced in a terminal.
+//!
+//! ### Robustness testing
+//!
+//! For robustness against unexpected input, [`TempEnvScope::fuzz`] cycles variables through a
+//! battery of hostile values, like empty, very long or invalid UTF-8 ones.
+//! Code depending on the order in which variables were set is caught by
+//! [`check_order_independent`], which runs a closure with the variables applied in different orders
+//! and reports differing results.
+//! With the `proptest` feature, `prop::proptest_env` runs a property test for generated
+//! variables, each case applied within a scope.
+//!
+//! ### Integrations
+//!
+//! Projects using the `temp-env` crate can migrate by changing one import to
+//! [`compat::temp_env`], which offers `with_var`, `with_vars`, `with_var_unset` and
+//! `with_vars_unset` with the same signatures.
+//!
+//! With the `figment` feature, `TempEnvScope::figment_provider` provides the variables set by a
+//! scope to `figment`, so tests can assert on the merged configuration.
+//! With the `config` feature, `config_source` of a scope, an [`EnvSnapshot`] or a [`MockEnv`]
+//! returns a `config::Environment` reading their variables instead of the process environment.
+//! With the `clap` feature, `TempEnvScope::parse_with_env` parses a command line while holding
+//! the lock, for concise tests of arguments with `env = "..."`.
+//!
+//! With the `criterion` feature, `bench::with_env` and `bench::group_with_env` apply variables
+//! around benchmarks.
+//! With the `ci_annotations` feature, strict mode violations and leaks are reported as GitHub
+//! Actions annotations, or as machine-readable lines on other CI systems.
+//!
+//! ### Coordinating with the lock
+//!
+//! Other crates and hand-rolled test helpers can serialize against the same lock
+//! the macro uses with [`env_lock`] (exclusive) and [`env_read_lock`] (shared). The
+//! lock is reentrant, so helpers taking it can also be called within tests using
+//! the macro.
+//!
+//! Authors of other test macros can compose with the lock through [`acquire`],
+//! which names the test holding the lock in diagnostics, and [`EnvLockGuard`].
+//!
+//! ```rust
+//! fn with_proxy<R>(f: impl FnOnce() -> R) -> R {
+//!     let _lock = temp_env_vars::env_lock();
+//!     let _env_scope = temp_env_vars::TempEnvScope::new();
+//!     std::env::set_var("HTTP_PROXY", "http://localhost:3128");
+//!     f()
+//! }
+//! # with_proxy(|| ());
+//! ```
+//!
+//! [`try_env_lock`] acquires the lock without blocking, and [`env_lock_status`] reports whether
+//! it is held and for how long, e.g. for better messages than a silent hang.
+//! `#[temp_env_vars]` names the holder after the test, as in "env lock held by
+//! `tests::db::connects_with_tls` for 32s".
+//!
+//! When a test hangs on the lock, [`active_scopes`] lists every active scope with its name, the
+//! location where it was created and the variables it changed.
+//! A hung test does not block the rest of the suite if its scope got a deadline with
+//! [`TempEnvScope::with_deadline`], which then restores the environment and releases the lock.
+//!
+//! ### Mutating the environment with Rust 2024
+//!
+//! With Rust 2024, `std::env::set_var` and `std::env::remove_var` are `unsafe`.
+//! [`set_var`] and [`remove_var`] are safe replacements which perform the mutation
+//! while holding the lock of this crate. See [`set_var`] for the exact contract.
+//! [`TempEnvScope::set`] and [`TempEnvScope::remove`] hold the lock as well.
+//!
+//! ```rust
+//! use temp_env_vars::temp_env_vars;
+//!
+//! #[test]
+//! #[temp_env_vars]
+//! fn test_some() {
+//!     temp_env_vars::set_var("FOO", "BAR");
+//!     assert_eq!(temp_env_vars::var("FOO").unwrap(), "BAR");
+//! }
+//! ```
+//!
+//! ### Tests using threads
+//!
+//! The lock only serializes tests, threads started by a test may still change the
+//! environment after the test restored it. The macro therefore rejects tests using
+//! multi-threaded tokio runtimes or common APIs starting threads, like
+//! `thread::spawn`, `tokio::spawn` or `rayon`. If the threads are done before the
+//! test ends, this is fine and can be allowed with `allow_threads`:
+//!
+//! ```rust
+//! use temp_env_vars::temp_env_vars;
+//!
+//! #[test]
+//! #[temp_env_vars(allow_threads)]
+//! fn test_some() {
+//!     std::env::set_var("FOO", "BAR");
+//!     std::thread::spawn(|| assert_eq!(std::env::var("FOO").unwrap(), "BAR"))
+//!         .join()
+//!         .unwrap();
+//! }
+//! ```
+//!
+//! ## Strict mode
+//!
+//! Reading variables with [`var`] and [`var_os`] instead of `std::env::var` lets
+//! the crate check which variables the code under test depends on. In strict mode,
+//! reading a variable which was not declared fails the test.
+//!
+//! ```rust
+//! use temp_env_vars::temp_env_vars;
+//!
+//! #[test]
+//! #[temp_env_vars(strict, declare("FOO"))]
+//! fn test_some() {
+//!     std::env::set_var("FOO", "BAR");
+//!     assert_eq!(temp_env_vars::var("FOO").unwrap(), "BAR");
+//!
+//!     // temp_env_vars::var("OTHER") would panic here
+//! }
+//! ```
+//!
+//! With `check_unused`, declared variables which were never read through [`var`] or
+//! [`var_os`] are reported when the test ends, as a warning on stderr or, in strict
+//! mode, as failure. This keeps fixtures from accumulating dead variables.
+//!
+//! ## Profiles
+//!
+//! Commonly used sets of variables can be registered once with [`profiles`] and
+//! applied by name with `#[temp_env_vars(profile = "...")]` or
+//! [`TempEnvScope::apply_profile`]. Profiles must be registered before the test
+//! applying them starts.
+//!
+//! ```rust
+//! use temp_env_vars::temp_env_vars;
+//!
+//! fn register_profiles() {
+//!     temp_env_vars::profiles().register("integration", [("DB_HOST", "localhost")]);
+//! }
+//!
+//! #[temp_env_vars(profile = "integration")]
+//! fn connect() {
+//!     assert_eq!(std::env::var("DB_HOST").unwrap(), "localhost");
+//! }
+//!
+//! #[test]
+//! fn test_connect() {
+//!     register_profiles();
+//!     connect();
+//! }
+//! ```
+//!
+//! ## Config file
+//!
+//! A `temp_env_vars.toml` in the crate directory or any directory above up to
+//! the workspace root, usually the latter, is read by the macro at compile time
+//! and baked into every test using `#[temp_env_vars]`. It defines variables set
+//! and removed at the start of each test, except read-only ones, and profiles.
+//!
+//! ```toml
+//! denylist = ["AWS_SECRET_ACCESS_KEY"]
+//!
+//! [defaults]
+//! RUST_LOG = "warn"
+//!
+//! [profiles.integration]
+//! DB_HOST = "localhost"
+//! ```
+//!
+//! ## Testing without the process environment
+//!
+//! Code which receives its environment through the [`EnvProvider`] trait can be
+//! tested with a [`MockEnv`]. Such tests never touch the process environment and
+//! can run fully in parallel.
+//!
+//! ```rust
+//! use temp_env_vars::{EnvProvider, MockEnv};
+//!
+//! fn port(env: &impl EnvProvider) -> u16 {
+//!     env.get("PORT").and_then(|port| port.parse().ok()).unwrap_or(80)
+//! }
+//!
+//! assert_eq!(port(&MockEnv::new().with("PORT", "8080")), 8080);
+//! ```
+//!
+//! ## Virtual environment
+//!
+//! A [`VirtualEnvScope`] never touches the process environment. Its variables are
+//! only visible through [`var`], [`var_os`] and [`StdEnv`] on the thread which
+//! created the scope, so tests using it can run in parallel without the global lock.
+//!
+//! ```rust
+//! use temp_env_vars::VirtualEnvScope;
+//!
+//! let env_scope = VirtualEnvScope::new();
+//! env_scope.set("FOO", "BAR");
+//!
+//! assert_eq!(temp_env_vars::var("FOO").unwrap(), "BAR");
+//! ```
+//!
+//! With the `tokio` feature, `ScopedEnv` offers the same for async tests on a
+//! per-task basis, even when tasks interleave on the same worker threads.
+//!
+//! Child processes only inherit the process environment. [`CommandEnvExt::apply_scope`] passes the variables
+//! as seen through `var` to a `std::process::Command`, or with the `tokio` feature to a
+//! `tokio::process::Command`, including all overlays.
+//!
+//! ## Isolation in a child process
+//!
+//! For code which unavoidably mutates global process state, [`isolated`] runs a
+//! closure of a test in a child process. Panics and exit codes of the child are
+//! propagated to the test.
+//!
+//! ```rust,no_run
+//! #[test]
+//! fn test_some() {
+//!     temp_env_vars::isolated(|| {
+//!         std::env::set_var("FOO", "BAR");
+//!     });
+//!
+//!     assert!(std::env::var("FOO").is_err());
+//! }
+//! ```
+//!
+//! Tests using this crate can run under Miri. There, the lock never takes the file lock of the
+//! `file_lock` feature, no C functions are called and [`isolated`] runs the closure in the current
+//! process.
+//!
+//! ## Base environment
+//!
+//! [`global_setup`] applies a base environment once per test binary, e.g. loaded from a `.env`
+//! file. Scopes capture it like any other environment, so per-test changes are layered on top.
+//! The original environment is restored when the process exits.
+//!
+//! ```rust,no_run
+//! fn setup() {
+//!     temp_env_vars::global_setup(|env| {
+//!         env.set("APP_ENV", "test").load_dotenv("tests/test.env");
+//!     });
+//! }
+//! ```
+//!
+//! ## Leak detection
+//!
+//! Tests which change environment variables without a scope silently pollute all
+//! tests running after them. With [`enable_leak_detection`], every change happening
+//! between the drop of one scope and the creation of the next scope is reported.
+//!
+//! ```rust
+//! temp_env_vars::enable_leak_detection();
+//! ```
+//!
+//! ## WebAssembly
+//!
+//! On wasm targets without an operating system, like `wasm32-unknown-unknown`,
+//! there is no process environment. There, this crate keeps the environment in
+//! memory, so scopes and `#[temp_env_vars]`, e.g. together with
+//! `#[wasm_bindgen_test]`, behave like on other targets. `std::env` does not see
+//! this environment, so the code under test must read it through [`var`] and
+//! [`var_os`].
+//!
+//! Other targets without a usable process environment, e.g. embedded systems,
+//! can install their own key-value store implementing [`EnvBackend`] with
+//! [`set_backend`], and still use scopes, snapshots and the macro.
+//!
+//! Changes and restores are visible to C libraries reading the environment with
+//! `getenv` or through `environ` as soon as the call returns. With the `libc`
+//! feature, `LibcBackend` changes the environment directly through `setenv` and
+//! `unsetenv` of the C library on Unix. Installing it is `unsafe`, as it is only
+//! sound if no other thread accesses the environment at the same time. On Windows,
+//! `Win32Backend` accesses the environment directly through Win32, keeping empty
+//! values, very long values and the casing of names exactly as Win32 sees them.
+
+#![allow(clippy::test_attr_in_doctest)]
 
-pub use temp_env_vars_macro::temp_env_vars;
+mod access;
+#[cfg(feature = "ci_annotations")]
+mod annotation;
+mod backend;
+mod batch;
+#[cfg(feature = "criterion")]
+pub mod bench;
+mod builder;
+mod child;
+#[cfg(feature = "clap")]
+mod clap_parse;
+mod command;
+pub mod compat;
+#[cfg(feature = "config")]
+mod config_source;
+mod context;
+mod deadline;
+#[cfg(feature = "serde")]
+mod deserialize;
+mod diff;
+mod dotenv;
+#[cfg(feature = "dotenvy")]
+mod dotenvy_load;
+mod encoding;
+mod env_snapshot;
+mod error;
+mod example;
+#[cfg(feature = "figment")]
+mod figment_provider;
+#[cfg(feature = "tempfile")]
+mod fixture;
+mod fuzz;
+mod global;
+#[cfg(feature = "libtest_mimic")]
+pub mod harness;
+mod isolation;
+mod late_write;
+mod layers;
+mod leak_detection;
+mod lock;
+mod mock;
+mod namespace;
+mod nesting;
+mod observe;
+mod order;
+mod overrides;
+mod path_var;
+mod preset;
+mod profile;
+#[cfg(feature = "proptest")]
+pub mod prop;
+mod provider;
+mod random;
+mod redact;
+mod registry;
+mod require;
+mod restore;
+mod restore_policy;
+mod scope_info;
+#[cfg(feature = "secrecy")]
+mod secrecy_value;
+#[cfg(feature = "serde")]
+mod serialize;
+mod shell;
+mod snapshot;
+mod spawn;
+mod sys;
+#[cfg(feature = "tokio")]
+mod task_local;
+mod units;
+#[cfg(feature = "url")]
+mod url_value;
+mod value;
+mod virtual_env;
+mod watch;
+mod world;
+
+pub use access::{remove_var, set_var, var, var_os};
+#[cfg(all(unix, feature = "libc"))]
+pub use backend::LibcBackend;
+#[cfg(windows)]
+pub use backend::Win32Backend;
+pub use backend::{set_backend, EnvBackend, MemoryBackend, StdBackend};
+pub use batch::{atomically, EnvBatch};
+pub use builder::TempEnvScopeBuilder;
+pub use child::ChildScope;
+pub use command::CommandEnvExt;
+#[cfg(feature = "rstest")]
+pub use context::temp_env;
+pub use context::EnvContext;
+pub use diff::{diff_env, EnvDiff};
+pub use env_snapshot::{capture, EnvSnapshot};
+pub use error::EnvError;
+#[cfg(feature = "figment")]
+pub use figment_provider::FigmentEnv;
+pub use fuzz::FuzzProfile;
+pub use global::{global_setup, GlobalEnv};
+pub use isolation::{isolated, Isolated};
+pub use layers::{Layer, LayeredScope, Layers};
+pub use leak_detection::{
+    disable_leak_detection, enable_leak_detection, take_leak_report, LeakReport,
+};
+pub use lock::{
+    acquire, env_lock, env_lock_status, env_read_lock, try_env_lock, EnvLockGuard, EnvLockStatus,
+    LockHolder,
+};
+#[cfg(feature = "tokio")]
+pub use lock::{env_lock_async, env_read_lock_async};
+pub use mock::{EnvWrite, MockEnv};
+pub use namespace::EnvNamespace;
+pub use nesting::{set_drop_order_policy, DropOrderPolicy};
+pub use observe::{EnvEvent, RestoreError};
+pub use order::{check_order_independent, OrderDependence};
+pub use overrides::Override;
+pub use path_var::PathVar;
+pub use profile::{profiles, Profiles};
+pub use provider::{EnvProvider, StdEnv};
+pub use redact::mark_secret;
+pub use require::MissingVars;
+pub use restore_policy::{set_restore_policy, RestorePolicy};
+pub use scope_info::{active_scopes, ScopeInfo};
+pub use spawn::ScopedJoinHandle;
+#[cfg(feature = "tokio")]
+pub use task_local::ScopedEnv;
+pub use temp_env_vars_macro::{temp_env_vars, TempEnv};
+pub use units::{ByteFormat, DurationFormat};
+#[cfg(feature = "url")]
+pub use url_value::UrlValue;
+pub use value::{SecretValue, ToEnvValue};
+pub use virtual_env::VirtualEnvScope;
+pub use watch::{watch, WatchGuard};
+pub use world::TempEnvWorld;
 
 use std::{
-    collections::HashMap,
-    sync::{Arc, LazyLock, Mutex},
+    ffi::{OsStr, OsString},
+    fmt,
+    sync::{Arc, Mutex},
 };
 
-// Makes the mutex available for the `temp_env_vars` macro. Unfortunately, Macro traits cannot
-// export other types than macros, so this is the least bad place to export this.
-#[doc(hidden)]
-pub static TEMP_ENV_VAR_MACRO_MUTEX: LazyLock<Arc<Mutex<()>>> = LazyLock::new(Arc::default);
+use registry::ScopeState;
+use restore::Restorer;
+use snapshot::{EnvKey, EnvMap};
 
-#[derive(Debug)]
 pub struct TempEnvScope {
-    original_vars: HashMap<String, String>,
+    /// Restores the environment, shared with the watchdog.
+    restorer: Arc<Restorer>,
+    state: Arc<ScopeState>,
+    read_only: bool,
+    /// Directories which get removed after the environment was restored.
+    #[cfg(feature = "tempfile")]
+    temp_dirs: Mutex<Vec<tempfile::TempDir>>,
+    /// Threads which get joined before the environment is restored.
+    threads: Mutex<Vec<Arc<dyn spawn::Joinable>>>,
+    watchdog: Mutex<Option<Arc<deadline::Watchdog>>>,
+}
+
+/// What a scope restores when it is dropped.
+#[derive(Debug)]
+enum Baseline {
+    /// A snapshot of the whole environment, shared with nested scopes.
+    Snapshot(Arc<EnvMap>),
+    /// Only the journal of the variables changed through the scope.
+    Journal,
 }
 
 impl TempEnvScope {
+    /// Captures the current environment, which gets restored when the scope is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the environment contains variables which cannot be restored
+    /// faithfully. Use [`Self::try_new`] to handle such environments.
+    #[track_caller]
     pub fn new() -> TempEnvScope {
+        TempEnvScope::try_new().unwrap_or_else(|err| panic!("temp_env_vars: {err}"))
+    }
+
+    /// Captures the current environment, which gets restored when the scope is dropped.
+    ///
+    /// Fails if the environment contains variables which cannot be restored
+    /// faithfully, e.g. malformed names or values too long for the platform.
+    /// Values which are not valid unicode are supported.
+    #[track_caller]
+    pub fn try_new() -> Result<TempEnvScope, EnvError> {
+        TempEnvScope::capture(None)
+    }
+
+    /// Like [`Self::new`], but the scope gets a name which identifies it in panic
+    /// messages, lock diagnostics, tracing output (with the `tracing` feature) and
+    /// its `Debug` output.
+    #[track_caller]
+    pub fn named(name: impl Into<String>) -> TempEnvScope {
+        TempEnvScope::capture(Some(name.into()))
+            .unwrap_or_else(|err| panic!("temp_env_vars: {err}"))
+    }
+
+    #[track_caller]
+    fn capture(name: Option<String>) -> Result<TempEnvScope, EnvError> {
+        let original_vars = snapshot::try_capture_shared()?;
+        let scope = TempEnvScope::with_baseline(Baseline::Snapshot(original_vars), name);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            "temp_env_vars: scope {} captured the environment",
+            scope.state
+        );
+        Ok(scope)
+    }
+
+    #[track_caller]
+    fn with_baseline(baseline: Baseline, name: Option<String>) -> TempEnvScope {
+        leak_detection::scope_created();
+        let state = registry::register(name);
         TempEnvScope {
-            original_vars: std::env::vars().collect(),
+            restorer: Arc::new(Restorer::new(baseline, Arc::clone(&state))),
+            #[cfg(feature = "tempfile")]
+            temp_dirs: Mutex::default(),
+            threads: Mutex::default(),
+            watchdog: Mutex::default(),
+            state,
+            read_only: false,
         }
     }
 
-    /// Sets the environment variables to the state as they were
-    /// when this `TempEnvScope` was created.
-    fn restore(&self) {
-        let mut now: HashMap<String, String> = std::env::vars().collect();
+    /// Returns the name given with [`Self::named`].
+    pub fn name(&self) -> Option<&str> {
+        self.state.name.as_deref()
+    }
 
-        self.original_vars.keys().for_each(|key| {
-            now.remove(key);
-        });
-        now.keys().for_each(|key| {
-            std::env::remove_var(key);
-        });
-        self.original_vars.iter().for_each(|(k, v)| {
-            std::env::set_var(k, v);
+    /// Creates a scope for code which must not change the environment. Instead of
+    /// restoring the environment, dropping the scope panics if anything changed.
+    ///
+    /// This is used by `#[temp_env_vars(read_only)]`, which lets many read-only
+    /// tests run concurrently while mutating tests still get exclusive access.
+    #[track_caller]
+    pub fn read_only() -> TempEnvScope {
+        let mut scope = TempEnvScope::new();
+        scope.read_only = true;
+        scope
+    }
+
+    /// Creates a scope which does not capture the environment. Instead, the
+    /// original value of a variable is recorded the first time it is changed
+    /// through [`Self::set`], [`Self::try_set`], [`Self::try_set_all`] or
+    /// [`Self::remove`], and only those variables get restored.
+    ///
+    /// This avoids copying huge environments, but changes made by other means,
+    /// e.g. [`set_var`], are not restored.
+    #[track_caller]
+    pub fn journaled() -> TempEnvScope {
+        TempEnvScope::with_baseline(Baseline::Journal, None)
+    }
+
+    /// Declares variables the code under test is allowed to read through
+    /// [`var`] and [`var_os`] when the scope is in strict mode.
+    pub fn declare<I, K>(&self, keys: I)
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<String>,
+    {
+        self.state
+            .data()
+            .declared
+            .extend(keys.into_iter().map(Into::into));
+    }
+
+    /// Enables the strict mode. While this scope is active, reading a variable
+    /// through [`var`] or [`var_os`] panics if it was not declared before.
+    pub fn strict(&self) {
+        self.state.data().strict = true;
+    }
+
+    /// Checks when this scope is dropped that every declared variable was read
+    /// through [`var`] or [`var_os`], which keeps fixtures free of dead variables.
+    ///
+    /// Unused declarations are printed as warning to stderr, or fail the test in
+    /// strict mode.
+    pub fn check_unused_declarations(&self) {
+        self.state.data().check_unused = true;
+    }
+
+    /// Returns the sorted names of all declared variables which were not read
+    /// through [`var`] or [`var_os`] so far.
+    pub fn unused_declarations(&self) -> Vec<String> {
+        let data = self.state.data();
+        let mut unused: Vec<String> = data.declared.difference(&data.used).cloned().collect();
+        unused.sort();
+        unused
+    }
+
+    /// Freezes the scope, e.g. after the arrange phase of a test. Afterwards,
+    /// changing a variable through the scope, or through [`set_var`] and
+    /// [`remove_var`] while the scope is active, panics with the location of
+    /// this call.
+    #[track_caller]
+    pub fn freeze(&self) {
+        self.state.data().frozen = Some(std::panic::Location::caller());
+    }
+
+    /// Allows dropping the scope on another thread than the one which created it,
+    /// e.g. for async tests on a multi-threaded executor.
+    ///
+    /// Otherwise, dropping the scope on another thread restores the environment
+    /// at a time the creating thread does not control, so it panics after
+    /// restoring.
+    pub fn allow_drop_on_other_thread(&self) {
+        self.state.data().foreign_drop_allowed = true;
+    }
+
+    /// Starts recording every variable read through [`var`] and [`var_os`]
+    /// while this scope is active.
+    pub fn track_reads(&self) {
+        self.state.data().track_reads = true;
+    }
+
+    /// Returns the sorted names of all variables read since [`Self::track_reads`]
+    /// was called.
+    pub fn reads(&self) -> Vec<String> {
+        self.state.data().reads.iter().cloned().collect()
+    }
+
+    /// Sets a variable, which gets restored when this scope is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is empty or contains `=` or NUL, or if the value contains
+    /// NUL. Use [`Self::try_set`] to handle such input.
+    pub fn set(&self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        if let Err(err) = self.try_set(key, value) {
+            panic!("temp_env_vars: {err}");
+        }
+    }
+
+    /// Sets a variable, which gets restored when this scope is dropped. Invalid
+    /// names or values are reported as error instead of panicking.
+    pub fn try_set(
+        &self,
+        key: impl AsRef<OsStr>,
+        value: impl AsRef<OsStr>,
+    ) -> Result<(), EnvError> {
+        let (key, value) = (key.as_ref(), value.as_ref());
+        error::validate(key, value)?;
+        self.write(key, Some(value));
+        Ok(())
+    }
+
+    /// Sets a variable like [`Self::set`] and marks it as secret with
+    /// [`mark_secret`], so its value never shows up in output of this crate.
+    ///
+    /// With the `secrecy` feature, the value can be a `secrecy::SecretString`.
+    /// It is exposed only to pass it to the environment and zeroized when this
+    /// returns; this crate does not copy it, unless observers registered with
+    /// [`Self::on_change`] get the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is empty or contains `=` or NUL, or if the value
+    /// contains NUL. The value is not part of the message.
+    pub fn set_secret(&self, key: impl AsRef<OsStr>, value: impl SecretValue) {
+        mark_secret(key.as_ref());
+        self.set(key, value.expose_env_value());
+    }
+
+    /// Sets a variable to a typed value, formatted by [`ToEnvValue`].
+    ///
+    /// ```rust
+    /// # use std::time::Duration;
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// env_scope.set_typed("PORT", 8080u16);
+    /// env_scope.set_typed("TIMEOUT", Duration::from_secs(5));
+    ///
+    /// assert_eq!(std::env::var("PORT").unwrap(), "8080");
+    /// assert_eq!(std::env::var("TIMEOUT").unwrap(), "5s");
+    /// ```
+    pub fn set_typed(&self, key: impl AsRef<OsStr>, value: impl ToEnvValue) {
+        self.set(key, value.to_env_value());
+    }
+
+    /// Sets a variable to a value formatted by the given function, for types
+    /// without [`ToEnvValue`] or needing another format.
+    pub fn set_formatted<T>(
+        &self,
+        key: impl AsRef<OsStr>,
+        value: T,
+        format: impl FnOnce(T) -> String,
+    ) {
+        self.set(key, format(value));
+    }
+
+    /// Sets a variable to a unique random value of letters and digits and returns
+    /// the value, e.g. for tokens the test needs to compare against later.
+    pub fn set_random(&self, key: impl AsRef<OsStr>) -> String {
+        self.set_random_with(key, random::DEFAULT_LEN, random::ALPHANUMERIC)
+    }
+
+    /// Like [`Self::set_random`], but the value has `len` characters taken from
+    /// `charset`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `charset` is empty or contains NUL.
+    pub fn set_random_with(&self, key: impl AsRef<OsStr>, len: usize, charset: &str) -> String {
+        let value = random::string(len, charset);
+        self.set(key, &value);
+        value
+    }
+
+    /// Sets all given variables while holding the lock once, so other threads
+    /// using the lock never see only some of them set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any name or value is invalid, before setting anything. Use
+    /// [`Self::try_set_all`] to handle such input.
+    pub fn set_many<I, K, V>(&self, vars: I)
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        if let Err(err) = self.try_set_all(vars) {
+            panic!("temp_env_vars: {err}");
+        }
+    }
+
+    /// Sets all given variables, if all of them are valid. If any is invalid,
+    /// none of them is set. The lock is held once for all of them.
+    pub fn try_set_all<I, K, V>(&self, vars: I) -> Result<(), EnvError>
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        let vars: Vec<(K, V)> = vars.into_iter().collect();
+        for (key, value) in &vars {
+            error::validate(key.as_ref(), value.as_ref())?;
+        }
+        let _lock = env_lock();
+        for (key, value) in &vars {
+            self.write(key.as_ref(), Some(value.as_ref()));
+        }
+        Ok(())
+    }
+
+    /// Sets the variables with a value and removes those with `None`, e.g. from
+    /// a `HashMap<&str, Option<&str>>`, while holding the lock once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any name or value is invalid, before changing anything.
+    pub fn apply<I, K, V>(&self, vars: I)
+    where
+        I: IntoIterator<Item = (K, Option<V>)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        let vars: Vec<(K, Option<V>)> = vars.into_iter().collect();
+        for (key, value) in &vars {
+            let result = match value {
+                Some(value) => error::validate(key.as_ref(), value.as_ref()),
+                None => error::validate_key(key.as_ref()),
+            };
+            if let Err(err) = result {
+                panic!("temp_env_vars: {err}");
+            }
+        }
+        let _lock = env_lock();
+        for (key, value) in &vars {
+            self.write(key.as_ref(), value.as_ref().map(AsRef::as_ref));
+        }
+    }
+
+    /// Sets each of the variables to every value of `profile` in turn, calling
+    /// `f` with the name and value each time. The variable gets its previous
+    /// value back after every iteration.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a name is empty or contains `=` or NUL.
+    pub fn fuzz<I, K>(&self, keys: I, profile: FuzzProfile, mut f: impl FnMut(&OsStr, &OsStr))
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<OsStr>,
+    {
+        let values = profile.values();
+        for key in keys {
+            let key = key.as_ref();
+            let previous = sys::var_os(key);
+            for value in &values {
+                self.set(key, value);
+                f(key, value);
+                match &previous {
+                    Some(previous) => self.set(key, previous),
+                    None => self.remove(key),
+                }
+            }
+        }
+    }
+
+    /// Removes a variable, which gets restored when this scope is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name is empty or contains `=` or NUL.
+    pub fn remove(&self, key: impl AsRef<OsStr>) {
+        let key = key.as_ref();
+        if let Err(err) = error::validate_key(key) {
+            panic!("temp_env_vars: {err}");
+        }
+        self.write(key, None);
+    }
+
+    /// Moves the value of `from` to `to`, e.g. for adapting real credentials to
+    /// the names the code under test expects. Both variables are restored when
+    /// this scope is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `from` is not set, or if a name is empty or contains `=` or NUL.
+    pub fn rename(&self, from: impl AsRef<OsStr>, to: impl AsRef<OsStr>) {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        let value = sys::var_os(from)
+            .unwrap_or_else(|| panic!("temp_env_vars: cannot rename {from:?}, it is not set"));
+        self.set(to, value);
+        if EnvKey::new(from) != EnvKey::new(to) {
+            self.remove(from);
+        }
+    }
+
+    /// Sets or removes a validated variable under the lock and notifies the
+    /// observers afterwards.
+    fn write(&self, key: &OsStr, value: Option<&OsStr>) {
+        self.state.check_not_frozen(key);
+        // Without observers, values are not copied, e.g. secrets
+        let observed = self.restorer.has_observers();
+        let previous = {
+            let _lock = env_lock();
+            self.record(key);
+            let previous = if observed { sys::var_os(key) } else { None };
+            match value {
+                Some(value) => sys::set_var(key, value),
+                None => sys::remove_var(key),
+            }
+            previous
+        };
+        if !observed {
+            return;
+        }
+        let key = key.to_os_string();
+        self.restorer.notify(match value {
+            Some(value) => EnvEvent::Set {
+                key,
+                previous,
+                value: value.to_os_string(),
+            },
+            None => EnvEvent::Remove { key, previous },
         });
     }
+
+    /// Records the original value of a variable about to be changed through
+    /// the scope, unless it was changed before.
+    fn record(&self, key: &OsStr) {
+        let mut journal = self.restorer.journal();
+        let key = EnvKey::new(key);
+        if !journal.iter().any(|(changed, _)| *changed == key) {
+            self.state
+                .data()
+                .overridden
+                .push(key.as_os_str().to_string_lossy().into_owned());
+            let value = sys::var_os(key.as_os_str());
+            journal.push((key, value));
+        }
+    }
 }
 
 impl Drop for TempEnvScope {
     fn drop(&mut self) {
-        self.restore();
+        let thread_panicked = self.join_threads();
+        let dropped_on_other_thread = std::thread::current().id() != self.state.thread
+            && !self.state.data().foreign_drop_allowed;
+        let expired = self.stop_watchdog();
+        let inner = registry::newer_scope_of_thread(&self.state);
+        registry::unregister(&self.state);
+        let changed_while_read_only = self.read_only
+            && matches!(&self.restorer.baseline, Baseline::Snapshot(original_vars) if **original_vars != snapshot::capture());
+        let late_write_window = self.state.data().late_write_window;
+        let restored_keys: Vec<OsString> = match late_write_window {
+            Some(_) => self
+                .restorer
+                .restore_plan()
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect(),
+            None => Vec::new(),
+        };
+        if (!self.read_only || changed_while_read_only) && !expired {
+            #[cfg(feature = "tracing")]
+            tracing::debug!(
+                "temp_env_vars: scope {} restores the environment",
+                self.state
+            );
+            self.restorer.notify_external_changes();
+            self.restorer.restore();
+        }
+        self.restorer.run_restore_hooks();
+        let late_writes = match late_write_window {
+            Some(window) if !std::thread::panicking() => late_write::poll(restored_keys, window),
+            _ => Vec::new(),
+        };
+        leak_detection::scope_dropped();
+        if changed_while_read_only && !std::thread::panicking() {
+            panic!(
+                "temp_env_vars: the environment was changed within a read-only scope {}",
+                self.state
+            );
+        }
+        let (check_unused, strict) = {
+            let data = self.state.data();
+            (data.check_unused, data.strict)
+        };
+        let unused = self.unused_declarations();
+        if check_unused && !unused.is_empty() && !std::thread::panicking() {
+            let message = format!(
+                "temp_env_vars: declared environment variables {unused:?} were never read in scope {}",
+                self.state
+            );
+            if strict {
+                panic!("{message}");
+            }
+            eprintln!("{message}");
+        }
+        if expired && !std::thread::panicking() {
+            panic!(
+                "temp_env_vars: scope {} was dropped after its deadline, the watchdog restored the environment before",
+                self.state
+            );
+        }
+        if !late_writes.is_empty() {
+            panic!(
+                "temp_env_vars: variables {late_writes:?} were changed again within {:?} after scope {} restored them, probably by a background thread",
+                late_write_window.unwrap_or_default(),
+                self.state
+            );
+        }
+        if thread_panicked && !std::thread::panicking() {
+            panic!(
+                "temp_env_vars: a thread spawned by scope {} panicked",
+                self.state
+            );
+        }
+        self.check_drop_order(inner);
+        if dropped_on_other_thread && !std::thread::panicking() {
+            panic!(
+                "temp_env_vars: scope {} created at {} was dropped on another thread; keep it on the creating thread or call `allow_drop_on_other_thread`",
+                self.state, self.state.location
+            );
+        }
+    }
+}
+
+/// Shows the captured variables, with the values of secrets like `*_TOKEN` redacted.
+impl fmt::Debug for TempEnvScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let journal = self.restorer.journal();
+        let mut debug = f.debug_struct("TempEnvScope");
+        debug
+            .field("name", &self.name())
+            .field("read_only", &self.read_only);
+        if let Baseline::Snapshot(original_vars) = &self.restorer.baseline {
+            debug.field(
+                "original_vars",
+                &redact::Vars::new(original_vars.iter().map(|(k, v)| (k.as_os_str(), v))),
+            );
+        }
+        debug
+            .field(
+                "changed",
+                &redact::Vars::new(journal.iter().map(|(k, v)| (k.as_os_str(), v))),
+            )
+            .finish()
+    }
+}
+
+impl Default for TempEnvScope {
+    #[track_caller]
+    fn default() -> Self {
+        Self::new()
     }
 }
 
@@ -119,10 +1163,15 @@ impl Drop for TempEnvScope {
 mod tests {
     use std::collections::HashMap;
 
-    use assertor::{assert_that, EqualityAssertion, ResultAssertion};
+    use assertor::{
+        assert_that, BooleanAssertion, EqualityAssertion, OptionAssertion, ResultAssertion,
+        StringAssertion,
+    };
     use serial_test::serial;
 
-    use super::TempEnvScope;
+    use std::{ffi::OsString, time::Duration};
+
+    use super::{error, EnvError, FuzzProfile, TempEnvScope};
 
     #[test]
     #[serial]
@@ -185,6 +1234,299 @@ mod tests {
         assert_that!(after).is_equal_to(original);
     }
 
+    #[test]
+    #[serial]
+    fn test_empty_vars_are_restored() {
+        std::env::set_var("FOO_EMPTY", "");
+
+        {
+            let _env_scope = TempEnvScope::new();
+            std::env::set_var("FOO_EMPTY", "BAR");
+        }
+        assert_that!(std::env::var("FOO_EMPTY")).has_ok(String::new());
+
+        {
+            let _env_scope = TempEnvScope::new();
+            std::env::remove_var("FOO_EMPTY");
+        }
+        assert_that!(std::env::var("FOO_EMPTY")).has_ok(String::new());
+        std::env::remove_var("FOO_EMPTY");
+    }
+
+    #[test]
+    #[serial]
+    fn test_try_new_accepts_regular_env() {
+        std::env::set_var("FOO", "BAR10");
+
+        let env_scope = TempEnvScope::try_new();
+
+        assert_that!(env_scope.is_ok()).is_true();
+    }
+
+    #[test]
+    fn test_malformed_existing_vars_are_detected() {
+        assert_that!(error::validate_existing("A=B".as_ref(), "1".as_ref()))
+            .has_err(EnvError::MalformedKey { key: "A=B".into() });
+        assert_that!(error::validate_existing("".as_ref(), "1".as_ref()))
+            .has_err(EnvError::MalformedKey { key: "".into() });
+        assert_that!(error::validate_existing("FOO".as_ref(), "1".as_ref())).is_ok();
+    }
+
+    #[test]
+    #[serial]
+    fn test_vars_set_through_the_scope_are_restored() {
+        std::env::remove_var("FOO");
+
+        {
+            let env_scope = TempEnvScope::new();
+            env_scope.set("FOO", "BAR9");
+            assert_that!(std::env::var("FOO")).has_ok("BAR9".to_string());
+        }
+
+        assert_that!(std::env::var("FOO")).is_err();
+    }
+
+    #[test]
+    #[serial]
+    fn test_journaled_scope_restores_changed_vars_only() {
+        std::env::set_var("FOO", "BAR");
+        std::env::remove_var("FOO2");
+
+        {
+            let env_scope = TempEnvScope::journaled();
+            env_scope.set("FOO", "BAR9");
+            env_scope.set("FOO", "BAR10");
+            env_scope.set("FOO2", "BAR");
+            std::env::set_var("FOO3", "BAR");
+        }
+
+        assert_that!(std::env::var("FOO")).has_ok("BAR".to_string());
+        assert_that!(std::env::var("FOO2")).is_err();
+        assert_that!(std::env::var("FOO3")).has_ok("BAR".to_string());
+        std::env::remove_var("FOO");
+        std::env::remove_var("FOO3");
+    }
+
+    #[test]
+    #[serial]
+    fn test_vars_are_restored_in_reverse_order_of_change() {
+        std::env::remove_var("FOO");
+        std::env::remove_var("FOO2");
+        std::env::remove_var("FOO3");
+
+        // Created one after another, so each is dropped before the next one exists
+        for new_scope in [
+            TempEnvScope::new as fn() -> TempEnvScope,
+            TempEnvScope::journaled,
+        ] {
+            let env_scope = new_scope();
+            env_scope.set("FOO2", "1");
+            env_scope.set("FOO", "1");
+            env_scope.set("FOO3", "1");
+            env_scope.set("FOO2", "2");
+
+            let order: Vec<OsString> = env_scope
+                .restorer
+                .restore_plan()
+                .into_iter()
+                .map(|(key, _)| key)
+                .collect();
+
+            assert_that!(order).is_equal_to(vec!["FOO3".into(), "FOO".into(), "FOO2".into()]);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_other_vars_are_restored_sorted_by_name() {
+        let env_scope = TempEnvScope::new();
+        std::env::set_var("FOO_B", "1");
+        std::env::set_var("FOO_A", "1");
+        env_scope.set("FOO_C", "1");
+
+        let order: Vec<OsString> = env_scope
+            .restorer
+            .restore_plan()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+
+        assert_that!(order).is_equal_to(vec!["FOO_C".into(), "FOO_A".into(), "FOO_B".into()]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_named_scope_is_identified_by_its_name() {
+        let env_scope = TempEnvScope::named("db-config-test");
+
+        assert_that!(env_scope.name()).is_equal_to(Some("db-config-test"));
+        assert_that!(format!("{env_scope:?}")).contains("db-config-test");
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "read-only scope `db-config-test`")]
+    fn test_named_scope_appears_in_panic_messages() {
+        let mut env_scope = TempEnvScope::named("db-config-test");
+        env_scope.read_only = true;
+        std::env::set_var("FOO_NAMED", "1");
+    }
+
+    #[test]
+    #[serial]
+    fn test_secret_values_are_redacted_in_debug_output() {
+        std::env::set_var("FOO_TOKEN", "token-value");
+        let env_scope = TempEnvScope::new();
+        env_scope.set("FOO_PASSWORD", "password-value");
+
+        let output = format!("{env_scope:?}");
+
+        assert_that!(output).contains("FOO_TOKEN");
+        assert_that!(output).does_not_contain("token-value");
+        assert_that!(output).does_not_contain("password-value");
+        drop(env_scope);
+        std::env::remove_var("FOO_TOKEN");
+    }
+
+    #[test]
+    #[serial]
+    fn test_random_values_are_set_and_returned() {
+        let env_scope = TempEnvScope::new();
+
+        let value = env_scope.set_random("FOO_RANDOM");
+        let other = env_scope.set_random_with("FOO_RANDOM2", 8, "0123456789");
+
+        assert_that!(std::env::var("FOO_RANDOM")).has_ok(value.clone());
+        assert_that!(std::env::var("FOO_RANDOM2")).has_ok(other.clone());
+        assert_that!(value.len()).is_equal_to(32);
+        assert_that!(other.chars().all(|c| c.is_ascii_digit())).is_true();
+    }
+
+    #[test]
+    #[serial]
+    fn test_typed_values_are_formatted() {
+        let env_scope = TempEnvScope::new();
+
+        env_scope.set_typed("FOO_DEBUG", true);
+        env_scope.set_formatted("FOO_TIMEOUT", Duration::from_secs(2), |d| {
+            d.as_millis().to_string()
+        });
+
+        assert_that!(std::env::var("FOO_DEBUG")).has_ok("true".to_string());
+        assert_that!(std::env::var("FOO_TIMEOUT")).has_ok("2000".to_string());
+    }
+
+    #[test]
+    #[serial]
+    fn test_invalid_vars_are_reported() {
+        let env_scope = TempEnvScope::new();
+
+        assert_that!(env_scope.try_set("FOO=BAR", "1")).has_err(EnvError::KeyContainsEquals {
+            key: "FOO=BAR".into(),
+        });
+        assert_that!(env_scope.try_set("", "1")).has_err(EnvError::EmptyKey);
+        assert_that!(env_scope.try_set("FOO", "BAR\0"))
+            .has_err(EnvError::ValueContainsNul { key: "FOO".into() });
+    }
+
+    #[test]
+    #[serial]
+    fn test_invalid_bulk_input_sets_nothing() {
+        std::env::remove_var("FOO");
+        let env_scope = TempEnvScope::new();
+
+        let result = env_scope.try_set_all([("FOO", "1"), ("BAR\0", "2")]);
+
+        assert_that!(result).has_err(EnvError::KeyContainsNul {
+            key: "BAR\0".into(),
+        });
+        assert_that!(std::env::var("FOO")).is_err();
+    }
+
+    #[test]
+    #[serial]
+    fn test_apply_sets_and_removes() {
+        std::env::set_var("FOO", "BAR");
+        {
+            let env_scope = TempEnvScope::new();
+
+            env_scope.apply([("FOO", None), ("BAZ", Some("1"))]);
+
+            assert_that!(std::env::var("FOO")).is_err();
+            assert_that!(std::env::var("BAZ")).has_ok("1".to_string());
+        }
+        assert_that!(std::env::var("FOO")).has_ok("BAR".to_string());
+        assert_that!(std::env::var("BAZ")).is_err();
+        std::env::remove_var("FOO");
+    }
+
+    #[test]
+    #[serial]
+    fn test_fuzzed_var_is_restored_between_iterations() {
+        std::env::set_var("FOO", "BAR");
+        let env_scope = TempEnvScope::new();
+        let mut values = Vec::new();
+
+        env_scope.fuzz(["FOO"], FuzzProfile::Hostile, |key, value| {
+            assert_that!(std::env::var_os(key)).has_value(value.to_os_string());
+            values.push(value.to_os_string());
+        });
+
+        assert_that!(values.len()).is_equal_to(FuzzProfile::Hostile.values().len());
+        assert_that!(values.contains(&OsString::new())).is_true();
+        assert_that!(std::env::var("FOO")).has_ok("BAR".to_string());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[serial]
+    fn test_non_unicode_vars_are_restored() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let value = OsStr::from_bytes(b"BAR\xff");
+        std::env::set_var("FOO_NON_UNICODE", value);
+        std::env::set_var(OsStr::from_bytes(b"FOO_\xfe"), "BAR");
+
+        {
+            let _env_scope = TempEnvScope::new();
+            std::env::remove_var("FOO_NON_UNICODE");
+            std::env::remove_var(OsStr::from_bytes(b"FOO_\xfe"));
+        }
+
+        assert_that!(std::env::var_os("FOO_NON_UNICODE")).is_equal_to(Some(value.to_os_string()));
+        assert_that!(std::env::var_os(OsStr::from_bytes(b"FOO_\xfe")))
+            .is_equal_to(Some("BAR".into()));
+        std::env::remove_var("FOO_NON_UNICODE");
+        std::env::remove_var(OsStr::from_bytes(b"FOO_\xfe"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_read_only_scope_accepts_unchanged_env() {
+        std::env::set_var("FOO", "BAR7");
+
+        {
+            let _env_scope = TempEnvScope::read_only();
+            assert_that!(std::env::var("FOO")).has_ok("BAR7".to_string());
+        }
+
+        assert_that!(std::env::var("FOO")).has_ok("BAR7".to_string());
+    }
+
+    #[test]
+    #[serial]
+    fn test_read_only_scope_restores_and_panics_on_change() {
+        std::env::remove_var("FOO");
+
+        let result = std::panic::catch_unwind(|| {
+            let _env_scope = TempEnvScope::read_only();
+            std::env::set_var("FOO", "BAR8");
+        });
+
+        assert_that!(result.is_err()).is_true();
+        assert_that!(std::env::var("FOO")).is_err();
+    }
+
     #[test]
     #[serial]
     fn test_two_scopes_active_at_same_time() {
@@ -222,4 +1564,57 @@ mod tests {
         }
         assert_that!(std::env::var("FOO")).is_err();
     }
+
+    #[test]
+    #[serial]
+    fn test_renamed_vars_are_restored() {
+        std::env::set_var("PROD_DB_URL", "postgres://prod");
+        std::env::remove_var("DB_URL");
+
+        {
+            let env_scope = TempEnvScope::new();
+            env_scope.rename("PROD_DB_URL", "DB_URL");
+
+            assert_that!(std::env::var("DB_URL")).has_ok("postgres://prod".to_string());
+            assert_that!(std::env::var("PROD_DB_URL")).is_err();
+        }
+
+        assert_that!(std::env::var("PROD_DB_URL")).has_ok("postgres://prod".to_string());
+        assert_that!(std::env::var("DB_URL")).is_err();
+        std::env::remove_var("PROD_DB_URL");
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "was frozen at")]
+    fn test_frozen_scope_rejects_changes() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("FROZEN_VAR", "1");
+        env_scope.freeze();
+
+        env_scope.set("FROZEN_VAR", "2");
+    }
+
+    #[test]
+    #[serial]
+    fn test_drop_on_other_thread_panics_after_restoring() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("FOREIGN_DROP", "1");
+
+        let dropped = std::thread::spawn(move || drop(env_scope)).join();
+
+        assert_that!(dropped.is_err()).is_true();
+        assert_that!(std::env::var("FOREIGN_DROP")).is_err();
+    }
+
+    #[test]
+    #[serial]
+    fn test_drop_on_other_thread_can_be_allowed() {
+        let env_scope = TempEnvScope::new();
+        env_scope.allow_drop_on_other_thread();
+
+        let dropped = std::thread::spawn(move || drop(env_scope)).join();
+
+        assert_that!(dropped.is_ok()).is_true();
+    }
 }
@@ -0,0 +1,910 @@
+//! The lock coordinating all tests which use the environment.
+//!
+//! Tests mutating the environment need exclusive access, while tests which only
+//! read the environment can share the lock with each other. Waiting exclusive
+//! holders take precedence over new shared holders, so a steady stream of
+//! read-only tests cannot starve a mutating test.
+//!
+//! With the `parking_lot` feature, the lock is built on `parking_lot` primitives,
+//! which have no poisoning and less overhead for large suites.
+//!
+//! With the `tokio` feature, the lock can also be acquired asynchronously, so
+//! async tests waiting for the lock don't block the executor.
+//!
+//! With the `file_lock` feature, the global lock additionally holds an advisory
+//! lock on a file in the `target/` directory while it is held by any thread.
+//! This serializes env-mutating tests of different test binaries running at the
+//! same time, e.g. with nextest, which runs each test in its own process.
+
+use std::{
+    cell::Cell,
+    collections::BTreeSet,
+    thread::{self, ThreadId},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "parking_lot")]
+use parking_lot::{Condvar, Mutex, MutexGuard};
+#[cfg(not(feature = "parking_lot"))]
+use std::sync::{Condvar, Mutex, MutexGuard, PoisonError};
+
+/// The lock used by `#[temp_env_vars]` and [`env_lock`].
+static ENV_LOCK: EnvLock = EnvLock::global();
+
+thread_local! {
+    /// Guards of [`ENV_LOCK`] held by the current thread.
+    static HELD: Cell<Held> = const { Cell::new(Held { exclusive: 0, shared: 0, token: 0 }) };
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Held {
+    exclusive: usize,
+    shared: usize,
+    /// Token of the raw guard the counted guards rely on, see [`LockState::tokens`].
+    token: u64,
+}
+
+/// Guard of the lock used by `#[temp_env_vars]`. The lock is released when the
+/// guard is dropped.
+#[derive(Debug)]
+#[must_use = "the lock is released as soon as the guard is dropped"]
+pub struct EnvLockGuard {
+    /// `None` if the current thread already held the lock when this guard was created.
+    raw: Option<RawGuard<'static>>,
+    exclusive: bool,
+    owner: ThreadId,
+}
+
+/// Releases the global lock if the given thread holds it exclusively, e.g. when
+/// a test hangs. Returns whether it was released.
+pub(crate) fn release_exclusive_of(owner: ThreadId) -> bool {
+    ENV_LOCK.revoke_exclusive_of(owner)
+}
+
+/// Returns the guards held by the current thread, for passing them on to a
+/// thread which finishes before they are released.
+pub(crate) fn held_by_current_thread() -> Held {
+    HELD.get()
+}
+
+/// Lets the current thread use the lock as if it held the given guards.
+pub(crate) fn inherit(held: Held) {
+    HELD.set(held);
+}
+
+/// Acquires the lock used by `#[temp_env_vars]` exclusively, blocking until all
+/// other holders released it.
+///
+/// Use this to serialize hand-rolled test helpers or other crates' tests against
+/// the tests using this crate. The lock is reentrant: if the current thread
+/// already holds it, e.g. within a `#[temp_env_vars]` test, this returns
+/// immediately.
+///
+/// ```rust
+/// let _lock = temp_env_vars::env_lock();
+/// std::env::set_var("FOO", "BAR");
+/// # std::env::remove_var("FOO");
+/// ```
+///
+/// # Panics
+///
+/// Panics if the current thread only holds the lock shared, e.g. within a
+/// `#[temp_env_vars(read_only)]` test.
+pub fn env_lock() -> EnvLockGuard {
+    EnvLockGuard::reentrant(true).unwrap_or_else(|| EnvLockGuard::new(ENV_LOCK.write(), true))
+}
+
+/// Acquires the lock used by `#[temp_env_vars]` exclusively for the named
+/// holder, e.g. the path of a test.
+///
+/// This is the entry point for other test macros composing with this crate,
+/// like a team's `#[integration_test]`: the generated code holds the returned
+/// guard for the duration of the test. It behaves like [`env_lock`] followed by
+/// [`EnvLockGuard::set_holder_name`], and is kept stable across releases.
+///
+/// ```rust
+/// // Code generated by an attribute macro for `fn connects_with_tls()`
+/// let _lock = temp_env_vars::acquire(concat!(module_path!(), "::connects_with_tls"));
+/// let _env_scope = temp_env_vars::TempEnvScope::new();
+/// ```
+///
+/// # Panics
+///
+/// Panics if the current thread only holds the lock shared.
+pub fn acquire(holder: impl Into<String>) -> EnvLockGuard {
+    let guard = env_lock();
+    guard.set_holder_name(holder);
+    guard
+}
+
+/// Acquires the lock used by `#[temp_env_vars]` exclusively if no other thread
+/// holds it, without blocking. Like [`env_lock`], this returns immediately if
+/// the current thread already holds the lock.
+///
+/// ```rust
+/// match temp_env_vars::try_env_lock() {
+///     Some(_lock) => std::env::set_var("FOO", "BAR"),
+///     None => eprintln!("{}", temp_env_vars::env_lock_status()),
+/// }
+/// # std::env::remove_var("FOO");
+/// ```
+///
+/// # Panics
+///
+/// Panics if the current thread only holds the lock shared.
+pub fn try_env_lock() -> Option<EnvLockGuard> {
+    EnvLockGuard::reentrant(true).or_else(|| Some(EnvLockGuard::new(ENV_LOCK.try_write()?, true)))
+}
+
+/// Returns the current state of the lock used by `#[temp_env_vars]`, e.g. for
+/// reporting contention instead of silently waiting.
+pub fn env_lock_status() -> EnvLockStatus {
+    ENV_LOCK.status()
+}
+
+/// State of the lock returned by [`env_lock_status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvLockStatus {
+    /// Whether a thread holds the lock exclusively.
+    pub exclusive: bool,
+    /// The number of shared holders.
+    pub shared: usize,
+    /// The number of threads waiting for exclusive access.
+    pub waiting: usize,
+    /// How long the lock has been held without interruption, `None` if it is free.
+    pub held_for: Option<Duration>,
+    /// The exclusive holder, if any.
+    pub holder: Option<LockHolder>,
+}
+
+/// The exclusive holder of the lock, reported by [`env_lock_status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockHolder {
+    /// The name given with [`EnvLockGuard::set_holder_name`], e.g. the path of
+    /// the test function for `#[temp_env_vars]` tests.
+    pub name: Option<String>,
+    /// The name of the thread which acquired the lock. The test harness names
+    /// the threads of tests after the test.
+    pub thread: Option<String>,
+}
+
+impl std::fmt::Display for LockHolder {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.name, &self.thread) {
+            (Some(name), _) | (None, Some(name)) => write!(f, "`{name}`"),
+            (None, None) => write!(f, "an unnamed thread"),
+        }
+    }
+}
+
+impl EnvLockStatus {
+    /// Returns whether the lock is held by anyone.
+    pub fn is_held(&self) -> bool {
+        self.exclusive || self.shared > 0
+    }
+}
+
+impl std::fmt::Display for EnvLockStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let held_for = self.held_for.unwrap_or_default().as_secs();
+        if let Some(holder) = &self.holder {
+            write!(f, "env lock held by {holder} for {held_for}s")?;
+        } else if self.exclusive {
+            write!(f, "env lock held exclusively for {held_for}s")?;
+        } else if self.shared > 0 {
+            write!(
+                f,
+                "env lock held by {} readers for {held_for}s",
+                self.shared
+            )?;
+        } else {
+            write!(f, "env lock is free")?;
+        }
+        if self.waiting > 0 {
+            write!(f, ", {} waiting", self.waiting)?;
+        }
+        Ok(())
+    }
+}
+
+/// Acquires the lock used by `#[temp_env_vars]` shared with other readers,
+/// blocking until no exclusive holder exists.
+pub fn env_read_lock() -> EnvLockGuard {
+    EnvLockGuard::reentrant(false).unwrap_or_else(|| EnvLockGuard::new(ENV_LOCK.read(), false))
+}
+
+/// Same as [`env_lock`], but waits without blocking the thread.
+#[cfg(feature = "tokio")]
+pub async fn env_lock_async() -> EnvLockGuard {
+    match EnvLockGuard::reentrant(true) {
+        Some(guard) => guard,
+        None => EnvLockGuard::new(ENV_LOCK.write_async().await, true),
+    }
+}
+
+/// Same as [`env_lock_async`], but also waits if the current thread already
+/// holds the lock. Executors interleaving tasks on one thread, like the one of
+/// `cucumber`, would otherwise let every task reenter the lock of the first one.
+#[cfg(feature = "cucumber")]
+pub(crate) async fn env_lock_async_per_task() -> EnvLockGuard {
+    let raw = ENV_LOCK.write_async().await;
+    // Other guards of this thread were released before the lock could be acquired
+    HELD.set(Held::default());
+    EnvLockGuard::new(raw, true)
+}
+
+/// Same as [`env_read_lock`], but waits without blocking the thread.
+#[cfg(feature = "tokio")]
+pub async fn env_read_lock_async() -> EnvLockGuard {
+    match EnvLockGuard::reentrant(false) {
+        Some(guard) => guard,
+        None => EnvLockGuard::new(ENV_LOCK.read_async().await, false),
+    }
+}
+
+impl EnvLockGuard {
+    fn new(raw: RawGuard<'static>, exclusive: bool) -> EnvLockGuard {
+        HELD.set(Held {
+            token: raw.token,
+            ..HELD.get()
+        });
+        let guard = EnvLockGuard {
+            raw: Some(raw),
+            exclusive,
+            owner: thread::current().id(),
+        };
+        guard.count(1);
+        guard
+    }
+
+    /// Returns a guard without acquiring the lock again if the current thread
+    /// already holds it.
+    fn reentrant(exclusive: bool) -> Option<EnvLockGuard> {
+        let mut held = HELD.get();
+        // The guard which acquired the lock might have been dropped on another
+        // thread, e.g. by an async executor, so the counts can be stale
+        if (held.exclusive > 0 || held.shared > 0) && !ENV_LOCK.holds(held.token) {
+            held = Held::default();
+            HELD.set(held);
+        }
+        if exclusive && held.exclusive == 0 && held.shared > 0 {
+            let scope = crate::registry::current_thread_scope()
+                .map(|scope| format!(" (within scope {scope})"))
+                .unwrap_or_default();
+            panic!("temp_env_vars: cannot lock the environment exclusively while holding it shared, e.g. within a read-only test{scope}");
+        }
+        if held.exclusive == 0 && held.shared == 0 {
+            return None;
+        }
+
+        let guard = EnvLockGuard {
+            raw: None,
+            exclusive: exclusive || held.exclusive > 0,
+            owner: thread::current().id(),
+        };
+        guard.count(1);
+        Some(guard)
+    }
+
+    /// Returns whether this guard grants exclusive access.
+    pub fn is_exclusive(&self) -> bool {
+        self.exclusive
+    }
+
+    /// Names the holder of the lock in [`env_lock_status`] and in panic
+    /// messages, e.g. with the path of the test. `#[temp_env_vars]` does this
+    /// for every test.
+    ///
+    /// Does nothing unless this guard acquired the lock exclusively.
+    pub fn set_holder_name(&self, name: impl Into<String>) {
+        if let Some(raw) = &self.raw {
+            raw.set_holder_name(name.into());
+        }
+    }
+
+    fn count(&self, delta: isize) {
+        let mut held = HELD.get();
+        let counter = if self.exclusive {
+            &mut held.exclusive
+        } else {
+            &mut held.shared
+        };
+        *counter = counter.saturating_add_signed(delta);
+        HELD.set(held);
+    }
+}
+
+impl Drop for EnvLockGuard {
+    fn drop(&mut self) {
+        // Guards of async tests might be dropped on another thread than they
+        // were created on, those don't count for the current thread
+        if self.owner == thread::current().id() {
+            self.count(-1);
+        }
+        self.raw.take();
+    }
+}
+
+#[derive(Debug, Default)]
+struct LockState {
+    exclusive: bool,
+    /// The thread which acquired the exclusive guard.
+    exclusive_owner: Option<ThreadId>,
+    holder: Option<LockHolder>,
+    /// Tokens of the raw guards currently held. A revoked guard loses its
+    /// token, see [`release_exclusive_of`].
+    tokens: BTreeSet<u64>,
+    next_token: u64,
+    shared: usize,
+    waiting_exclusive: usize,
+    /// When the lock was acquired after being free.
+    since: Option<Instant>,
+}
+
+impl LockState {
+    fn acquire_exclusive(&mut self) {
+        self.exclusive = true;
+        self.exclusive_owner = Some(thread::current().id());
+        self.holder = Some(LockHolder {
+            name: None,
+            thread: thread::current().name().map(String::from),
+        });
+        self.update_since();
+    }
+
+    fn issue_token(&mut self) -> u64 {
+        self.next_token += 1;
+        self.tokens.insert(self.next_token);
+        self.next_token
+    }
+
+    fn release_exclusive(&mut self) {
+        self.exclusive = false;
+        self.exclusive_owner = None;
+        self.holder = None;
+        self.update_since();
+    }
+
+    /// Starts or stops the time the lock is held, after a guard was acquired
+    /// or released.
+    fn update_since(&mut self) {
+        if !self.exclusive && self.shared == 0 {
+            self.since = None;
+        } else if self.since.is_none() {
+            self.since = Some(Instant::now());
+        }
+    }
+}
+
+/// Readers-writer lock for the environment.
+#[derive(Debug, Default)]
+pub(crate) struct EnvLock {
+    #[cfg(feature = "file_lock")]
+    cross_process: bool,
+    state: Mutex<LockState>,
+    /// The locked file while any guard of this process is held. Only taken
+    /// without holding the state, as locking the file blocks.
+    #[cfg(feature = "file_lock")]
+    file: Mutex<Option<LockedFile>>,
+    released: Condvar,
+    #[cfg(feature = "tokio")]
+    released_async: tokio::sync::Notify,
+}
+
+/// The lock file, locked shared or exclusively.
+#[cfg(feature = "file_lock")]
+#[derive(Debug)]
+struct LockedFile {
+    file: std::fs::File,
+    exclusive: bool,
+}
+
+/// Releases the [`EnvLock`] when dropped.
+#[derive(Debug)]
+pub(crate) struct RawGuard<'a> {
+    lock: &'a EnvLock,
+    exclusive: bool,
+    token: u64,
+}
+
+impl EnvLock {
+    #[cfg(test)]
+    pub const fn new() -> EnvLock {
+        EnvLock::with_cross_process(false)
+    }
+
+    /// Creates the lock shared by all tests of the process. With the `file_lock`
+    /// feature, this lock also coordinates with other processes, except under
+    /// Miri, which runs without file system access.
+    pub const fn global() -> EnvLock {
+        EnvLock::with_cross_process(!cfg!(miri))
+    }
+
+    #[cfg_attr(not(feature = "file_lock"), allow(unused_variables))]
+    const fn with_cross_process(cross_process: bool) -> EnvLock {
+        EnvLock {
+            #[cfg(feature = "file_lock")]
+            cross_process,
+            state: Mutex::new(LockState {
+                exclusive: false,
+                exclusive_owner: None,
+                holder: None,
+                tokens: BTreeSet::new(),
+                next_token: 0,
+                shared: 0,
+                waiting_exclusive: 0,
+                since: None,
+            }),
+            #[cfg(feature = "file_lock")]
+            file: Mutex::new(None),
+            released: Condvar::new(),
+            #[cfg(feature = "tokio")]
+            released_async: tokio::sync::Notify::const_new(),
+        }
+    }
+
+    /// Blocks until no other guard is held.
+    pub fn write(&self) -> RawGuard<'_> {
+        let mut state = self.state();
+        state.waiting_exclusive += 1;
+        while state.exclusive || state.shared > 0 {
+            state = self.wait(state);
+        }
+        state.waiting_exclusive -= 1;
+        state.acquire_exclusive();
+        self.acquired(state, true)
+    }
+
+    /// Blocks until no exclusive guard is held or waited for.
+    pub fn read(&self) -> RawGuard<'_> {
+        let mut state = self.state();
+        while state.exclusive || state.waiting_exclusive > 0 {
+            state = self.wait(state);
+        }
+        state.shared += 1;
+        state.update_since();
+        self.acquired(state, false)
+    }
+
+    /// Acquires the exclusive guard if no other guard is held, without waiting.
+    pub fn try_write(&self) -> Option<RawGuard<'_>> {
+        let mut state = self.state();
+        if state.exclusive || state.shared > 0 {
+            return None;
+        }
+        state.acquire_exclusive();
+        Some(self.acquired(state, true))
+    }
+
+    /// Creates the guard after the state was updated for it, then takes the
+    /// file lock without holding the state.
+    fn acquired(&self, mut state: MutexGuard<'_, LockState>, exclusive: bool) -> RawGuard<'_> {
+        let guard = RawGuard {
+            lock: self,
+            exclusive,
+            token: state.issue_token(),
+        };
+        drop(state);
+        // The guard releases the lock again if this panics
+        self.lock_file(exclusive);
+        guard
+    }
+
+    /// Returns whether the raw guard with the given token is still held.
+    fn holds(&self, token: u64) -> bool {
+        self.state().tokens.contains(&token)
+    }
+
+    pub fn status(&self) -> EnvLockStatus {
+        let state = self.state();
+        EnvLockStatus {
+            exclusive: state.exclusive,
+            shared: state.shared,
+            waiting: state.waiting_exclusive,
+            held_for: state.since.map(|since| since.elapsed()),
+            holder: state.holder.clone(),
+        }
+    }
+
+    /// Waits without blocking the thread until no other guard is held.
+    #[cfg(feature = "tokio")]
+    pub async fn write_async(&self) -> RawGuard<'_> {
+        let _waiting = WaitingExclusive::new(self);
+        loop {
+            let released = self.released_async.notified();
+            tokio::pin!(released);
+            // Register for the notification before checking, so no release gets lost
+            released.as_mut().enable();
+            {
+                let mut state = self.state();
+                if !state.exclusive && state.shared == 0 {
+                    state.acquire_exclusive();
+                    return self.acquired(state, true);
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Waits without blocking the thread until no exclusive guard is held or waited for.
+    #[cfg(feature = "tokio")]
+    pub async fn read_async(&self) -> RawGuard<'_> {
+        loop {
+            let released = self.released_async.notified();
+            tokio::pin!(released);
+            released.as_mut().enable();
+            {
+                let mut state = self.state();
+                if !state.exclusive && state.waiting_exclusive == 0 {
+                    state.shared += 1;
+                    state.update_since();
+                    return self.acquired(state, false);
+                }
+            }
+            released.await;
+        }
+    }
+
+    /// Takes the file lock when the first guard of this process is created, or
+    /// locks it exclusively for an exclusive guard if it is only locked shared,
+    /// e.g. by a reader which was released but did not unlock the file yet.
+    ///
+    /// Other threads of the process acquiring a guard meanwhile wait for the
+    /// file, so none of them returns before the file lock is acquired.
+    #[cfg(feature = "file_lock")]
+    fn lock_file(&self, exclusive: bool) {
+        use fs4::fs_std::FileExt;
+
+        if !self.cross_process {
+            return;
+        }
+        let mut locked_file = lock(&self.file);
+        if locked_file
+            .as_ref()
+            .is_some_and(|locked| locked.exclusive || !exclusive)
+        {
+            return;
+        }
+        let path = lock_file_path();
+        let file = match locked_file.take() {
+            // Windows cannot upgrade a shared lock, so it is released before
+            Some(locked) => {
+                let _ = FileExt::unlock(&locked.file);
+                locked.file
+            }
+            None => std::fs::OpenOptions::new()
+                .create(true)
+                .truncate(false)
+                .write(true)
+                .open(&path)
+                .unwrap_or_else(|err| {
+                    panic!("temp_env_vars: cannot open lock file {path:?}: {err}")
+                }),
+        };
+        let locked = if exclusive {
+            FileExt::lock_exclusive(&file)
+        } else {
+            FileExt::lock_shared(&file)
+        };
+        locked.unwrap_or_else(|err| panic!("temp_env_vars: cannot lock file {path:?}: {err}"));
+        *locked_file = Some(LockedFile { file, exclusive });
+    }
+
+    #[cfg(not(feature = "file_lock"))]
+    fn lock_file(&self, _exclusive: bool) {}
+
+    /// Releases the file lock when the last guard of this process was dropped,
+    /// unless another guard was acquired meanwhile.
+    #[cfg(feature = "file_lock")]
+    fn unlock_file(&self) {
+        let mut locked_file = lock(&self.file);
+        let state = self.state();
+        if state.exclusive || state.shared > 0 {
+            return;
+        }
+        drop(state);
+        if let Some(locked) = locked_file.take() {
+            let _ = fs4::fs_std::FileExt::unlock(&locked.file);
+        }
+    }
+
+    #[cfg(not(feature = "file_lock"))]
+    fn unlock_file(&self) {}
+
+    /// Releases the exclusive guard if the given thread holds it. The guard
+    /// does nothing when it is dropped later.
+    fn revoke_exclusive_of(&self, owner: ThreadId) -> bool {
+        let mut state = self.state();
+        if !state.exclusive || state.exclusive_owner != Some(owner) {
+            return false;
+        }
+        state.release_exclusive();
+        // The exclusive guard is the only one
+        state.tokens.clear();
+        drop(state);
+        self.unlock_file();
+        self.notify_released();
+        true
+    }
+
+    fn notify_released(&self) {
+        self.released.notify_all();
+        #[cfg(feature = "tokio")]
+        self.released_async.notify_waiters();
+    }
+
+    fn state(&self) -> MutexGuard<'_, LockState> {
+        lock(&self.state)
+    }
+
+    #[cfg(not(feature = "parking_lot"))]
+    fn wait<'a>(&self, state: MutexGuard<'a, LockState>) -> MutexGuard<'a, LockState> {
+        self.released
+            .wait(state)
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    #[cfg(feature = "parking_lot")]
+    fn wait<'a>(&self, mut state: MutexGuard<'a, LockState>) -> MutexGuard<'a, LockState> {
+        self.released.wait(&mut state);
+        state
+    }
+}
+
+impl RawGuard<'_> {
+    fn set_holder_name(&self, name: String) {
+        let mut state = self.lock.state();
+        if self.exclusive && state.tokens.contains(&self.token) {
+            if let Some(holder) = &mut state.holder {
+                holder.name = Some(name);
+            }
+        }
+    }
+}
+
+impl Drop for RawGuard<'_> {
+    fn drop(&mut self) {
+        let mut state = self.lock.state();
+        if !state.tokens.remove(&self.token) {
+            // Revoked already
+            return;
+        }
+        if self.exclusive {
+            state.release_exclusive();
+        } else {
+            state.shared -= 1;
+            state.update_since();
+        }
+        drop(state);
+        self.lock.unlock_file();
+        self.lock.notify_released();
+    }
+}
+
+#[cfg(not(feature = "parking_lot"))]
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(feature = "parking_lot")]
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock()
+}
+
+/// Location of the lock file: `TEMP_ENV_VARS_LOCK_FILE` if set, otherwise
+/// `temp_env_vars.lock` in the target directory of the running test binary.
+#[cfg(feature = "file_lock")]
+fn lock_file_path() -> std::path::PathBuf {
+    if let Some(path) = std::env::var_os("TEMP_ENV_VARS_LOCK_FILE") {
+        return path.into();
+    }
+    let target_dir = std::env::var_os("CARGO_TARGET_DIR")
+        .map(std::path::PathBuf::from)
+        .or_else(|| {
+            // Test binaries live in `<target>/<profile>/deps/`
+            let exe = std::env::current_exe().ok()?;
+            Some(exe.parent()?.parent()?.parent()?.to_path_buf())
+        })
+        .unwrap_or_else(std::env::temp_dir);
+    target_dir.join("temp_env_vars.lock")
+}
+
+/// Counts an async exclusive waiter for as long as it waits, even if the waiting
+/// future gets cancelled.
+#[cfg(feature = "tokio")]
+struct WaitingExclusive<'a> {
+    lock: &'a EnvLock,
+}
+
+#[cfg(feature = "tokio")]
+impl<'a> WaitingExclusive<'a> {
+    fn new(lock: &'a EnvLock) -> WaitingExclusive<'a> {
+        lock.state().waiting_exclusive += 1;
+        WaitingExclusive { lock }
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Drop for WaitingExclusive<'_> {
+    fn drop(&mut self) {
+        self.lock.state().waiting_exclusive -= 1;
+        // Shared waiters might have been held back by this waiter
+        self.lock.notify_released();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
+    };
+
+    use assertor::{assert_that, BooleanAssertion, StringAssertion};
+
+    use super::EnvLock;
+
+    #[test]
+    fn test_shared_guards_can_be_held_together() {
+        let lock = EnvLock::new();
+
+        let _first = lock.read();
+        let _second = lock.read();
+    }
+
+    #[test]
+    fn test_exclusive_guard_waits_for_shared_guards() {
+        let lock = Arc::new(EnvLock::new());
+        let acquired = Arc::new(AtomicBool::new(false));
+
+        let shared = lock.read();
+        let writer = {
+            let lock = Arc::clone(&lock);
+            let acquired = Arc::clone(&acquired);
+            thread::spawn(move || {
+                let _guard = lock.write();
+                acquired.store(true, Ordering::SeqCst);
+            })
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        assert_that!(acquired.load(Ordering::SeqCst)).is_false();
+
+        drop(shared);
+        writer.join().unwrap();
+        assert_that!(acquired.load(Ordering::SeqCst)).is_true();
+    }
+
+    #[test]
+    fn test_status_reports_holders() {
+        let lock = EnvLock::new();
+        assert_that!(lock.status().is_held()).is_false();
+
+        let guard = lock.write();
+        guard.set_holder_name("tests::holder".to_string());
+        let status = lock.status();
+
+        assert_that!(status.exclusive).is_true();
+        assert_that!(status.to_string()).starts_with("env lock held by `tests::holder` for");
+        assert_that!(status.held_for.is_some()).is_true();
+        assert_that!(lock.try_write().is_none()).is_true();
+
+        drop(guard);
+        assert_that!(lock.status().held_for.is_none()).is_true();
+        assert_that!(lock.try_write().is_some()).is_true();
+    }
+
+    #[test]
+    fn test_env_lock_is_reentrant() {
+        let outer = super::env_lock();
+        let inner = super::env_lock();
+        let shared = super::env_read_lock();
+
+        assert_that!(inner.is_exclusive()).is_true();
+        assert_that!(shared.is_exclusive()).is_true();
+        drop((shared, inner, outer));
+
+        let _other_thread = thread::spawn(super::env_lock).join().unwrap();
+    }
+
+    #[test]
+    fn test_guard_dropped_on_other_thread_releases_the_thread() {
+        let guard = super::env_lock();
+        thread::spawn(move || drop(guard)).join().unwrap();
+
+        let guard = super::env_lock();
+
+        assert_that!(guard.raw.is_some()).is_true();
+    }
+
+    #[test]
+    #[should_panic(expected = "while holding it shared")]
+    fn test_exclusive_lock_within_shared_lock_panics() {
+        let _shared = super::env_read_lock();
+        let _exclusive = super::env_lock();
+    }
+
+    #[cfg(all(feature = "file_lock", not(miri)))]
+    #[test]
+    fn test_global_lock_holds_the_file_lock() {
+        use fs4::fs_std::FileExt;
+
+        let lock = EnvLock::global();
+
+        let guard = lock.write();
+        let other = std::fs::File::open(super::lock_file_path()).unwrap();
+        assert_that!(FileExt::try_lock_shared(&other).unwrap_or(false)).is_false();
+
+        drop(guard);
+        assert_that!(FileExt::try_lock_shared(&other).unwrap_or(false)).is_true();
+    }
+
+    #[cfg(all(feature = "file_lock", not(miri)))]
+    #[test]
+    fn test_writer_after_reader_locks_the_file_exclusively() {
+        const CHILD_MARKER: &str = "TEMP_ENV_VARS_LOCK_TEST_CHILD";
+        if std::env::var_os(CHILD_MARKER).is_some() {
+            let file = std::fs::File::open(super::lock_file_path()).unwrap();
+            let locked = fs4::fs_std::FileExt::try_lock_shared(&file).unwrap_or(false);
+            println!("lock test child locked shared: {locked}");
+            return;
+        }
+        let lock = EnvLock::global();
+        let reader = lock.read();
+
+        let writer = thread::scope(|scope| {
+            let file = super::lock(&lock.file);
+            // The reader is released, but did not unlock the file yet
+            let releasing = scope.spawn(move || drop(reader));
+            while lock.status().shared > 0 {
+                thread::yield_now();
+            }
+            let writing = scope.spawn(|| lock.write());
+            while !lock.status().exclusive {
+                thread::yield_now();
+            }
+            drop(file);
+            releasing.join().unwrap();
+            writing.join().unwrap()
+        });
+
+        // Another process must not get the file while the writer holds it
+        let child = std::process::Command::new(std::env::current_exe().unwrap())
+            .args([
+                "lock::tests::test_writer_after_reader_locks_the_file_exclusively",
+                "--exact",
+                "--nocapture",
+            ])
+            .env(CHILD_MARKER, "1")
+            .output()
+            .unwrap();
+        drop(writer);
+        assert_that!(String::from_utf8_lossy(&child.stdout).into_owned())
+            .contains("lock test child locked shared: false");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_async_exclusive_guard_waits_for_sync_guard() {
+        let lock = Arc::new(EnvLock::new());
+
+        let shared = lock.read();
+        let writer = {
+            let lock = Arc::clone(&lock);
+            tokio::spawn(async move {
+                let _guard = lock.write_async().await;
+            })
+        };
+
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_that!(writer.is_finished()).is_false();
+
+        drop(shared);
+        writer.await.unwrap();
+        let _guard = lock.read_async().await;
+    }
+}
@@ -0,0 +1,212 @@
+//! In-memory environment for tests which must not touch the process environment.
+
+use std::{
+    collections::BTreeMap,
+    fmt,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+use crate::{redact, EnvProvider};
+
+/// An [`EnvProvider`] which keeps all variables in memory.
+///
+/// Tests using a `MockEnv` never mutate the process environment and can therefore
+/// run in parallel. Every read and write is recorded for later assertions.
+/// Clones share the same variables and records.
+///
+/// ```rust
+/// use temp_env_vars::{EnvProvider, MockEnv};
+///
+/// let env = MockEnv::new().with("HOST", "localhost").with("PORT", "8080");
+///
+/// assert_eq!(env.get("HOST"), Some("localhost".to_string()));
+/// env.set("PORT", "9090");
+///
+/// assert_eq!(env.reads(), vec!["HOST".to_string()]);
+/// assert_eq!(env.writes(), vec![temp_env_vars::EnvWrite::Set("PORT".into(), "9090".into())]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockEnv {
+    inner: Arc<Mutex<MockState>>,
+}
+
+#[derive(Default)]
+struct MockState {
+    vars: BTreeMap<String, String>,
+    reads: Vec<String>,
+    writes: Vec<EnvWrite>,
+}
+
+/// A write recorded by a [`MockEnv`].
+#[derive(Clone, PartialEq, Eq)]
+pub enum EnvWrite {
+    Set(String, String),
+    Remove(String),
+}
+
+impl fmt::Debug for MockState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockState")
+            .field("vars", &redact::Vars::new(&self.vars))
+            .field("reads", &self.reads)
+            .field("writes", &self.writes)
+            .finish()
+    }
+}
+
+/// Values of secrets like `*_TOKEN` are redacted.
+impl fmt::Debug for EnvWrite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnvWrite::Set(key, value) => f
+                .debug_tuple("Set")
+                .field(key)
+                .field(&redact::Raw(&redact::value(key.as_ref(), value)))
+                .finish(),
+            EnvWrite::Remove(key) => f.debug_tuple("Remove").field(key).finish(),
+        }
+    }
+}
+
+impl MockEnv {
+    /// Creates an empty environment.
+    pub fn new() -> MockEnv {
+        MockEnv::default()
+    }
+
+    /// Creates an environment seeded with a copy of the process environment.
+    /// Variables which are not valid unicode are skipped.
+    pub fn from_process() -> MockEnv {
+        MockEnv::new().with_all(
+            crate::sys::vars_os()
+                .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?))),
+        )
+    }
+
+    /// Seeds a variable. Seeding is not recorded as write.
+    pub fn with(self, key: impl Into<String>, value: impl Into<String>) -> MockEnv {
+        self.state().vars.insert(key.into(), value.into());
+        self
+    }
+
+    /// Seeds all given variables. Seeding is not recorded as write.
+    pub fn with_all<I, K, V>(self, vars: I) -> MockEnv
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.state()
+            .vars
+            .extend(vars.into_iter().map(|(k, v)| (k.into(), v.into())));
+        self
+    }
+
+    /// Returns the names of all variables read so far, in the order of reading.
+    pub fn reads(&self) -> Vec<String> {
+        self.state().reads.clone()
+    }
+
+    /// Returns all writes done so far, in the order they happened.
+    pub fn writes(&self) -> Vec<EnvWrite> {
+        self.state().writes.clone()
+    }
+
+    /// Returns whether the variable was read at least once.
+    pub fn was_read(&self, key: &str) -> bool {
+        self.state().reads.iter().any(|read| read == key)
+    }
+
+    fn state(&self) -> MutexGuard<'_, MockState> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl EnvProvider for MockEnv {
+    fn get(&self, key: &str) -> Option<String> {
+        let mut state = self.state();
+        state.reads.push(key.to_string());
+        state.vars.get(key).cloned()
+    }
+
+    fn set(&self, key: &str, value: &str) {
+        let mut state = self.state();
+        state.vars.insert(key.to_string(), value.to_string());
+        state
+            .writes
+            .push(EnvWrite::Set(key.to_string(), value.to_string()));
+    }
+
+    fn remove(&self, key: &str) {
+        let mut state = self.state();
+        state.vars.remove(key);
+        state.writes.push(EnvWrite::Remove(key.to_string()));
+    }
+
+    fn vars(&self) -> Vec<(String, String)> {
+        self.state()
+            .vars
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{
+        assert_that, BooleanAssertion, EqualityAssertion, OptionAssertion, StringAssertion,
+    };
+
+    use super::{EnvWrite, MockEnv};
+    use crate::EnvProvider;
+
+    #[test]
+    fn test_seeded_values_are_returned() {
+        let env = MockEnv::new().with("MOCK_A", "1");
+
+        assert_that!(env.get("MOCK_A")).has_value("1".to_string());
+        assert_that!(env.get("MOCK_B")).is_none();
+        assert_that!(env.writes()).is_equal_to(vec![]);
+    }
+
+    #[test]
+    fn test_writes_are_recorded_but_not_applied_to_the_process() {
+        let env = MockEnv::new().with("MOCK_A", "1");
+
+        env.set("MOCK_B", "2");
+        env.remove("MOCK_A");
+
+        assert_that!(env.vars()).is_equal_to(vec![("MOCK_B".to_string(), "2".to_string())]);
+        assert_that!(env.writes()).is_equal_to(vec![
+            EnvWrite::Set("MOCK_B".to_string(), "2".to_string()),
+            EnvWrite::Remove("MOCK_A".to_string()),
+        ]);
+        assert_that!(std::env::var_os("MOCK_B")).is_none();
+    }
+
+    #[test]
+    fn test_clones_share_their_state() {
+        let env = MockEnv::new();
+        let clone = env.clone();
+
+        clone.set("MOCK_A", "1");
+
+        assert_that!(env.get("MOCK_A")).has_value("1".to_string());
+        assert_that!(clone.was_read("MOCK_A")).is_true();
+    }
+
+    #[test]
+    fn test_secret_values_are_redacted_in_debug_output() {
+        let env = MockEnv::new()
+            .with("MOCK_TOKEN", "abc")
+            .with("MOCK_HOST", "localhost");
+        env.set("MOCK_PASSWORD", "def");
+
+        let output = format!("{env:?}");
+
+        assert_that!(output).contains("localhost");
+        assert_that!(output).does_not_contain("abc");
+        assert_that!(output).does_not_contain("def");
+    }
+}
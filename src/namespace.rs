@@ -0,0 +1,107 @@
+//! Unique prefixes for variables, so parallel tests against shared external
+//! systems keyed by the environment don't collide.
+
+use std::ffi::OsStr;
+
+use crate::{random, TempEnvScope};
+
+/// Characters of generated prefixes, valid in variable names on all platforms.
+const CHARSET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+/// Number of random characters of a generated prefix.
+const LEN: usize = 7;
+
+/// A unique prefix like `T8F3A0QZ_` for variables set through a
+/// [`TempEnvScope`].
+///
+/// Tests running in parallel against external systems keyed by the
+/// environment, e.g. a shared queue or bucket, don't collide this way.
+///
+/// ```rust
+/// let env_scope = temp_env_vars::TempEnvScope::new();
+/// let ns = env_scope.namespace();
+///
+/// ns.set("QUEUE", "orders");
+///
+/// assert_eq!(std::env::var(ns.name("QUEUE")).unwrap(), "orders");
+/// ```
+#[derive(Debug)]
+pub struct EnvNamespace<'a> {
+    scope: &'a TempEnvScope,
+    prefix: String,
+}
+
+impl TempEnvScope {
+    /// Returns a namespace with a newly generated unique prefix.
+    pub fn namespace(&self) -> EnvNamespace<'_> {
+        EnvNamespace {
+            scope: self,
+            prefix: format!("T{}_", random::string(LEN, CHARSET)),
+        }
+    }
+}
+
+impl EnvNamespace<'_> {
+    /// Returns the prefix, including the trailing `_`.
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    /// Returns the prefixed name of `key`, e.g. for passing it to the code under test.
+    pub fn name(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+
+    /// Sets the prefixed variable through the scope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name contains `=` or NUL, or if the value contains NUL.
+    pub fn set(&self, key: &str, value: impl AsRef<OsStr>) -> &Self {
+        self.scope.set(self.name(key), value);
+        self
+    }
+
+    /// Removes the prefixed variable through the scope.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the name contains `=` or NUL.
+    pub fn remove(&self, key: &str) -> &Self {
+        self.scope.remove(self.name(key));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, BooleanAssertion, EqualityAssertion, ResultAssertion};
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_variables_are_prefixed_and_restored() {
+        let name = {
+            let env_scope = TempEnvScope::new();
+            let ns = env_scope.namespace();
+            ns.set("QUEUE", "orders");
+
+            assert_that!(std::env::var(ns.name("QUEUE"))).has_ok("orders".to_string());
+            ns.name("QUEUE")
+        };
+
+        assert_that!(std::env::var(name)).is_err();
+    }
+
+    #[test]
+    #[serial]
+    fn test_prefixes_are_unique() {
+        let env_scope = TempEnvScope::journaled();
+        let (first, second) = (env_scope.namespace(), env_scope.namespace());
+
+        assert_that!(first.prefix() != second.prefix()).is_true();
+        assert_that!(first.prefix().len()).is_equal_to(9);
+    }
+}
@@ -0,0 +1,77 @@
+//! Checking that the scopes of a thread are dropped in the reverse order of
+//! their creation.
+//!
+//! If an outer scope is dropped before an inner one, the outer scope restores
+//! its baseline, and the inner scope later restores the values the outer scope
+//! set, which then stay in the environment.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+use crate::{registry::ScopeState, TempEnvScope};
+
+/// What happens when a scope is dropped while a scope created after it on the
+/// same thread is still alive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropOrderPolicy {
+    /// Panics with the locations of both scopes after restoring, unless the
+    /// thread is already panicking. This is
+    /// the default.
+    Panic,
+    /// Prints a warning to stderr.
+    Warn,
+    /// Does not check the drop order.
+    Ignore,
+}
+
+static POLICY: Mutex<DropOrderPolicy> = Mutex::new(DropOrderPolicy::Panic);
+
+/// Sets what happens when scopes are dropped out of order.
+pub fn set_drop_order_policy(policy: DropOrderPolicy) {
+    *POLICY.lock().unwrap_or_else(PoisonError::into_inner) = policy;
+}
+
+impl TempEnvScope {
+    /// Reports `inner`, a scope created after this one on the same thread and
+    /// still alive when this one is dropped.
+    pub(crate) fn check_drop_order(&self, inner: Option<Arc<ScopeState>>) {
+        let Some(inner) = inner else {
+            return;
+        };
+        let message = format!(
+            "temp_env_vars: scope {} created at {} was dropped before scope {} created at {} within it",
+            self.state, self.state.location, inner, inner.location
+        );
+        match *POLICY.lock().unwrap_or_else(PoisonError::into_inner) {
+            DropOrderPolicy::Panic if !std::thread::panicking() => panic!("{message}"),
+            DropOrderPolicy::Panic | DropOrderPolicy::Warn => eprintln!("{message}"),
+            DropOrderPolicy::Ignore => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_scopes_dropped_in_reverse_order_pass() {
+        let outer = TempEnvScope::new();
+        let inner = TempEnvScope::new();
+
+        drop(inner);
+        drop(outer);
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "was dropped before scope `inner`")]
+    fn test_outer_scope_dropped_first_panics() {
+        let outer = TempEnvScope::named("outer");
+        let _inner = TempEnvScope::named("inner");
+
+        drop(outer);
+    }
+}
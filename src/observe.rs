@@ -0,0 +1,276 @@
+//! Observers of the changes made through a scope.
+
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+};
+
+use crate::{redact, restore::Restorer, TempEnvScope};
+
+/// A change of a variable, passed to the observers registered with
+/// [`TempEnvScope::on_change`].
+#[derive(Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EnvEvent {
+    /// A variable was set through the scope.
+    Set {
+        key: OsString,
+        previous: Option<OsString>,
+        value: OsString,
+    },
+    /// A variable was removed through the scope.
+    Remove {
+        key: OsString,
+        previous: Option<OsString>,
+    },
+    /// A variable was changed by other means, detected when the scope restores
+    /// the environment. `value` is `None` if it was removed.
+    ExternalChange {
+        key: OsString,
+        value: Option<OsString>,
+    },
+}
+
+impl EnvEvent {
+    /// Returns the name of the changed variable.
+    pub fn key(&self) -> &OsStr {
+        match self {
+            EnvEvent::Set { key, .. }
+            | EnvEvent::Remove { key, .. }
+            | EnvEvent::ExternalChange { key, .. } => key,
+        }
+    }
+}
+
+/// Shows the event, with the values of secrets like `*_TOKEN` redacted.
+impl fmt::Debug for EnvEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let key = self.key();
+        let (name, fields): (&str, Vec<(&str, String)>) = match self {
+            EnvEvent::Set {
+                previous, value, ..
+            } => (
+                "Set",
+                vec![
+                    ("previous", redact::value(key, previous)),
+                    ("value", redact::value(key, value)),
+                ],
+            ),
+            EnvEvent::Remove { previous, .. } => {
+                ("Remove", vec![("previous", redact::value(key, previous))])
+            }
+            EnvEvent::ExternalChange { value, .. } => {
+                ("ExternalChange", vec![("value", redact::value(key, value))])
+            }
+        };
+        let mut debug = f.debug_struct(name);
+        debug.field("key", &key);
+        for (field, value) in &fields {
+            debug.field(field, &redact::Raw(value));
+        }
+        debug.finish()
+    }
+}
+
+pub(crate) type Observer = Box<dyn FnMut(&EnvEvent) + Send>;
+type RestoreErrorCallback = Box<dyn FnOnce(&RestoreError) + Send>;
+
+/// Variables which still differ from the baseline after a scope restored the
+/// environment, passed to the callbacks registered with
+/// [`TempEnvScope::on_restore_error`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RestoreError {
+    pub vars: Vec<String>,
+}
+
+impl fmt::Display for RestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "environment variables {:?} differ from the baseline after restoring",
+            self.vars
+        )
+    }
+}
+
+impl std::error::Error for RestoreError {}
+
+/// Callbacks run after a scope restored the environment.
+#[derive(Default)]
+pub(crate) struct RestoreHooks {
+    on_restore: Vec<Box<dyn FnOnce() + Send>>,
+    on_error: Vec<RestoreErrorCallback>,
+}
+
+impl TempEnvScope {
+    /// Calls `observer` for every variable set or removed through this scope,
+    /// and for every variable changed by other means, detected when the scope
+    /// restores the environment (except for [`Self::journaled`] scopes).
+    ///
+    /// Observers must not change the environment through this scope.
+    ///
+    /// ```rust
+    /// use temp_env_vars::{EnvEvent, TempEnvScope};
+    ///
+    /// let env_scope = TempEnvScope::new();
+    /// env_scope.on_change(|event: &EnvEvent| println!("{event:?}"));
+    /// env_scope.set("FOO", "BAR");
+    /// ```
+    pub fn on_change(&self, observer: impl FnMut(&EnvEvent) + Send + 'static) {
+        self.restorer
+            .observers
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(Box::new(observer));
+    }
+
+    /// Calls `callback` right after this scope restored the environment, e.g. to
+    /// reinitialize a logger reading it.
+    ///
+    /// The callback is not called if variables still differ from the baseline
+    /// afterwards, see [`Self::on_restore_error`].
+    pub fn on_restore(&self, callback: impl FnOnce() + Send + 'static) {
+        self.restorer
+            .restore_hooks()
+            .on_restore
+            .push(Box::new(callback));
+    }
+
+    /// Calls `callback` if variables still differ from the baseline right after
+    /// this scope restored the environment, e.g. because another thread changed
+    /// them in between.
+    pub fn on_restore_error(&self, callback: impl FnOnce(&RestoreError) + Send + 'static) {
+        self.restorer
+            .restore_hooks()
+            .on_error
+            .push(Box::new(callback));
+    }
+}
+
+impl Restorer {
+    pub(crate) fn notify(&self, event: EnvEvent) {
+        let mut observers = self.observers.lock().unwrap_or_else(|err| err.into_inner());
+        for observer in observers.iter_mut() {
+            observer(&event);
+        }
+    }
+
+    /// Runs the callbacks registered for after restoring.
+    pub(crate) fn run_restore_hooks(&self) {
+        let hooks = std::mem::take(&mut *self.restore_hooks());
+        if hooks.on_restore.is_empty() && hooks.on_error.is_empty() {
+            return;
+        }
+        let vars = self.unrestored_vars();
+        if vars.is_empty() {
+            hooks.on_restore.into_iter().for_each(|callback| callback());
+        } else {
+            let err = RestoreError { vars };
+            hooks
+                .on_error
+                .into_iter()
+                .for_each(|callback| callback(&err));
+        }
+    }
+
+    fn restore_hooks(&self) -> std::sync::MutexGuard<'_, RestoreHooks> {
+        self.restore_hooks
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub(crate) fn has_observers(&self) -> bool {
+        !self
+            .observers
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use assertor::{assert_that, EqualityAssertion};
+    use serial_test::serial;
+
+    use super::{EnvEvent, RestoreError};
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_changes_are_observed() {
+        std::env::set_var("OBSERVED_REMOVED", "1");
+        let events = Arc::new(Mutex::new(Vec::new()));
+
+        {
+            let env_scope = TempEnvScope::new();
+            let recorded = Arc::clone(&events);
+            env_scope.on_change(move |event| recorded.lock().unwrap().push(event.clone()));
+
+            env_scope.set("OBSERVED_SET", "1");
+            env_scope.remove("OBSERVED_REMOVED");
+            std::env::set_var("OBSERVED_EXTERNAL", "1");
+        }
+
+        assert_that!(events.lock().unwrap().clone()).is_equal_to(vec![
+            EnvEvent::Set {
+                key: "OBSERVED_SET".into(),
+                previous: None,
+                value: "1".into(),
+            },
+            EnvEvent::Remove {
+                key: "OBSERVED_REMOVED".into(),
+                previous: Some("1".into()),
+            },
+            EnvEvent::ExternalChange {
+                key: "OBSERVED_EXTERNAL".into(),
+                value: Some("1".into()),
+            },
+        ]);
+        std::env::remove_var("OBSERVED_REMOVED");
+    }
+
+    #[test]
+    #[serial]
+    fn test_restore_callbacks_are_called() {
+        let called = Arc::new(Mutex::new(Vec::new()));
+
+        {
+            let env_scope = TempEnvScope::new();
+            env_scope.set("RESTORE_HOOK_FOO", "1");
+            let (restored, failed) = (Arc::clone(&called), Arc::clone(&called));
+            env_scope.on_restore(move || {
+                let value = std::env::var("RESTORE_HOOK_FOO").ok();
+                restored.lock().unwrap().push(format!("restored {value:?}"));
+            });
+            env_scope.on_restore_error(move |err| failed.lock().unwrap().push(err.to_string()));
+        }
+
+        assert_that!(called.lock().unwrap().clone()).is_equal_to(vec!["restored None".to_string()]);
+    }
+
+    #[test]
+    fn test_restore_errors_name_the_vars() {
+        let err = RestoreError {
+            vars: vec!["FOO".to_string()],
+        };
+
+        assert_that!(err.to_string()).is_equal_to(
+            "environment variables [\"FOO\"] differ from the baseline after restoring".to_string(),
+        );
+    }
+
+    #[test]
+    fn test_secrets_are_redacted_in_debug_output() {
+        let event = EnvEvent::Set {
+            key: "API_TOKEN".into(),
+            previous: None,
+            value: "abc".into(),
+        };
+
+        assert_that!(format!("{event:?}"))
+            .is_equal_to(r#"Set { key: "API_TOKEN", previous: ***, value: *** }"#.to_string());
+    }
+}
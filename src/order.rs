@@ -0,0 +1,129 @@
+//! Checking that code reading the environment does not depend on the order in
+//! which variables were set, or on earlier reads.
+
+use std::{ffi::OsStr, fmt};
+
+use crate::{env_lock, TempEnvScope};
+
+/// Describes a run of [`check_order_independent`] with a different result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderDependence {
+    /// How the variables were applied in the differing run.
+    pub run: String,
+    /// The result when applying the variables in the given order, in debug format.
+    pub expected: String,
+    /// The result of the differing run, in debug format.
+    pub actual: String,
+}
+
+impl fmt::Display for OrderDependence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "result {} when applying the variables {} differs from {} when applying them in the given order",
+            self.actual, self.run, self.expected
+        )
+    }
+}
+
+impl std::error::Error for OrderDependence {}
+
+/// Runs `f` several times with all variables set, and checks that it always
+/// returns the same result.
+///
+/// The variables are applied in the given order, in reverse order and
+/// incrementally, with `f` called before each variable. This flushes out code
+/// depending on the order in which the variables were set, or on values read
+/// before all of them were set. Each run happens within its own scope, while
+/// holding the lock. Returns the result of the first run.
+///
+/// ```rust
+/// let result = temp_env_vars::check_order_independent([("HOST", "example.com"), ("PORT", "80")], || {
+///     format!("{}:{}", std::env::var("HOST").unwrap_or_default(), std::env::var("PORT").unwrap_or_default())
+/// });
+///
+/// assert_eq!(result.unwrap(), "example.com:80");
+/// ```
+///
+/// # Panics
+///
+/// Panics if any of the variables is invalid.
+pub fn check_order_independent<I, K, V, R>(
+    vars: I,
+    mut f: impl FnMut() -> R,
+) -> Result<R, OrderDependence>
+where
+    I: IntoIterator<Item = (K, V)>,
+    K: AsRef<OsStr>,
+    V: AsRef<OsStr>,
+    R: PartialEq + fmt::Debug,
+{
+    let vars: Vec<(K, V)> = vars.into_iter().collect();
+    let _lock = env_lock();
+
+    let run = |order: &[&(K, V)], read_between: bool, f: &mut dyn FnMut() -> R| {
+        let env_scope = TempEnvScope::new();
+        for (key, value) in order {
+            if read_between {
+                f();
+            }
+            env_scope.set(key, value);
+        }
+        f()
+    };
+
+    let given: Vec<&(K, V)> = vars.iter().collect();
+    let expected = run(&given, false, &mut f);
+    let reversed: Vec<&(K, V)> = vars.iter().rev().collect();
+    let runs = [
+        ("in reverse order", &reversed, false),
+        ("incrementally", &given, true),
+        ("incrementally in reverse order", &reversed, true),
+    ];
+    for (name, order, read_between) in runs {
+        let actual = run(order, read_between, &mut f);
+        if actual != expected {
+            return Err(OrderDependence {
+                run: name.to_string(),
+                expected: format!("{expected:?}"),
+                actual: format!("{actual:?}"),
+            });
+        }
+    }
+    Ok(expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, ResultAssertion};
+    use serial_test::serial;
+
+    use super::check_order_independent;
+
+    #[test]
+    #[serial]
+    fn test_same_results_are_accepted() {
+        let result = check_order_independent([("FOO", "1"), ("BAR", "2")], || {
+            (std::env::var("FOO").ok(), std::env::var("BAR").ok())
+        });
+
+        assert_that!(result).has_ok((Some("1".to_string()), Some("2".to_string())));
+    }
+
+    // New variables are appended to the environment on unix, so the first one
+    // found depends on the order they were set in.
+    #[cfg(unix)]
+    #[test]
+    #[serial]
+    fn test_order_dependent_results_are_reported() {
+        let result = check_order_independent([("FOO_A", "1"), ("FOO_B", "2")], || {
+            std::env::vars().find(|(key, _)| key.starts_with("FOO_"))
+        });
+
+        assert_that!(result).has_err(super::OrderDependence {
+            run: "in reverse order".to_string(),
+            expected: r#"Some(("FOO_A", "1"))"#.to_string(),
+            actual: r#"Some(("FOO_B", "2"))"#.to_string(),
+        });
+    }
+}
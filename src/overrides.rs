@@ -0,0 +1,149 @@
+//! Introspection of what a scope currently overrides.
+
+use crate::{
+    snapshot::{self, Change},
+    sys, Baseline, TempEnvScope,
+};
+
+/// How a variable differs from its value when the scope was created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Override {
+    /// The variable did not exist and is set now.
+    Set,
+    /// The variable existed and is removed now.
+    Removed,
+    /// The variable existed and has another value now.
+    Changed,
+}
+
+impl TempEnvScope {
+    /// Returns whether any variable differs from its value when the scope was
+    /// created, i.e. whether dropping the scope restores anything.
+    pub fn is_modified(&self) -> bool {
+        !self.override_list().is_empty()
+    }
+
+    /// Returns the variables which differ from their value when the scope was
+    /// created, sorted by name.
+    ///
+    /// Scopes created with [`Self::journaled`] only know about variables changed
+    /// through the scope.
+    ///
+    /// ```rust
+    /// use temp_env_vars::Override;
+    ///
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// env_scope.set("OVERRIDDEN_VAR", "1");
+    ///
+    /// let overrides: Vec<_> = env_scope.overrides().collect();
+    /// assert_eq!(overrides, [("OVERRIDDEN_VAR".to_string(), Override::Set)]);
+    /// ```
+    pub fn overrides(&self) -> impl Iterator<Item = (String, Override)> {
+        self.override_list().into_iter()
+    }
+
+    /// Returns the variables set or changed by this scope with their current
+    /// value, skipping values which are not valid unicode.
+    #[cfg_attr(not(any(feature = "config", feature = "figment")), allow(dead_code))]
+    pub(crate) fn overridden_values(&self) -> Vec<(String, String)> {
+        self.override_list()
+            .into_iter()
+            .filter(|(_, kind)| *kind != Override::Removed)
+            .filter_map(|(key, _)| {
+                let value = sys::var_os(&key)?.into_string().ok()?;
+                Some((key, value))
+            })
+            .collect()
+    }
+
+    fn override_list(&self) -> Vec<(String, Override)> {
+        let mut overrides: Vec<(String, Override)> = match &self.restorer.baseline {
+            Baseline::Snapshot(original_vars) => {
+                let now = snapshot::capture();
+                snapshot::changes(original_vars, &now)
+                    .into_iter()
+                    .map(|change| {
+                        let key = change.key().as_os_str();
+                        let kind = match change {
+                            Change::Remove(_) => Override::Set,
+                            Change::Set(..) => classify(sys::var_os(key).is_some()),
+                        };
+                        (key.to_string_lossy().into_owned(), kind)
+                    })
+                    .collect()
+            }
+            Baseline::Journal => {
+                let journal = self.restorer.journal();
+                journal
+                    .iter()
+                    .filter_map(|(key, original)| {
+                        let now = sys::var_os(key.as_os_str());
+                        if *original == now {
+                            return None;
+                        }
+                        let kind = match original {
+                            None => Override::Set,
+                            Some(_) => classify(now.is_some()),
+                        };
+                        Some((key.as_os_str().to_string_lossy().into_owned(), kind))
+                    })
+                    .collect()
+            }
+        };
+        overrides.sort_by(|(a, _), (b, _)| a.cmp(b));
+        overrides
+    }
+}
+
+/// Classifies a variable which existed when the scope was created.
+fn classify(exists: bool) -> Override {
+    if exists {
+        Override::Changed
+    } else {
+        Override::Removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, BooleanAssertion, EqualityAssertion};
+    use serial_test::serial;
+
+    use super::Override;
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_overrides_are_classified() {
+        std::env::set_var("OVERRIDE_CHANGED", "1");
+        std::env::set_var("OVERRIDE_REMOVED", "1");
+        std::env::set_var("OVERRIDE_RESET", "1");
+
+        // Created one after another, so each is dropped before the next one exists
+        for new_scope in [
+            TempEnvScope::new as fn() -> TempEnvScope,
+            TempEnvScope::journaled,
+        ] {
+            let env_scope = new_scope();
+            assert_that!(env_scope.is_modified()).is_false();
+
+            env_scope.set("OVERRIDE_CHANGED", "2");
+            env_scope.remove("OVERRIDE_REMOVED");
+            env_scope.set("OVERRIDE_RESET", "2");
+            env_scope.set("OVERRIDE_RESET", "1");
+            env_scope.set("OVERRIDE_SET", "1");
+
+            let overrides: Vec<_> = env_scope.overrides().collect();
+            assert_that!(env_scope.is_modified()).is_true();
+            assert_that!(overrides).is_equal_to(vec![
+                ("OVERRIDE_CHANGED".to_string(), Override::Changed),
+                ("OVERRIDE_REMOVED".to_string(), Override::Removed),
+                ("OVERRIDE_SET".to_string(), Override::Set),
+            ]);
+        }
+
+        std::env::remove_var("OVERRIDE_CHANGED");
+        std::env::remove_var("OVERRIDE_REMOVED");
+        std::env::remove_var("OVERRIDE_RESET");
+    }
+}
@@ -0,0 +1,139 @@
+//! Editing list variables like `PATH` entry by entry.
+
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use crate::{access, TempEnvScope};
+
+/// A list variable like `PATH`, `PYTHONPATH` or `LD_LIBRARY_PATH`, edited
+/// through a [`TempEnvScope`], so the original list is restored when the
+/// scope is dropped.
+///
+/// Entries are separated by the separator of the platform, `:` on Unix and
+/// `;` on Windows.
+///
+/// ```rust
+/// let env_scope = temp_env_vars::TempEnvScope::new();
+///
+/// env_scope.path_var("PATH").prepend("/opt/tool/bin").remove("/usr/games");
+/// ```
+#[derive(Debug)]
+pub struct PathVar<'a> {
+    scope: &'a TempEnvScope,
+    key: &'a OsStr,
+}
+
+impl TempEnvScope {
+    /// Returns a handle for editing the list variable `key` entry by entry.
+    pub fn path_var<'a>(&'a self, key: &'a (impl AsRef<OsStr> + ?Sized)) -> PathVar<'a> {
+        PathVar {
+            scope: self,
+            key: key.as_ref(),
+        }
+    }
+}
+
+impl PathVar<'_> {
+    /// Returns the current entries. A missing variable has none.
+    pub fn entries(&self) -> Vec<PathBuf> {
+        access::var_os(self.key)
+            .map(|value| std::env::split_paths(&value).collect())
+            .unwrap_or_default()
+    }
+
+    /// Adds an entry in front of all others.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry contains the separator.
+    pub fn prepend(&self, entry: impl AsRef<Path>) -> &Self {
+        let mut entries = self.entries();
+        entries.insert(0, entry.as_ref().to_path_buf());
+        self.set(entries)
+    }
+
+    /// Adds an entry after all others.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the entry contains the separator.
+    pub fn append(&self, entry: impl AsRef<Path>) -> &Self {
+        let mut entries = self.entries();
+        entries.push(entry.as_ref().to_path_buf());
+        self.set(entries)
+    }
+
+    /// Removes all occurrences of an entry.
+    pub fn remove(&self, entry: impl AsRef<Path>) -> &Self {
+        let mut entries = self.entries();
+        entries.retain(|existing| existing != entry.as_ref());
+        self.set(entries)
+    }
+
+    fn set(&self, entries: Vec<PathBuf>) -> &Self {
+        let value = std::env::join_paths(entries)
+            .unwrap_or_else(|err| panic!("temp_env_vars: cannot set {:?}: {err}", self.key));
+        self.scope.set(self.key, value);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use assertor::{assert_that, EqualityAssertion, ResultAssertion};
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_entries_are_edited_and_restored() {
+        let original = std::env::join_paths(["/a", "/b"]).unwrap();
+        std::env::set_var("PATH_VAR_TEST", &original);
+
+        {
+            let env_scope = TempEnvScope::new();
+            env_scope
+                .path_var("PATH_VAR_TEST")
+                .prepend("/first")
+                .append("/last")
+                .remove("/a");
+
+            assert_that!(env_scope.path_var("PATH_VAR_TEST").entries()).is_equal_to(vec![
+                PathBuf::from("/first"),
+                PathBuf::from("/b"),
+                PathBuf::from("/last"),
+            ]);
+        }
+
+        assert_that!(std::env::var_os("PATH_VAR_TEST")).is_equal_to(Some(original));
+        std::env::remove_var("PATH_VAR_TEST");
+    }
+
+    #[test]
+    #[serial]
+    fn test_missing_var_is_created() {
+        std::env::remove_var("PATH_VAR_TEST");
+        let env_scope = TempEnvScope::new();
+
+        env_scope.path_var("PATH_VAR_TEST").append("/only");
+
+        assert_that!(std::env::var("PATH_VAR_TEST")).has_ok("/only".to_string());
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "cannot set")]
+    fn test_entries_containing_the_separator_are_rejected() {
+        let separator = if cfg!(windows) { ";" } else { ":" };
+        let env_scope = TempEnvScope::new();
+
+        env_scope
+            .path_var("PATH_VAR_TEST")
+            .append(format!("/a{separator}/b"));
+    }
+}
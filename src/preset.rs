@@ -0,0 +1,138 @@
+//! Presets changing groups of well-known variables at once.
+
+use std::ffi::OsStr;
+
+use crate::TempEnvScope;
+
+/// Names of variables set by CI systems.
+const CI_NAMES: &[&str] = &[
+    "CI",
+    "CONTINUOUS_INTEGRATION",
+    "BUILD_ID",
+    "BUILD_NUMBER",
+    "RUN_ID",
+    "JENKINS_URL",
+    "TF_BUILD",
+    "TEAMCITY_VERSION",
+    "CIRCLECI",
+    "TRAVIS",
+    "APPVEYOR",
+    "BUILDKITE",
+    "DRONE",
+    "CODEBUILD_BUILD_ID",
+];
+
+/// Prefixes of variables set by CI systems.
+const CI_PREFIXES: &[&str] = &[
+    "GITHUB_",
+    "GITLAB_",
+    "CI_",
+    "RUNNER_",
+    "BUILDKITE_",
+    "CIRCLE_",
+    "TRAVIS_",
+    "APPVEYOR_",
+    "DRONE_",
+    "BITBUCKET_",
+    "AZURE_PIPELINES_",
+    "SYSTEM_TEAMFOUNDATION",
+];
+
+impl TempEnvScope {
+    /// Removes the variables of well-known CI systems, like `CI`, `GITHUB_*`,
+    /// `GITLAB_*` and `RUNNER_*`, so tests checking the behavior outside of CI
+    /// pass wherever they run.
+    pub fn sanitize_ci(&self) {
+        self.remove_matching(|key| {
+            CI_NAMES.contains(&key) || CI_PREFIXES.iter().any(|prefix| key.starts_with(prefix))
+        });
+    }
+
+    /// Removes the variables cargo sets for build scripts and tests, like
+    /// `CARGO_MANIFEST_DIR` or `CARGO_PKG_VERSION`.
+    pub fn sanitize_cargo(&self) {
+        self.remove_matching(|key| key == "CARGO" || key.starts_with("CARGO_"));
+    }
+
+    /// Sets the timezone through `TZ`, e.g. `UTC` or `Europe/Vienna`.
+    ///
+    /// Libraries reading `TZ` on every conversion see the change immediately.
+    /// The C library caches the timezone though, so C code only sees it after
+    /// calling `tzset`. On Windows, the C runtime only understands values like
+    /// `PST8PDT`.
+    pub fn set_timezone(&self, tz: impl AsRef<OsStr>) {
+        self.set("TZ", tz);
+    }
+
+    /// Sets the locale for all categories, e.g. `C` or `de_AT.UTF-8`.
+    ///
+    /// Sets `LC_ALL`, which takes precedence over the `LC_*` variables of the
+    /// single categories, and `LANG`. `LANGUAGE` is removed, as it would
+    /// otherwise still take precedence for the language of messages.
+    pub fn set_locale(&self, locale: impl AsRef<OsStr>) {
+        let locale = locale.as_ref();
+        self.set("LC_ALL", locale);
+        self.set("LANG", locale);
+        self.remove("LANGUAGE");
+    }
+
+    pub(crate) fn remove_matching(&self, matches: impl Fn(&str) -> bool) {
+        let keys: Vec<_> = crate::sys::vars_os()
+            .map(|(key, _)| key)
+            .filter(|key| key.to_str().is_some_and(&matches))
+            .collect();
+        for key in keys {
+            self.remove(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, ResultAssertion};
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_ci_vars_are_removed() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("CI", "true");
+        env_scope.set("GITHUB_ACTIONS", "true");
+        env_scope.set("PRESET_OTHER", "1");
+
+        env_scope.sanitize_ci();
+
+        assert_that!(std::env::var("CI")).is_err();
+        assert_that!(std::env::var("GITHUB_ACTIONS")).is_err();
+        assert_that!(std::env::var("PRESET_OTHER")).has_ok("1".to_string());
+    }
+
+    #[test]
+    #[serial]
+    fn test_timezone_and_locale_are_set() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("LANGUAGE", "de");
+
+        env_scope.set_timezone("UTC");
+        env_scope.set_locale("C");
+
+        assert_that!(std::env::var("TZ")).has_ok("UTC".to_string());
+        assert_that!(std::env::var("LC_ALL")).has_ok("C".to_string());
+        assert_that!(std::env::var("LANG")).has_ok("C".to_string());
+        assert_that!(std::env::var("LANGUAGE")).is_err();
+    }
+
+    #[test]
+    #[serial]
+    fn test_cargo_vars_are_removed() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("CARGO_PKG_NAME", "foo");
+
+        env_scope.sanitize_cargo();
+
+        assert_that!(std::env::var("CARGO_PKG_NAME")).is_err();
+        assert_that!(std::env::var("CARGO")).is_err();
+    }
+}
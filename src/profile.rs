@@ -0,0 +1,136 @@
+//! Named sets of variables, defined once and applied by name across a test suite.
+
+use std::{
+    collections::BTreeMap,
+    ffi::{OsStr, OsString},
+    fmt,
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{error, TempEnvScope};
+
+static PROFILES: Profiles = Profiles {
+    profiles: Mutex::new(BTreeMap::new()),
+};
+
+/// Registry of named variable sets, see [`profiles`].
+pub struct Profiles {
+    profiles: Mutex<BTreeMap<String, Vec<(OsString, OsString)>>>,
+}
+
+/// Returns the registry of profiles of the process.
+///
+/// Profiles are applied by [`TempEnvScope::apply_profile`] and by
+/// `#[temp_env_vars(profile = "...")]`, so they must be registered before,
+/// e.g. from a helper called at the start of the tests.
+///
+/// ```rust
+/// use temp_env_vars::{profiles, TempEnvScope};
+///
+/// profiles().register("integration", [("DB_HOST", "localhost"), ("DB_PORT", "5432")]);
+///
+/// let env_scope = TempEnvScope::new();
+/// env_scope.apply_profile("integration");
+/// assert_eq!(std::env::var("DB_PORT").unwrap(), "5432");
+/// ```
+pub fn profiles() -> &'static Profiles {
+    &PROFILES
+}
+
+impl Profiles {
+    /// Registers the variables under the given name, replacing an earlier
+    /// profile with the same name.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the variables is invalid.
+    pub fn register<I, K, V>(&self, name: impl Into<String>, vars: I) -> &Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: AsRef<OsStr>,
+        V: AsRef<OsStr>,
+    {
+        let vars = vars
+            .into_iter()
+            .map(|(key, value)| {
+                let (key, value) = (key.as_ref(), value.as_ref());
+                if let Err(err) = error::validate(key, value) {
+                    panic!("temp_env_vars: {err}");
+                }
+                (key.to_os_string(), value.to_os_string())
+            })
+            .collect();
+        self.lock().insert(name.into(), vars);
+        self
+    }
+
+    /// Returns the variables of the profile, if it is registered.
+    pub fn vars(&self, name: &str) -> Option<Vec<(OsString, OsString)>> {
+        self.lock().get(name).cloned()
+    }
+
+    /// Returns the names of all registered profiles, sorted.
+    pub fn names(&self) -> Vec<String> {
+        self.lock().keys().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, Vec<(OsString, OsString)>>> {
+        self.profiles.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for Profiles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Profiles")
+            .field("names", &self.names())
+            .finish()
+    }
+}
+
+impl TempEnvScope {
+    /// Sets all variables of a registered profile, see [`profiles`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if no profile with that name is registered.
+    pub fn apply_profile(&self, name: &str) {
+        let Some(vars) = profiles().vars(name) else {
+            panic!("temp_env_vars: profile {name:?} is not registered");
+        };
+        for (key, value) in vars {
+            self.set(key, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, ResultAssertion};
+    use serial_test::serial;
+
+    use super::profiles;
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_profile_is_applied_and_restored() {
+        profiles().register("unit", [("PROFILE_FOO", "1"), ("PROFILE_BAR", "2")]);
+
+        {
+            let env_scope = TempEnvScope::new();
+            env_scope.apply_profile("unit");
+
+            assert_that!(std::env::var("PROFILE_FOO")).has_ok("1".to_string());
+            assert_that!(std::env::var("PROFILE_BAR")).has_ok("2".to_string());
+        }
+
+        assert_that!(std::env::var("PROFILE_FOO")).is_err();
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "profile \"unknown\" is not registered")]
+    fn test_unknown_profile_panics() {
+        TempEnvScope::new().apply_profile("unknown");
+    }
+}
@@ -0,0 +1,116 @@
+//! Property-based testing against the real environment with `proptest`, with
+//! the `proptest` feature.
+//!
+//! ```rust
+//! use temp_env_vars::prop::proptest_env;
+//!
+//! proptest_env(|_env_scope| {
+//!     for (key, value) in std::env::vars().filter(|(key, _)| key.starts_with("PROPTEST_")) {
+//!         // Feed the config parser with the generated variables.
+//!         # let _ = (key, value);
+//!     }
+//!     Ok(())
+//! });
+//! ```
+
+use std::collections::BTreeMap;
+use std::ops::Range;
+
+use proptest::collection::btree_map;
+use proptest::prelude::{any, prop_oneof, Just, Strategy};
+use proptest::test_runner::{TestCaseError, TestRunner};
+
+use crate::{env_lock, TempEnvScope};
+
+/// Generates valid variable names, starting with `prefix`.
+pub fn env_key(prefix: &str) -> impl Strategy<Value = String> {
+    let prefix = prefix.to_string();
+    "[A-Z_][A-Z0-9_]{0,15}".prop_map(move |name| format!("{prefix}{name}"))
+}
+
+/// Generates values including tricky ones: empty, whitespace only, unicode and
+/// very long values.
+pub fn env_value() -> impl Strategy<Value = String> {
+    prop_oneof![
+        Just(String::new()),
+        "[ \t\n]{1,4}",
+        "[a-zA-Z0-9_./:-]{1,32}",
+        "\\PC{1,32}",
+        (4096..65536usize, any::<char>())
+            .prop_filter("NUL is not allowed", |(_, c)| *c != '\0')
+            .prop_map(|(len, c)| c.to_string().repeat(len)),
+    ]
+}
+
+/// Generates maps of variables with names starting with `prefix`.
+pub fn env_map(
+    prefix: &str,
+    size: Range<usize>,
+) -> impl Strategy<Value = BTreeMap<String, String>> {
+    btree_map(env_key(prefix), env_value(), size)
+}
+
+/// Runs `test` for maps of variables generated by `env_map("PROPTEST_", 0..8)`.
+/// See [`proptest_env_with`].
+pub fn proptest_env(test: impl Fn(&TempEnvScope) -> Result<(), TestCaseError>) {
+    proptest_env_with(env_map("PROPTEST_", 0..8), test);
+}
+
+/// Runs `test` for the variables generated by `strategy`, each case within a
+/// scope holding the lock. The environment is restored after every case.
+///
+/// # Panics
+///
+/// Panics with the minimal failing case if `test` fails for any case.
+pub fn proptest_env_with<S>(strategy: S, test: impl Fn(&TempEnvScope) -> Result<(), TestCaseError>)
+where
+    S: Strategy<Value = BTreeMap<String, String>>,
+{
+    let result = TestRunner::default().run(&strategy, |vars| {
+        let _lock = env_lock();
+        let env_scope = TempEnvScope::new();
+        env_scope
+            .try_set_all(&vars)
+            .map_err(|err| TestCaseError::fail(err.to_string()))?;
+        test(&env_scope)
+    });
+    if let Err(err) = result {
+        panic!("temp_env_vars: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, BooleanAssertion};
+    use proptest::prelude::prop_assert_eq;
+    use serial_test::serial;
+
+    use super::{env_map, proptest_env, proptest_env_with};
+
+    fn proptest_vars() -> Vec<(String, String)> {
+        std::env::vars()
+            .filter(|(key, _)| key.starts_with("PROPTEST_"))
+            .collect()
+    }
+
+    #[test]
+    #[serial]
+    fn test_generated_vars_are_applied_and_restored() {
+        proptest_env_with(env_map("PROPTEST_", 1..4), |_| {
+            prop_assert_eq!(proptest_vars().is_empty(), false);
+            Ok(())
+        });
+
+        assert_that!(proptest_vars().is_empty()).is_true();
+    }
+
+    #[test]
+    #[serial]
+    fn test_failing_case_panics() {
+        let result = std::panic::catch_unwind(|| {
+            proptest_env(|_| Err(proptest::test_runner::TestCaseError::fail("broken")));
+        });
+
+        assert_that!(result.is_err()).is_true();
+    }
+}
@@ -0,0 +1,71 @@
+//! Abstraction over the environment for code which wants to be testable
+//! without touching the process environment.
+
+use crate::access;
+
+/// Source and sink of environment variables.
+///
+/// Code which receives its environment through this trait can be tested with
+/// [`crate::MockEnv`], while production code uses [`StdEnv`].
+pub trait EnvProvider {
+    /// Returns the value of a variable, if it is set and valid unicode.
+    fn get(&self, key: &str) -> Option<String>;
+
+    /// Sets a variable.
+    fn set(&self, key: &str, value: &str);
+
+    /// Removes a variable.
+    fn remove(&self, key: &str);
+
+    /// Returns all variables, sorted by their name.
+    fn vars(&self) -> Vec<(String, String)>;
+}
+
+impl<T: EnvProvider + ?Sized> EnvProvider for &T {
+    fn get(&self, key: &str) -> Option<String> {
+        (**self).get(key)
+    }
+
+    fn set(&self, key: &str, value: &str) {
+        (**self).set(key, value)
+    }
+
+    fn remove(&self, key: &str) {
+        (**self).remove(key)
+    }
+
+    fn vars(&self) -> Vec<(String, String)> {
+        (**self).vars()
+    }
+}
+
+/// The environment of the current process.
+///
+/// Reads go through [`crate::var`], so they are subject to the strict mode and
+/// read tracking of the active scopes. Writes go through [`crate::set_var`] and
+/// [`crate::remove_var`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdEnv;
+
+impl EnvProvider for StdEnv {
+    fn get(&self, key: &str) -> Option<String> {
+        access::var(key).ok()
+    }
+
+    fn set(&self, key: &str, value: &str) {
+        access::set_var(key, value);
+    }
+
+    fn remove(&self, key: &str) {
+        access::remove_var(key);
+    }
+
+    fn vars(&self) -> Vec<(String, String)> {
+        let mut vars: Vec<(String, String)> = access::effective_vars()
+            .into_iter()
+            .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?)))
+            .collect();
+        vars.sort();
+        vars
+    }
+}
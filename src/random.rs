@@ -0,0 +1,67 @@
+//! Random values for variables, without pulling in a random number generator.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+/// Characters used by default: letters and digits.
+pub(crate) const ALPHANUMERIC: &str =
+    "ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+
+/// Length of values generated by default.
+pub(crate) const DEFAULT_LEN: usize = 32;
+
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Generates a random string of `len` characters taken from `charset`.
+///
+/// The randomness comes from the randomly seeded hasher of the standard library.
+/// It is fine for unique test values, but not for cryptographic purposes.
+///
+/// # Panics
+///
+/// Panics if `charset` is empty.
+pub(crate) fn string(len: usize, charset: &str) -> String {
+    let charset: Vec<char> = charset.chars().collect();
+    assert!(
+        !charset.is_empty(),
+        "temp_env_vars: cannot generate a random value from an empty charset"
+    );
+
+    let state = RandomState::new();
+    (0..len)
+        .map(|_| {
+            let mut hasher = state.build_hasher();
+            hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+            charset[(hasher.finish() % charset.len() as u64) as usize]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, BooleanAssertion, EqualityAssertion};
+
+    use super::{string, ALPHANUMERIC};
+
+    #[test]
+    fn test_values_have_the_given_length_and_charset() {
+        let value = string(64, "ab");
+
+        assert_that!(value.len()).is_equal_to(64);
+        assert_that!(value.chars().all(|c| c == 'a' || c == 'b')).is_true();
+    }
+
+    #[test]
+    fn test_values_are_unique() {
+        assert_that!(string(32, ALPHANUMERIC) != string(32, ALPHANUMERIC)).is_true();
+    }
+
+    #[test]
+    #[should_panic(expected = "empty charset")]
+    fn test_empty_charset_is_rejected() {
+        string(8, "");
+    }
+}
@@ -0,0 +1,147 @@
+//! Redaction of secret values in diagnostic output.
+//!
+//! Values of variables whose name looks like a secret, e.g. `GITHUB_TOKEN` or
+//! `DB_PASSWORD`, or which were marked with [`mark_secret`], are replaced by
+//! `***` in every `Debug` output of this crate, so they don't end up in test logs.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    ffi::OsStr,
+    fmt::{self, Debug},
+    sync::{LazyLock, Mutex, PoisonError},
+};
+
+use crate::snapshot::EnvKey;
+
+pub(crate) const REDACTED: &str = "***";
+
+static MARKED: LazyLock<Mutex<HashSet<EnvKey>>> = LazyLock::new(Mutex::default);
+
+/// Name parts which mark a variable as secret, either as the whole name or
+/// as its last `_`-separated part(s).
+const SECRET_SUFFIXES: &[&str] = &[
+    "TOKEN",
+    "SECRET",
+    "PASSWORD",
+    "PASSWD",
+    "API_KEY",
+    "PRIVATE_KEY",
+    "CREDENTIALS",
+];
+
+/// Marks a variable as secret for the rest of the process, so its value is
+/// shown as `***` in all output of this crate, whatever its name is.
+///
+/// ```rust
+/// temp_env_vars::mark_secret("DATABASE_URL");
+/// ```
+pub fn mark_secret(key: impl AsRef<OsStr>) {
+    MARKED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(EnvKey::new(key.as_ref()));
+}
+
+/// Returns whether the value of the variable must not be shown.
+pub(crate) fn is_secret(key: &OsStr) -> bool {
+    if MARKED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .contains(&EnvKey::new(key))
+    {
+        return true;
+    }
+    let key = key.to_string_lossy().to_uppercase();
+    SECRET_SUFFIXES.iter().any(|suffix| {
+        key.strip_suffix(suffix)
+            .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('_'))
+    })
+}
+
+/// Formats a value for `Debug` output, redacted if the variable is secret.
+pub(crate) fn value(key: &OsStr, value: &impl Debug) -> String {
+    if is_secret(key) {
+        REDACTED.to_string()
+    } else {
+        format!("{value:?}")
+    }
+}
+
+/// Variables formatted as map, sorted by name and with secret values redacted.
+pub(crate) struct Vars(BTreeMap<String, String>);
+
+impl Vars {
+    pub(crate) fn new<'a, K, V>(vars: impl IntoIterator<Item = (&'a K, &'a V)>) -> Vars
+    where
+        K: AsRef<OsStr> + ?Sized + 'a,
+        V: Debug + ?Sized + 'a,
+    {
+        Vars(
+            vars.into_iter()
+                .map(|(key, v)| {
+                    let key = key.as_ref();
+                    (key.to_string_lossy().into_owned(), value(key, &v))
+                })
+                .collect(),
+        )
+    }
+}
+
+impl Debug for Vars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(self.0.iter().map(|(key, value)| (key, Raw(value))))
+            .finish()
+    }
+}
+
+/// An already formatted value.
+pub(crate) struct Raw<'a>(pub(crate) &'a str);
+
+impl Debug for Raw<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, BooleanAssertion, EqualityAssertion};
+
+    use super::{is_secret, mark_secret, Vars};
+
+    #[test]
+    fn test_secret_names_are_detected() {
+        assert_that!(is_secret("GITHUB_TOKEN".as_ref())).is_true();
+        assert_that!(is_secret("db_password".as_ref())).is_true();
+        assert_that!(is_secret("SECRET".as_ref())).is_true();
+        assert_that!(is_secret("AWS_SECRET".as_ref())).is_true();
+        assert_that!(is_secret("STRIPE_API_KEY".as_ref())).is_true();
+    }
+
+    #[test]
+    fn test_regular_names_are_not_secret() {
+        assert_that!(is_secret("PATH".as_ref())).is_false();
+        assert_that!(is_secret("TOKENIZER_THREADS".as_ref())).is_false();
+        assert_that!(is_secret("NOSECRET".as_ref())).is_false();
+        assert_that!(is_secret("KEYBOARD".as_ref())).is_false();
+    }
+
+    #[test]
+    fn test_marked_names_are_secret() {
+        assert_that!(is_secret("REDACT_MARKED_URL".as_ref())).is_false();
+
+        mark_secret("REDACT_MARKED_URL");
+
+        assert_that!(is_secret("REDACT_MARKED_URL".as_ref())).is_true();
+    }
+
+    #[test]
+    fn test_secret_values_are_redacted() {
+        let vars = [("HOST", "localhost"), ("API_TOKEN", "abc")];
+
+        let output = format!("{:?}", Vars::new(vars.iter().map(|(k, v)| (k, v))));
+
+        assert_that!(output).is_equal_to(r#"{"API_TOKEN": ***, "HOST": "localhost"}"#.to_string());
+    }
+}
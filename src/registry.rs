@@ -0,0 +1,161 @@
+//! Process wide registry of all currently active scopes.
+//!
+//! Checks of the accessor functions of this crate, like strict mode, only
+//! consult the scopes of the current thread, so tests running concurrently
+//! without the lock, e.g. read-only tests, don't affect each other. Threads
+//! spawned through [`crate::TempEnvScope::spawn`] belong to the thread which
+//! spawned them.
+
+use std::{
+    cell::Cell,
+    collections::{BTreeSet, HashSet},
+    ffi::OsStr,
+    fmt,
+    panic::Location,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, LazyLock, Mutex, MutexGuard, PoisonError,
+    },
+    thread::{self, ThreadId},
+    time::Duration,
+};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+static ACTIVE: LazyLock<Mutex<Vec<Arc<ScopeState>>>> = LazyLock::new(Mutex::default);
+
+thread_local! {
+    /// The thread whose scopes the current thread belongs to, if it was
+    /// spawned by a scope.
+    static OWNER: Cell<Option<ThreadId>> = const { Cell::new(None) };
+}
+
+/// State of a scope which must be reachable from outside the scope itself.
+#[derive(Debug)]
+pub(crate) struct ScopeState {
+    pub(crate) id: u64,
+    pub(crate) name: Option<String>,
+    /// The thread which created the scope.
+    pub(crate) thread: ThreadId,
+    /// Where the scope was created.
+    pub(crate) location: &'static Location<'static>,
+    data: Mutex<ScopeData>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct ScopeData {
+    pub(crate) strict: bool,
+    pub(crate) declared: HashSet<String>,
+    /// Declared variables which were read.
+    pub(crate) used: HashSet<String>,
+    pub(crate) check_unused: bool,
+    pub(crate) late_write_window: Option<Duration>,
+    pub(crate) track_reads: bool,
+    pub(crate) reads: BTreeSet<String>,
+    /// Where the scope was frozen, if it was.
+    pub(crate) frozen: Option<&'static Location<'static>>,
+    /// Variables changed through the scope, in the order of their first change.
+    pub(crate) overridden: Vec<String>,
+    pub(crate) foreign_drop_allowed: bool,
+    pub(crate) restore_policy: Option<crate::RestorePolicy>,
+}
+
+impl ScopeState {
+    pub(crate) fn data(&self) -> MutexGuard<'_, ScopeData> {
+        self.data.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Panics if the scope was frozen, as `key` is about to be changed.
+    pub(crate) fn check_not_frozen(&self, key: &OsStr) {
+        let frozen = self.data().frozen;
+        if let Some(location) = frozen {
+            panic!("temp_env_vars: cannot change {key:?}, scope {self} was frozen at {location}");
+        }
+    }
+}
+
+/// Panics if an active scope of the current thread was frozen, as `key` is
+/// about to be changed.
+pub(crate) fn check_not_frozen(key: &OsStr) {
+    for scope in owned_scopes() {
+        scope.check_not_frozen(key);
+    }
+}
+
+/// Identifies the scope in diagnostics, by its name if it has one.
+impl fmt::Display for ScopeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.name {
+            Some(name) => write!(f, "`{name}`"),
+            None => write!(f, "#{}", self.id),
+        }
+    }
+}
+
+/// Creates the state for a new scope and adds it to the active scopes.
+#[track_caller]
+pub(crate) fn register(name: Option<String>) -> Arc<ScopeState> {
+    let state = Arc::new(ScopeState {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        name,
+        thread: thread::current().id(),
+        location: Location::caller(),
+        data: Mutex::default(),
+    });
+    active().push(Arc::clone(&state));
+    state
+}
+
+pub(crate) fn unregister(state: &ScopeState) {
+    active().retain(|active| active.id != state.id);
+}
+
+/// Returns the newest active scope created after `state` on the same thread.
+pub(crate) fn newer_scope_of_thread(state: &ScopeState) -> Option<Arc<ScopeState>> {
+    let active = active();
+    let position = active.iter().position(|active| active.id == state.id)?;
+    active[position + 1..]
+        .iter()
+        .rev()
+        .find(|active| active.thread == state.thread)
+        .cloned()
+}
+
+/// Returns the thread whose scopes the current thread belongs to.
+pub(crate) fn owner() -> ThreadId {
+    OWNER.get().unwrap_or_else(|| thread::current().id())
+}
+
+/// Lets the current thread belong to the scopes of `owner`.
+pub(crate) fn adopt(owner: ThreadId) {
+    OWNER.set(Some(owner));
+}
+
+/// Returns the active scopes of the current thread and of the thread it
+/// belongs to, from the oldest to the newest.
+pub(crate) fn owned_scopes() -> Vec<Arc<ScopeState>> {
+    let (current, owner) = (thread::current().id(), owner());
+    active()
+        .iter()
+        .filter(|state| state.thread == current || state.thread == owner)
+        .cloned()
+        .collect()
+}
+
+/// Returns all active scopes, from the oldest to the newest.
+pub(crate) fn active_scopes() -> Vec<Arc<ScopeState>> {
+    active().clone()
+}
+
+/// Returns the newest active scope created by the current thread.
+pub(crate) fn current_thread_scope() -> Option<Arc<ScopeState>> {
+    let current = thread::current().id();
+    active()
+        .iter()
+        .rev()
+        .find(|state| state.thread == current)
+        .cloned()
+}
+
+fn active() -> MutexGuard<'static, Vec<Arc<ScopeState>>> {
+    ACTIVE.lock().unwrap_or_else(PoisonError::into_inner)
+}
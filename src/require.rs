@@ -0,0 +1,117 @@
+//! Preflight checks for the variables a test depends on.
+
+use std::{ffi::OsStr, fmt};
+
+use crate::TempEnvScope;
+
+/// The required variables which are not set, or empty, see
+/// [`TempEnvScope::require`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MissingVars {
+    pub missing: Vec<String>,
+    pub empty: Vec<String>,
+}
+
+impl fmt::Display for MissingVars {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "required environment variables are missing: {:?}",
+            self.missing
+        )?;
+        if !self.empty.is_empty() {
+            write!(f, ", or empty: {:?}", self.empty)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MissingVars {}
+
+impl TempEnvScope {
+    /// Checks that all variables are set, listing all missing ones at once.
+    ///
+    /// ```rust
+    /// use temp_env_vars::TempEnvScope;
+    ///
+    /// let env_scope = TempEnvScope::new();
+    /// env_scope.set("DATABASE_URL", "postgres://localhost");
+    /// env_scope.remove("S3_BUCKET");
+    ///
+    /// let err = env_scope.require(["DATABASE_URL", "S3_BUCKET"]).unwrap_err();
+    /// assert_eq!(err.missing, ["S3_BUCKET"]);
+    /// ```
+    pub fn require<I, K>(&self, keys: I) -> Result<(), MissingVars>
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<OsStr>,
+    {
+        check(keys, false)
+    }
+
+    /// Same as [`TempEnvScope::require`], but also fails for empty values.
+    pub fn require_non_empty<I, K>(&self, keys: I) -> Result<(), MissingVars>
+    where
+        I: IntoIterator<Item = K>,
+        K: AsRef<OsStr>,
+    {
+        check(keys, true)
+    }
+}
+
+fn check<I, K>(keys: I, non_empty: bool) -> Result<(), MissingVars>
+where
+    I: IntoIterator<Item = K>,
+    K: AsRef<OsStr>,
+{
+    let mut missing = MissingVars::default();
+    for key in keys {
+        let key = key.as_ref();
+        let name = key.to_string_lossy().into_owned();
+        match crate::sys::var_os(key) {
+            None => missing.missing.push(name),
+            Some(value) if non_empty && value.is_empty() => missing.empty.push(name),
+            Some(_) => {}
+        }
+    }
+    if missing.missing.is_empty() && missing.empty.is_empty() {
+        Ok(())
+    } else {
+        Err(missing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, ResultAssertion};
+    use serial_test::serial;
+
+    use super::MissingVars;
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_all_missing_vars_are_listed() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("REQUIRED_SET", "1");
+        env_scope.set("REQUIRED_EMPTY", "");
+        env_scope.remove("REQUIRED_FOO");
+        env_scope.remove("REQUIRED_BAR");
+        let keys = [
+            "REQUIRED_SET",
+            "REQUIRED_EMPTY",
+            "REQUIRED_FOO",
+            "REQUIRED_BAR",
+        ];
+
+        assert_that!(env_scope.require(keys)).has_err(MissingVars {
+            missing: vec!["REQUIRED_FOO".to_string(), "REQUIRED_BAR".to_string()],
+            empty: vec![],
+        });
+        assert_that!(env_scope.require_non_empty(keys)).has_err(MissingVars {
+            missing: vec!["REQUIRED_FOO".to_string(), "REQUIRED_BAR".to_string()],
+            empty: vec!["REQUIRED_EMPTY".to_string()],
+        });
+        assert_that!(env_scope.require(["REQUIRED_SET"])).is_ok();
+    }
+}
@@ -0,0 +1,109 @@
+//! Restoring the environment of a scope, shared by the scope and its watchdog.
+
+use std::{
+    cmp::Reverse,
+    ffi::OsString,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use crate::{
+    observe::{self, EnvEvent},
+    registry::ScopeState,
+    snapshot::{self, EnvKey},
+    sys, Baseline,
+};
+
+/// What a scope needs for restoring the environment, so the watchdog restores
+/// it the same way as the scope itself when dropped.
+pub(crate) struct Restorer {
+    pub(crate) baseline: Baseline,
+    /// The variables changed through the scope in the order of their first change,
+    /// with their original value. `None` for variables which did not exist.
+    journal: Mutex<Vec<(EnvKey, Option<OsString>)>>,
+    pub(crate) state: Arc<ScopeState>,
+    pub(crate) observers: Mutex<Vec<observe::Observer>>,
+    pub(crate) restore_hooks: Mutex<observe::RestoreHooks>,
+}
+
+impl Restorer {
+    pub(crate) fn new(baseline: Baseline, state: Arc<ScopeState>) -> Restorer {
+        Restorer {
+            baseline,
+            journal: Mutex::default(),
+            state,
+            observers: Mutex::default(),
+            restore_hooks: Mutex::default(),
+        }
+    }
+
+    pub(crate) fn journal(&self) -> MutexGuard<'_, Vec<(EnvKey, Option<OsString>)>> {
+        self.journal.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /// Notifies the observers about variables changed by other means than
+    /// through the scope.
+    pub(crate) fn notify_external_changes(&self) {
+        if !matches!(self.baseline, Baseline::Snapshot(_)) || !self.has_observers() {
+            return;
+        }
+        let changed: Vec<EnvKey> = self.journal().iter().map(|(key, _)| key.clone()).collect();
+        for (key, _) in self.restore_plan() {
+            if !changed.contains(&EnvKey::new(key.as_os_str())) {
+                let value = sys::var_os(&key);
+                self.notify(EnvEvent::ExternalChange { key, value });
+            }
+        }
+    }
+
+    /// Sets the environment variables to the state as they were
+    /// when the scope was created.
+    pub(crate) fn restore(&self) {
+        self.restore_vars(self.restore_plan());
+    }
+
+    /// Returns the sorted names of the variables which differ from the baseline.
+    pub(crate) fn unrestored_vars(&self) -> Vec<String> {
+        let mut vars: Vec<String> = match &self.baseline {
+            Baseline::Snapshot(original_vars) => {
+                let now = snapshot::capture();
+                snapshot::changes(original_vars, &now)
+                    .iter()
+                    .map(|change| change.key().as_os_str().to_string_lossy().into_owned())
+                    .collect()
+            }
+            Baseline::Journal => self
+                .journal()
+                .iter()
+                .filter(|(key, original)| sys::var_os(key.as_os_str()) != *original)
+                .map(|(key, _)| key.as_os_str().to_string_lossy().into_owned())
+                .collect(),
+        };
+        vars.dedup();
+        vars
+    }
+
+    /// Returns the variables to set (or remove, if `None`) for restoring, in order.
+    ///
+    /// Variables changed through the scope are restored first, in reverse order
+    /// of their first change. Other variables follow, sorted by name.
+    pub(crate) fn restore_plan(&self) -> Vec<(OsString, Option<OsString>)> {
+        let journal = self.journal();
+        match &self.baseline {
+            Baseline::Snapshot(original_vars) => {
+                let now = snapshot::capture();
+                let mut changes = snapshot::changes(original_vars, &now);
+                // Stable, so a differently-cased name is still removed before being set
+                changes.sort_by_key(|change| {
+                    let position = journal.iter().position(|(key, _)| key == change.key());
+                    Reverse(position.map_or(0, |index| index + 1))
+                });
+                changes.iter().map(|change| change.to_var()).collect()
+            }
+            Baseline::Journal => journal
+                .iter()
+                .rev()
+                .map(|(key, value)| (key.as_os_str().to_owned(), value.clone()))
+                .collect(),
+        }
+    }
+}
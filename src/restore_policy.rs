@@ -0,0 +1,140 @@
+//! What happens when a scope cannot restore some variables.
+
+use std::{
+    ffi::OsString,
+    panic::{self, AssertUnwindSafe},
+    sync::{Mutex, PoisonError},
+};
+
+use crate::{restore::Restorer, sys, RestoreError, TempEnvScope};
+
+/// The policy used when restoring variables fails, e.g. because the backend
+/// panics. The remaining variables are restored in any case.
+#[derive(Debug, Clone, Copy)]
+pub enum RestorePolicy {
+    /// Panics after restoring the remaining variables, unless the thread is
+    /// already panicking. This is the default.
+    Panic,
+    /// Prints the error to stderr and continues.
+    Log,
+    /// Calls the function with the error and continues.
+    Callback(fn(&RestoreError)),
+}
+
+static GLOBAL_POLICY: Mutex<RestorePolicy> = Mutex::new(RestorePolicy::Panic);
+
+/// Sets the policy of all scopes which have no policy of their own set with
+/// [`TempEnvScope::set_restore_policy`].
+pub fn set_restore_policy(policy: RestorePolicy) {
+    *GLOBAL_POLICY.lock().unwrap_or_else(PoisonError::into_inner) = policy;
+}
+
+impl TempEnvScope {
+    /// Sets the policy used if this scope cannot restore some variables,
+    /// instead of the one set with [`set_restore_policy`].
+    pub fn set_restore_policy(&self, policy: RestorePolicy) {
+        self.state.data().restore_policy = Some(policy);
+    }
+}
+
+/// Sets (or removes, if `None`) the given variables, continuing after failures,
+/// and returns the names of the variables which could not be restored.
+pub(crate) fn restore_all(vars: Vec<(OsString, Option<OsString>)>) -> Vec<String> {
+    let mut failed = Vec::new();
+    for (key, value) in vars {
+        let restored = panic::catch_unwind(AssertUnwindSafe(|| match &value {
+            Some(value) => sys::set_var(&key, value),
+            None => sys::remove_var(&key),
+        }));
+        if restored.is_err() {
+            failed.push(key.to_string_lossy().into_owned());
+        }
+    }
+    failed
+}
+
+impl Restorer {
+    /// Restores the given variables, handling failures according to the policy.
+    pub(crate) fn restore_vars(&self, vars: Vec<(OsString, Option<OsString>)>) {
+        let failed = restore_all(vars);
+        if !failed.is_empty() {
+            self.restore_failed(&RestoreError { vars: failed });
+        }
+    }
+
+    fn restore_failed(&self, err: &RestoreError) {
+        let policy = self
+            .state
+            .data()
+            .restore_policy
+            .unwrap_or_else(|| *GLOBAL_POLICY.lock().unwrap_or_else(PoisonError::into_inner));
+        match policy {
+            RestorePolicy::Panic if !std::thread::panicking() => {
+                panic!(
+                    "temp_env_vars: scope {} failed restoring: {err}",
+                    self.state
+                )
+            }
+            RestorePolicy::Panic | RestorePolicy::Log => {
+                eprintln!(
+                    "temp_env_vars: scope {} failed restoring: {err}",
+                    self.state
+                )
+            }
+            RestorePolicy::Callback(callback) => callback(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use assertor::{assert_that, EqualityAssertion};
+    use serial_test::serial;
+
+    use super::RestorePolicy;
+    use crate::{RestoreError, TempEnvScope};
+
+    static FAILURES: AtomicUsize = AtomicUsize::new(0);
+
+    fn count_failure(err: &RestoreError) {
+        FAILURES.fetch_add(err.vars.len(), Ordering::SeqCst);
+    }
+
+    fn failure() -> RestoreError {
+        RestoreError {
+            vars: vec!["FOO".to_string()],
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_callback_policy_receives_failures() {
+        let env_scope = TempEnvScope::journaled();
+        env_scope.set_restore_policy(RestorePolicy::Callback(count_failure));
+
+        env_scope.restorer.restore_failed(&failure());
+
+        assert_that!(FAILURES.load(Ordering::SeqCst)).is_equal_to(1);
+    }
+
+    #[test]
+    #[serial]
+    fn test_log_policy_continues() {
+        let env_scope = TempEnvScope::journaled();
+        env_scope.set_restore_policy(RestorePolicy::Log);
+
+        env_scope.restorer.restore_failed(&failure());
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "failed restoring")]
+    fn test_panic_policy_panics() {
+        let env_scope = TempEnvScope::journaled();
+        env_scope.set_restore_policy(RestorePolicy::Panic);
+
+        env_scope.restorer.restore_failed(&failure());
+    }
+}
@@ -0,0 +1,74 @@
+//! Listing the active scopes for debugging, e.g. when a test hangs on the lock.
+
+use std::{panic::Location, thread::ThreadId};
+
+use crate::registry;
+
+/// An active scope, see [`active_scopes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopeInfo {
+    /// The id identifying the scope in diagnostics as `#<id>`.
+    pub id: u64,
+    /// The name given with [`crate::TempEnvScope::named`].
+    pub name: Option<String>,
+    /// Where the scope was created.
+    pub location: &'static Location<'static>,
+    /// The thread which created the scope.
+    pub thread: ThreadId,
+    /// The variables changed through the scope so far, in the order of their
+    /// first change.
+    pub overridden: Vec<String>,
+}
+
+/// Returns all active scopes of the process, from the oldest to the newest,
+/// e.g. to find out who holds overrides when a test hangs on the lock.
+///
+/// ```rust
+/// let env_scope = temp_env_vars::TempEnvScope::named("debugging");
+/// env_scope.set("DEBUG_VAR", "1");
+///
+/// let scope = temp_env_vars::active_scopes().pop().unwrap();
+/// assert_eq!(scope.name.as_deref(), Some("debugging"));
+/// assert_eq!(scope.overridden, ["DEBUG_VAR"]);
+/// ```
+pub fn active_scopes() -> Vec<ScopeInfo> {
+    registry::active_scopes()
+        .iter()
+        .map(|state| ScopeInfo {
+            id: state.id,
+            name: state.name.clone(),
+            location: state.location,
+            thread: state.thread,
+            overridden: state.data().overridden.clone(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, BooleanAssertion, EqualityAssertion};
+    use serial_test::serial;
+
+    use super::active_scopes;
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_active_scopes_are_listed() {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("SCOPE_INFO_B", "1");
+        env_scope.set("SCOPE_INFO_A", "1");
+        env_scope.set("SCOPE_INFO_B", "2");
+
+        let info = active_scopes()
+            .into_iter()
+            .find(|info| info.id == env_scope.state.id)
+            .unwrap();
+        assert_that!(info.overridden)
+            .is_equal_to(vec!["SCOPE_INFO_B".to_string(), "SCOPE_INFO_A".to_string()]);
+        assert_that!(info.location.file().ends_with("scope_info.rs")).is_true();
+
+        drop(env_scope);
+        assert_that!(active_scopes().iter().any(|scope| scope.id == info.id)).is_false();
+    }
+}
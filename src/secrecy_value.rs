@@ -0,0 +1,43 @@
+//! Setting variables to secrets of the `secrecy` crate.
+
+use std::ffi::OsStr;
+
+use secrecy::{ExposeSecret, SecretString};
+
+use crate::SecretValue;
+
+/// Exposes the secret only to pass it to the environment. `SecretString`
+/// zeroizes it when dropped, e.g. right after [`crate::TempEnvScope::set_secret`]
+/// took it by value.
+///
+/// ```rust
+/// use secrecy::SecretString;
+///
+/// let env_scope = temp_env_vars::TempEnvScope::new();
+/// env_scope.set_secret("API_KEY", SecretString::from("s3cr3t"));
+/// ```
+impl SecretValue for SecretString {
+    fn expose_env_value(&self) -> &OsStr {
+        OsStr::new(self.expose_secret())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, BooleanAssertion, ResultAssertion};
+    use secrecy::SecretString;
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_secret_is_set_and_redacted() {
+        let env_scope = TempEnvScope::new();
+
+        env_scope.set_secret("SECRECY_KEY", SecretString::from("s3cr3t"));
+
+        assert_that!(std::env::var("SECRECY_KEY")).has_ok("s3cr3t".to_string());
+        assert_that!(format!("{:?}", crate::capture()).contains("s3cr3t")).is_false();
+    }
+}
@@ -0,0 +1,163 @@
+//! Setting variables from serializable values, with the `serde` feature.
+
+use std::ffi::OsStr;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::TempEnvScope;
+
+impl TempEnvScope {
+    /// Sets a variable to the JSON representation of the value.
+    ///
+    /// ```rust
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// env_scope.set_json("FEATURE_FLAGS", &serde_json::json!({"a": true}));
+    ///
+    /// assert_eq!(std::env::var("FEATURE_FLAGS").unwrap(), r#"{"a":true}"#);
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value cannot be serialized, e.g. a map with non-string keys.
+    pub fn set_json<T: Serialize + ?Sized>(&self, key: impl AsRef<OsStr>, value: &T) {
+        let key = key.as_ref();
+        let json = serde_json::to_string(value).unwrap_or_else(|err| {
+            panic!("temp_env_vars: cannot serialize the value of {key:?} to JSON: {err}")
+        });
+        self.set(key, json);
+    }
+
+    /// Sets one variable per field of a struct, named after the prefix and the
+    /// uppercased field name, as expected by `envy` and `config`.
+    ///
+    /// Fields of nested structs are separated by `__`, sequences of plain values
+    /// are joined by `,` and `None` fields are not set. Other sequences are set
+    /// as JSON.
+    ///
+    /// ```rust
+    /// #[derive(serde::Serialize)]
+    /// struct Db {
+    ///     url: String,
+    /// }
+    ///
+    /// #[derive(serde::Serialize)]
+    /// struct Config {
+    ///     port: u16,
+    ///     db: Db,
+    /// }
+    ///
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// let db = Db { url: "postgres://localhost".into() };
+    /// env_scope.set_struct("APP", &Config { port: 8080, db });
+    ///
+    /// assert_eq!(std::env::var("APP_PORT").unwrap(), "8080");
+    /// assert_eq!(std::env::var("APP_DB__URL").unwrap(), "postgres://localhost");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value cannot be serialized or is not a struct or map.
+    pub fn set_struct<T: Serialize + ?Sized>(&self, prefix: &str, value: &T) {
+        let value = serde_json::to_value(value).unwrap_or_else(|err| {
+            panic!("temp_env_vars: cannot serialize the value for {prefix:?}: {err}")
+        });
+        let Value::Object(fields) = value else {
+            panic!("temp_env_vars: the value for {prefix:?} must be a struct or map, not {value}");
+        };
+
+        let mut vars = Vec::new();
+        for (name, value) in fields {
+            flatten(
+                format!("{prefix}_{}", name.to_uppercase()),
+                value,
+                &mut vars,
+            );
+        }
+        for (key, value) in vars {
+            self.set(key, value);
+        }
+    }
+}
+
+/// Collects the variables for the value, nested fields separated by `__`.
+fn flatten(key: String, value: Value, vars: &mut Vec<(String, String)>) {
+    match value {
+        Value::Null => {}
+        Value::Object(fields) => {
+            for (name, value) in fields {
+                flatten(format!("{key}__{}", name.to_uppercase()), value, vars);
+            }
+        }
+        Value::Array(items) if items.iter().all(is_plain) => {
+            let items: Vec<String> = items.into_iter().filter_map(plain).collect();
+            vars.push((key, items.join(",")));
+        }
+        Value::Array(_) => vars.push((key, value.to_string())),
+        value => vars.extend(plain(value).map(|value| (key, value))),
+    }
+}
+
+fn is_plain(value: &Value) -> bool {
+    matches!(value, Value::Bool(_) | Value::Number(_) | Value::String(_))
+}
+
+fn plain(value: Value) -> Option<String> {
+    match value {
+        Value::Bool(value) => Some(value.to_string()),
+        Value::Number(value) => Some(value.to_string()),
+        Value::String(value) => Some(value),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use assertor::{assert_that, ResultAssertion};
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_json_values_are_set() {
+        let env_scope = TempEnvScope::new();
+
+        env_scope.set_json(
+            "SERIALIZE_FLAGS",
+            &BTreeMap::from([("a", true), ("b", false)]),
+        );
+
+        assert_that!(std::env::var("SERIALIZE_FLAGS"))
+            .has_ok(r#"{"a":true,"b":false}"#.to_string());
+    }
+
+    #[test]
+    #[serial]
+    fn test_structs_are_flattened_into_prefixed_vars() {
+        let env_scope = TempEnvScope::new();
+        let config = serde_json::json!({
+            "host": "localhost",
+            "hosts": ["a", "b"],
+            "db": {"url": "postgres://localhost", "pool": {"size": 4}},
+            "user": null,
+        });
+
+        env_scope.set_struct("SERIALIZE", &config);
+
+        assert_that!(std::env::var("SERIALIZE_HOST")).has_ok("localhost".to_string());
+        assert_that!(std::env::var("SERIALIZE_HOSTS")).has_ok("a,b".to_string());
+        assert_that!(std::env::var("SERIALIZE_DB__URL")).has_ok("postgres://localhost".to_string());
+        assert_that!(std::env::var("SERIALIZE_DB__POOL__SIZE")).has_ok("4".to_string());
+        assert_that!(std::env::var("SERIALIZE_USER")).is_err();
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "must be a struct or map")]
+    fn test_plain_values_cannot_be_flattened() {
+        TempEnvScope::new().set_struct("SERIALIZE", &1);
+    }
+}
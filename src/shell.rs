@@ -0,0 +1,84 @@
+//! Scripts reproducing the changes of a scope in a terminal.
+
+use std::ffi::OsString;
+
+use crate::TempEnvScope;
+
+impl TempEnvScope {
+    /// Returns `export KEY='value'` and `unset KEY` lines for all variables
+    /// changed since this scope was created, sorted by name, for POSIX shells.
+    ///
+    /// The environment of a failing test can be pasted into a terminal this way.
+    /// Values are not redacted, secrets included.
+    ///
+    /// ```rust
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// env_scope.set("GREETING", "it's me");
+    ///
+    /// assert_eq!(env_scope.to_shell_exports(), "export GREETING='it'\\''s me'\n");
+    /// ```
+    pub fn to_shell_exports(&self) -> String {
+        self.changed_vars()
+            .into_iter()
+            .map(|(key, value)| match value {
+                Some(value) => format!("export {key}='{}'\n", value.replace('\'', r"'\''")),
+                None => format!("unset {key}\n"),
+            })
+            .collect()
+    }
+
+    /// Same as [`Self::to_shell_exports`], but for PowerShell.
+    pub fn to_powershell(&self) -> String {
+        self.changed_vars()
+            .into_iter()
+            .map(|(key, value)| match value {
+                Some(value) => format!("${{env:{key}}} = '{}'\n", value.replace('\'', "''")),
+                None => format!("Remove-Item -ErrorAction SilentlyContinue 'Env:{key}'\n"),
+            })
+            .collect()
+    }
+
+    /// Returns the current values of all changed variables, sorted by name.
+    fn changed_vars(&self) -> Vec<(String, Option<String>)> {
+        let mut keys: Vec<OsString> = self
+            .restorer
+            .restore_plan()
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        keys.sort();
+        keys.into_iter()
+            .map(|key| {
+                let value =
+                    crate::sys::var_os(&key).map(|value| value.to_string_lossy().into_owned());
+                (key.to_string_lossy().into_owned(), value)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, EqualityAssertion};
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_changes_are_exported_as_scripts() {
+        std::env::set_var("SHELL_REMOVED", "1");
+        let env_scope = TempEnvScope::new();
+        env_scope.set("SHELL_QUOTED", "it's");
+        env_scope.remove("SHELL_REMOVED");
+
+        assert_that!(env_scope.to_shell_exports())
+            .is_equal_to("export SHELL_QUOTED='it'\\''s'\nunset SHELL_REMOVED\n".to_string());
+        assert_that!(env_scope.to_powershell()).is_equal_to(
+            "${env:SHELL_QUOTED} = 'it''s'\nRemove-Item -ErrorAction SilentlyContinue 'Env:SHELL_REMOVED'\n"
+                .to_string(),
+        );
+        drop(env_scope);
+        std::env::remove_var("SHELL_REMOVED");
+    }
+}
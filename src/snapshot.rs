@@ -0,0 +1,304 @@
+//! Capturing the environment for later comparison and restoration.
+
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    hash::{Hash, Hasher},
+    sync::{Arc, Mutex, Weak},
+};
+
+use crate::{error, sys, EnvError};
+
+/// Variables of the environment by their name.
+pub(crate) type EnvMap = HashMap<EnvKey, EnvValue>;
+
+/// Value of a variable, shared between snapshots.
+pub(crate) type EnvValue = Arc<OsStr>;
+
+/// Captures the current process environment.
+pub(crate) fn capture() -> EnvMap {
+    let latest = latest();
+    sys::vars_os()
+        .map(|(key, value)| intern(latest.as_deref(), key, value))
+        .collect()
+}
+
+/// Captures the current process environment, failing if any variable could
+/// not be restored faithfully.
+pub(crate) fn try_capture() -> Result<EnvMap, EnvError> {
+    let latest = latest();
+    sys::vars_os()
+        .map(|(key, value)| {
+            error::validate_existing(&key, &value)?;
+            Ok(intern(latest.as_deref(), key, value))
+        })
+        .collect()
+}
+
+/// Reuses the name and value of a variable from the latest snapshot, if they
+/// are the same, so snapshots of similar environments share their memory.
+fn intern(latest: Option<&EnvMap>, key: OsString, value: OsString) -> (EnvKey, EnvValue) {
+    let key = EnvKey::new(key);
+    match latest.and_then(|latest| latest.get_key_value(&key)) {
+        Some((shared_key, shared_value)) if shared_key.as_os_str() == key.as_os_str() => {
+            let value = if **shared_value == *value {
+                Arc::clone(shared_value)
+            } else {
+                value.into()
+            };
+            (shared_key.clone(), value)
+        }
+        _ => (key, value.into()),
+    }
+}
+
+/// The most recent snapshot still in use by a scope.
+static LATEST: Mutex<Weak<EnvMap>> = Mutex::new(Weak::new());
+
+fn latest() -> Option<Arc<EnvMap>> {
+    LATEST
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .upgrade()
+}
+
+/// Like [`try_capture`], but shares the snapshot with other scopes if the
+/// environment did not change in between, e.g. for nested scopes.
+///
+/// Snapshots are never changed after capturing, so sharing them is safe.
+pub(crate) fn try_capture_shared() -> Result<Arc<EnvMap>, EnvError> {
+    let vars = try_capture()?;
+    let mut latest = LATEST.lock().unwrap_or_else(|err| err.into_inner());
+    if let Some(shared) = latest.upgrade().filter(|shared| **shared == vars) {
+        return Ok(shared);
+    }
+    let shared = Arc::new(vars);
+    *latest = Arc::downgrade(&shared);
+    Ok(shared)
+}
+
+/// A single change needed to turn one snapshot into another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Change<'a> {
+    Remove(&'a EnvKey),
+    Set(&'a EnvKey, &'a EnvValue),
+}
+
+/// Computes the changes needed to turn `now` back into `original`, sorted by
+/// the name of the variable.
+///
+/// Variables which are unchanged are not touched at all. Variables which are
+/// not restorable are ignored.
+pub(crate) fn changes<'a>(original: &'a EnvMap, now: &'a EnvMap) -> Vec<Change<'a>> {
+    let mut changes = Vec::new();
+    for key in now.keys() {
+        if key.is_restorable() && !original.contains_key(key) {
+            changes.push(Change::Remove(key));
+        }
+    }
+    for (key, value) in original.iter().filter(|(key, _)| key.is_restorable()) {
+        match now.get_key_value(key) {
+            Some((now_key, now_value))
+                if now_key.as_os_str() == key.as_os_str() && now_value == value => {}
+            Some((now_key, _)) => {
+                // Restore the original casing of case-insensitive names
+                if now_key.as_os_str() != key.as_os_str() {
+                    changes.push(Change::Remove(now_key));
+                }
+                changes.push(Change::Set(key, value));
+            }
+            None => changes.push(Change::Set(key, value)),
+        }
+    }
+    // Stable, so a differently-cased name is still removed before being set
+    changes.sort_by_key(|change| change.key());
+    changes
+}
+
+impl<'a> Change<'a> {
+    pub(crate) fn key(&self) -> &'a EnvKey {
+        match self {
+            Change::Remove(key) | Change::Set(key, _) => key,
+        }
+    }
+
+    /// Returns the name and the value to restore, `None` for removing it.
+    pub(crate) fn to_var(&self) -> (OsString, Option<OsString>) {
+        match self {
+            Change::Remove(key) => (key.as_os_str().to_owned(), None),
+            Change::Set(key, value) => (key.as_os_str().to_owned(), Some(value.to_os_string())),
+        }
+    }
+}
+
+/// Name of an environment variable.
+///
+/// On Windows, names are case-insensitive, so `Path` and `PATH` are the same
+/// variable. Such keys compare equal, while the original casing is preserved
+/// for restoring the variable.
+///
+/// Keys are cheap to clone, as the name is shared.
+#[derive(Debug, Clone)]
+pub(crate) struct EnvKey {
+    original: Arc<OsStr>,
+    #[cfg(windows)]
+    normalized: Arc<str>,
+}
+
+impl EnvKey {
+    pub(crate) fn new(key: impl Into<OsString>) -> EnvKey {
+        let original: Arc<OsStr> = key.into().into();
+        EnvKey {
+            #[cfg(windows)]
+            normalized: original.to_string_lossy().to_uppercase().into(),
+            original,
+        }
+    }
+
+    pub(crate) fn as_os_str(&self) -> &OsStr {
+        &self.original
+    }
+
+    /// Returns whether the variable can be set and removed through `std::env`.
+    ///
+    /// Names containing `=` or NUL cannot. On Windows, such variables exist
+    /// nevertheless, e.g. the hidden per-drive working directories like `=C:`
+    /// maintained by cmd.exe. They are kept in snapshots, but never touched
+    /// when restoring.
+    pub(crate) fn is_restorable(&self) -> bool {
+        let key = self.original.to_string_lossy();
+        !key.is_empty() && !key.contains(['=', '\0'])
+    }
+
+    #[cfg(windows)]
+    fn normalized(&self) -> &str {
+        &self.normalized
+    }
+
+    #[cfg(not(windows))]
+    fn normalized(&self) -> &OsStr {
+        &self.original
+    }
+}
+
+impl PartialEq for EnvKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.normalized() == other.normalized()
+    }
+}
+
+impl Eq for EnvKey {}
+
+impl PartialOrd for EnvKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for EnvKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.normalized().cmp(other.normalized())
+    }
+}
+
+impl Hash for EnvKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, BooleanAssertion, EqualityAssertion};
+
+    use std::{ffi::OsStr, sync::Arc};
+
+    use serial_test::serial;
+
+    use super::{changes, try_capture_shared, Change, EnvKey, EnvMap};
+
+    fn env_map(vars: &[(&str, &str)]) -> EnvMap {
+        vars.iter()
+            .map(|(key, value)| (EnvKey::new(key), OsStr::new(value).into()))
+            .collect()
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_keys_are_case_insensitive() {
+        assert_that!(EnvKey::new("Path") == EnvKey::new("PATH")).is_true();
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_keys_are_case_sensitive() {
+        assert_that!(EnvKey::new("Path") == EnvKey::new("PATH")).is_false();
+    }
+
+    #[test]
+    fn test_hidden_and_malformed_keys_are_not_restorable() {
+        assert_that!(EnvKey::new("PATH").is_restorable()).is_true();
+        assert_that!(EnvKey::new("=C:").is_restorable()).is_false();
+        assert_that!(EnvKey::new("A=B").is_restorable()).is_false();
+        assert_that!(EnvKey::new("").is_restorable()).is_false();
+    }
+
+    #[test]
+    fn test_original_casing_is_preserved() {
+        assert_that!(EnvKey::new("Path").as_os_str() == "Path").is_true();
+    }
+
+    #[test]
+    fn test_only_differences_are_changed() {
+        let original = env_map(&[("SAME", "1"), ("CHANGED", "1"), ("REMOVED", "1")]);
+        let now = env_map(&[("SAME", "1"), ("CHANGED", "2"), ("ADDED", "1")]);
+
+        assert_that!(changes(&original, &now)).is_equal_to(vec![
+            Change::Remove(&EnvKey::new("ADDED")),
+            Change::Set(&EnvKey::new("CHANGED"), &OsStr::new("1").into()),
+            Change::Set(&EnvKey::new("REMOVED"), &OsStr::new("1").into()),
+        ]);
+    }
+
+    #[test]
+    fn test_nothing_is_changed_for_equal_snapshots() {
+        let original = env_map(&[("SAME", "1"), ("=C:", "C:\\")]);
+
+        assert_that!(changes(&original, &original.clone()).is_empty()).is_true();
+    }
+
+    #[test]
+    #[serial]
+    fn test_snapshots_of_unchanged_env_are_shared() {
+        let outer = try_capture_shared().unwrap();
+        let inner = try_capture_shared().unwrap();
+
+        assert_that!(Arc::ptr_eq(&outer, &inner)).is_true();
+    }
+
+    #[test]
+    #[serial]
+    fn test_unchanged_vars_are_shared_between_snapshots() {
+        std::env::set_var("SNAPSHOT_INTERNED", "1");
+        let outer = try_capture_shared().unwrap();
+        std::env::set_var("SNAPSHOT_CHANGED", "1");
+        let inner = try_capture_shared().unwrap();
+        std::env::remove_var("SNAPSHOT_CHANGED");
+        std::env::remove_var("SNAPSHOT_INTERNED");
+
+        let key = EnvKey::new("SNAPSHOT_INTERNED");
+        assert_that!(Arc::ptr_eq(&outer[&key], &inner[&key])).is_true();
+    }
+
+    #[test]
+    #[serial]
+    fn test_snapshots_of_changed_env_are_not_shared() {
+        let outer = try_capture_shared().unwrap();
+        std::env::set_var("SNAPSHOT_SHARED", "1");
+        let inner = try_capture_shared().unwrap();
+        std::env::remove_var("SNAPSHOT_SHARED");
+
+        assert_that!(Arc::ptr_eq(&outer, &inner)).is_false();
+    }
+}
@@ -0,0 +1,204 @@
+//! Threads bound to a scope, which are joined before the scope restores the
+//! environment.
+
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    thread::{self, JoinHandle, Thread},
+};
+
+use crate::{lock, registry, TempEnvScope};
+
+/// Handle of a thread spawned by [`TempEnvScope::spawn`].
+#[derive(Debug)]
+pub struct ScopedJoinHandle<T> {
+    spawned: Arc<Spawned<T>>,
+    thread: Thread,
+}
+
+#[derive(Debug)]
+struct Spawned<T> {
+    status: Mutex<Status<T>>,
+}
+
+#[derive(Debug)]
+enum Status<T> {
+    Running(JoinHandle<T>),
+    /// Joined by the scope, keeping the result for the handle.
+    Joined(thread::Result<T>),
+    /// Joined through the handle.
+    Taken,
+}
+
+/// A spawned thread, whatever it returns.
+pub(crate) trait Joinable: Send + Sync {
+    /// Joins the thread unless it was joined before. Returns whether it panicked.
+    fn join_if_running(&self) -> bool;
+}
+
+impl<T: Send> Joinable for Spawned<T> {
+    fn join_if_running(&self) -> bool {
+        let mut status = self.status.lock().unwrap_or_else(PoisonError::into_inner);
+        match std::mem::replace(&mut *status, Status::Taken) {
+            Status::Running(handle) => {
+                let result = handle.join();
+                let panicked = result.is_err();
+                *status = Status::Joined(result);
+                panicked
+            }
+            other => {
+                *status = other;
+                false
+            }
+        }
+    }
+}
+
+impl TempEnvScope {
+    /// Spawns a thread which is joined when the scope is dropped, before the
+    /// environment gets restored, so the thread cannot race with restoring.
+    ///
+    /// If the current thread holds the lock of this crate, e.g. within a
+    /// `#[temp_env_vars]` test, the spawned thread shares it, so it can use
+    /// [`crate::set_var`] and other functions taking the lock.
+    ///
+    /// If the thread panicked and was not joined through the returned handle,
+    /// dropping the scope panics after restoring the environment.
+    pub fn spawn<F, T>(&self, f: F) -> ScopedJoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let held = lock::held_by_current_thread();
+        let owner = registry::owner();
+        let handle = thread::spawn(move || {
+            lock::inherit(held);
+            registry::adopt(owner);
+            f()
+        });
+        let thread = handle.thread().clone();
+        let spawned = Arc::new(Spawned {
+            status: Mutex::new(Status::Running(handle)),
+        });
+        self.threads
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::clone(&spawned) as Arc<dyn Joinable>);
+        ScopedJoinHandle { spawned, thread }
+    }
+
+    /// Joins all spawned threads which were not joined yet. Returns whether
+    /// any of them panicked.
+    pub(crate) fn join_threads(&self) -> bool {
+        let threads =
+            std::mem::take(&mut *self.threads.lock().unwrap_or_else(PoisonError::into_inner));
+        let panicked: Vec<bool> = threads
+            .iter()
+            .map(|thread| thread.join_if_running())
+            .collect();
+        panicked.contains(&true)
+    }
+}
+
+impl<T> ScopedJoinHandle<T> {
+    /// Waits for the thread to finish, like [`JoinHandle::join`]. If the scope
+    /// was dropped before, it joined the thread already and this returns the
+    /// result kept since.
+    pub fn join(self) -> thread::Result<T> {
+        let status = std::mem::replace(
+            &mut *self
+                .spawned
+                .status
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner),
+            Status::Taken,
+        );
+        match status {
+            Status::Running(handle) => handle.join(),
+            Status::Joined(result) => result,
+            Status::Taken => unreachable!("only `join` takes the result, consuming the handle"),
+        }
+    }
+
+    /// Returns the spawned thread.
+    pub fn thread(&self) -> &Thread {
+        &self.thread
+    }
+
+    /// Returns whether the thread finished running.
+    pub fn is_finished(&self) -> bool {
+        match &*self
+            .spawned
+            .status
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+        {
+            Status::Running(handle) => handle.is_finished(),
+            Status::Joined(_) | Status::Taken => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use assertor::{assert_that, BooleanAssertion, EqualityAssertion, ResultAssertion};
+    use serial_test::serial;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_threads_are_joined_before_restoring() {
+        let finished = Arc::new(AtomicBool::new(false));
+
+        {
+            let env_scope = TempEnvScope::new();
+            let finished = Arc::clone(&finished);
+            env_scope.spawn(move || {
+                std::thread::sleep(Duration::from_millis(50));
+                finished.store(true, Ordering::SeqCst);
+            });
+        }
+
+        assert_that!(finished.load(Ordering::SeqCst)).is_true();
+    }
+
+    #[test]
+    #[serial]
+    fn test_threads_share_the_lock_of_the_spawning_thread() {
+        let _lock = crate::env_lock();
+        let env_scope = TempEnvScope::new();
+
+        let handle = env_scope.spawn(|| crate::set_var("SPAWN_VAR", "1"));
+
+        assert_that!(handle.join().is_ok()).is_true();
+        assert_that!(std::env::var("SPAWN_VAR")).has_ok("1".to_string());
+    }
+
+    #[test]
+    #[serial]
+    fn test_handle_returns_the_result_after_the_scope_was_dropped() {
+        let env_scope = TempEnvScope::new();
+        let handle = env_scope.spawn(|| 42);
+
+        drop(env_scope);
+
+        assert_that!(handle.is_finished()).is_true();
+        assert_that!(handle.join().ok()).is_equal_to(Some(42));
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "panicked")]
+    fn test_panics_of_threads_are_propagated() {
+        let env_scope = TempEnvScope::new();
+        env_scope.spawn(|| panic!("failure in thread"));
+    }
+}
@@ -0,0 +1,28 @@
+//! Access to the environment.
+//!
+//! All reads and writes of the environment by this crate go through this module,
+//! which delegates to the installed [`crate::EnvBackend`].
+
+use std::ffi::{OsStr, OsString};
+
+use crate::{backend, watch};
+
+/// Returns the value of a variable.
+pub(crate) fn var_os(key: impl AsRef<OsStr>) -> Option<OsString> {
+    backend::backend().var_os(key.as_ref())
+}
+
+/// Returns all variables.
+pub(crate) fn vars_os() -> impl Iterator<Item = (OsString, OsString)> {
+    backend::backend().vars_os().into_iter()
+}
+
+/// Sets a variable, notifying the watchers of the variable.
+pub(crate) fn set_var(key: &OsStr, value: &OsStr) {
+    watch::changing(key, || backend::backend().set_var(key, value));
+}
+
+/// Removes a variable, notifying the watchers of the variable.
+pub(crate) fn remove_var(key: &OsStr) {
+    watch::changing(key, || backend::backend().remove_var(key));
+}
@@ -0,0 +1,186 @@
+//! Task-local overlay on top of the process environment for async tests.
+//!
+//! Futures run through [`ScopedEnv::run`] see their own variables through the
+//! accessors of this crate ([`crate::var`], [`crate::var_os`], [`crate::StdEnv`]),
+//! even when several of them interleave on the same worker threads.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    fmt,
+    future::Future,
+};
+
+use crate::redact;
+
+tokio::task_local! {
+    static OVERLAY: RefCell<HashMap<OsString, Option<OsString>>>;
+}
+
+/// Variables which are only visible within the futures run by [`ScopedEnv::run`].
+///
+/// ```rust
+/// use temp_env_vars::ScopedEnv;
+///
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// ScopedEnv::task_local()
+///     .set("FOO", "BAR")
+///     .run(async {
+///         assert_eq!(temp_env_vars::var("FOO").unwrap(), "BAR");
+///     })
+///     .await;
+/// # });
+/// ```
+#[derive(Clone, Default)]
+pub struct ScopedEnv {
+    vars: HashMap<OsString, Option<OsString>>,
+}
+
+/// Values of secrets like `*_TOKEN` are redacted.
+impl fmt::Debug for ScopedEnv {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScopedEnv")
+            .field("vars", &redact::Vars::new(&self.vars))
+            .finish()
+    }
+}
+
+impl ScopedEnv {
+    /// Creates an empty task-local environment.
+    pub fn task_local() -> ScopedEnv {
+        ScopedEnv::default()
+    }
+
+    /// Sets a variable for the task.
+    pub fn set(mut self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> ScopedEnv {
+        self.vars.insert(
+            key.as_ref().to_os_string(),
+            Some(value.as_ref().to_os_string()),
+        );
+        self
+    }
+
+    /// Sets a variable for the task and marks it as secret with
+    /// [`crate::mark_secret`], so its value never shows up in output of this crate.
+    pub fn set_secret(self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) -> ScopedEnv {
+        crate::mark_secret(key.as_ref());
+        self.set(key, value)
+    }
+
+    /// Hides a variable for the task, even if it is set in the process environment.
+    pub fn remove(mut self, key: impl AsRef<OsStr>) -> ScopedEnv {
+        self.vars.insert(key.as_ref().to_os_string(), None);
+        self
+    }
+
+    /// Runs the future with the variables of this environment. The variables of
+    /// an enclosing task-local environment are inherited.
+    pub async fn run<F: Future>(self, future: F) -> F::Output {
+        let mut vars = OVERLAY
+            .try_with(|overlay| overlay.borrow().clone())
+            .unwrap_or_default();
+        vars.extend(self.vars);
+
+        OVERLAY.scope(RefCell::new(vars), future).await
+    }
+}
+
+/// Looks the variable up in the overlay of the current task.
+///
+/// Returns `None` if the overlay does not know the variable, `Some(None)` if it
+/// was removed in the overlay.
+pub(crate) fn lookup(key: &OsStr) -> Option<Option<OsString>> {
+    OVERLAY
+        .try_with(|overlay| overlay.borrow().get(key).cloned())
+        .ok()
+        .flatten()
+}
+
+/// Applies the change to the overlay of the current task. Returns `false` if
+/// the current task runs without a task-local environment.
+pub(crate) fn update(key: &OsStr, value: Option<&OsStr>) -> bool {
+    OVERLAY
+        .try_with(|overlay| {
+            overlay
+                .borrow_mut()
+                .insert(key.to_os_string(), value.map(OsStr::to_os_string));
+        })
+        .is_ok()
+}
+
+/// Applies the overlay of the current task on top of the given variables.
+pub(crate) fn apply_overlay(vars: &mut HashMap<OsString, OsString>) {
+    let _ = OVERLAY.try_with(|overlay| {
+        for (key, value) in overlay.borrow().iter() {
+            match value {
+                Some(value) => vars.insert(key.clone(), value.clone()),
+                None => vars.remove(key),
+            };
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use assertor::{assert_that, OptionAssertion, ResultAssertion};
+    use serial_test::parallel;
+
+    use super::ScopedEnv;
+    use crate::{EnvProvider, StdEnv};
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    #[parallel]
+    async fn test_interleaving_tasks_see_their_own_variables() {
+        let task = |value: &'static str| {
+            ScopedEnv::task_local()
+                .set("TASK_LOCAL_A", value)
+                .run(async move {
+                    for _ in 0..5 {
+                        tokio::time::sleep(Duration::from_millis(5)).await;
+                        assert_that!(crate::var("TASK_LOCAL_A")).has_ok(value.to_string());
+                    }
+                })
+        };
+
+        let first = tokio::spawn(task("1"));
+        let second = tokio::spawn(task("2"));
+
+        first.await.unwrap();
+        second.await.unwrap();
+        assert_that!(std::env::var_os("TASK_LOCAL_A")).is_none();
+    }
+
+    #[tokio::test]
+    #[parallel]
+    async fn test_nested_environments_inherit_and_shadow() {
+        ScopedEnv::task_local()
+            .set("TASK_LOCAL_B", "outer")
+            .set("TASK_LOCAL_C", "outer")
+            .run(async {
+                ScopedEnv::task_local()
+                    .set("TASK_LOCAL_C", "inner")
+                    .run(async {
+                        assert_that!(crate::var("TASK_LOCAL_B")).has_ok("outer".to_string());
+                        assert_that!(crate::var("TASK_LOCAL_C")).has_ok("inner".to_string());
+                    })
+                    .await;
+            })
+            .await;
+    }
+
+    #[tokio::test]
+    #[parallel]
+    async fn test_std_env_provider_writes_into_the_task_overlay() {
+        ScopedEnv::task_local()
+            .run(async {
+                StdEnv.set("TASK_LOCAL_D", "1");
+                assert_that!(StdEnv.get("TASK_LOCAL_D")).has_value("1".to_string());
+            })
+            .await;
+
+        assert_that!(std::env::var_os("TASK_LOCAL_D")).is_none();
+    }
+}
@@ -0,0 +1,130 @@
+//! Durations and sizes in the formats configuration parsers expect.
+
+use std::{ffi::OsStr, time::Duration};
+
+use crate::{TempEnvScope, ToEnvValue};
+
+/// Format of durations set with [`TempEnvScope::set_duration_as`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DurationFormat {
+    /// The largest unit which represents the duration exactly, e.g. `30s` or
+    /// `500ms`, as understood by `humantime`.
+    #[default]
+    Units,
+    /// Seconds without unit, with a fraction if needed, e.g. `30` or `0.5`.
+    Seconds,
+    /// Milliseconds without unit, rounded down, e.g. `500`.
+    Millis,
+}
+
+/// Format of sizes set with [`TempEnvScope::set_bytes_as`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ByteFormat {
+    /// The largest binary unit which represents the size exactly, e.g. `64MiB`,
+    /// as understood by `bytesize` and `byte-unit`.
+    #[default]
+    Binary,
+    /// The largest decimal unit which represents the size exactly, e.g. `64MB`.
+    Decimal,
+    /// The number of bytes without unit, e.g. `67108864`.
+    Bytes,
+}
+
+impl DurationFormat {
+    /// Formats the duration.
+    pub fn format(self, duration: Duration) -> String {
+        match self {
+            DurationFormat::Units => duration.to_env_value(),
+            DurationFormat::Seconds if duration.subsec_nanos() == 0 => {
+                duration.as_secs().to_string()
+            }
+            DurationFormat::Seconds => duration.as_secs_f64().to_string(),
+            DurationFormat::Millis => duration.as_millis().to_string(),
+        }
+    }
+}
+
+impl ByteFormat {
+    /// Formats the size in bytes.
+    pub fn format(self, bytes: u64) -> String {
+        let (base, units): (u64, [&str; 6]) = match self {
+            ByteFormat::Binary => (1024, ["B", "KiB", "MiB", "GiB", "TiB", "PiB"]),
+            ByteFormat::Decimal => (1000, ["B", "KB", "MB", "GB", "TB", "PB"]),
+            ByteFormat::Bytes => return bytes.to_string(),
+        };
+        let (mut value, mut unit) = (bytes, 0);
+        while value != 0 && value % base == 0 && unit + 1 < units.len() {
+            value /= base;
+            unit += 1;
+        }
+        format!("{value}{}", units[unit])
+    }
+}
+
+impl TempEnvScope {
+    /// Sets a variable to a duration in the largest exact unit, e.g. `30s`.
+    pub fn set_duration(&self, key: impl AsRef<OsStr>, duration: Duration) {
+        self.set_duration_as(key, duration, DurationFormat::default());
+    }
+
+    /// Sets a variable to a duration in the given format.
+    ///
+    /// ```rust
+    /// use std::time::Duration;
+    ///
+    /// use temp_env_vars::DurationFormat;
+    ///
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// env_scope.set_duration_as("TIMEOUT_MS", Duration::from_secs(2), DurationFormat::Millis);
+    ///
+    /// assert_eq!(std::env::var("TIMEOUT_MS").unwrap(), "2000");
+    /// ```
+    pub fn set_duration_as(
+        &self,
+        key: impl AsRef<OsStr>,
+        duration: Duration,
+        format: DurationFormat,
+    ) {
+        self.set(key, format.format(duration));
+    }
+
+    /// Sets a variable to a size in the largest exact binary unit, e.g.
+    /// `64MiB` for `64 * 1024 * 1024`.
+    pub fn set_bytes(&self, key: impl AsRef<OsStr>, bytes: u64) {
+        self.set_bytes_as(key, bytes, ByteFormat::default());
+    }
+
+    /// Sets a variable to a size in bytes in the given format.
+    pub fn set_bytes_as(&self, key: impl AsRef<OsStr>, bytes: u64, format: ByteFormat) {
+        self.set(key, format.format(bytes));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use assertor::{assert_that, EqualityAssertion};
+
+    use super::{ByteFormat, DurationFormat};
+
+    #[test]
+    fn test_durations_are_formatted() {
+        let duration = Duration::from_millis(1500);
+
+        assert_that!(DurationFormat::Units.format(duration)).is_equal_to("1500ms".to_string());
+        assert_that!(DurationFormat::Seconds.format(duration)).is_equal_to("1.5".to_string());
+        assert_that!(DurationFormat::Seconds.format(Duration::from_secs(30)))
+            .is_equal_to("30".to_string());
+        assert_that!(DurationFormat::Millis.format(duration)).is_equal_to("1500".to_string());
+    }
+
+    #[test]
+    fn test_sizes_are_formatted_in_the_largest_exact_unit() {
+        assert_that!(ByteFormat::Binary.format(64 * 1024 * 1024)).is_equal_to("64MiB".to_string());
+        assert_that!(ByteFormat::Binary.format(1536)).is_equal_to("1536B".to_string());
+        assert_that!(ByteFormat::Binary.format(0)).is_equal_to("0B".to_string());
+        assert_that!(ByteFormat::Decimal.format(64_000_000)).is_equal_to("64MB".to_string());
+        assert_that!(ByteFormat::Bytes.format(1024)).is_equal_to("1024".to_string());
+    }
+}
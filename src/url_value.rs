@@ -0,0 +1,115 @@
+//! Setting variables to validated URLs of endpoints.
+
+use std::ffi::OsStr;
+
+use url::Url;
+
+use crate::{EnvError, TempEnvScope};
+
+/// A URL accepted by [`TempEnvScope::set_url`]: a `url::Url` or a string
+/// parsed as URL.
+pub trait UrlValue {
+    /// Returns the text of the URL, if it is valid.
+    fn to_url_value(&self) -> Result<String, String>;
+}
+
+impl UrlValue for Url {
+    fn to_url_value(&self) -> Result<String, String> {
+        check_endpoint(self)?;
+        Ok(self.to_string())
+    }
+}
+
+/// The text is kept as it is, e.g. without the trailing `/` `Url` adds.
+impl UrlValue for str {
+    fn to_url_value(&self) -> Result<String, String> {
+        let url = Url::parse(self).map_err(|err| format!("{self:?} is not a URL: {err}"))?;
+        check_endpoint(&url)?;
+        Ok(self.to_string())
+    }
+}
+
+impl UrlValue for String {
+    fn to_url_value(&self) -> Result<String, String> {
+        self.as_str().to_url_value()
+    }
+}
+
+impl<T: UrlValue + ?Sized> UrlValue for &T {
+    fn to_url_value(&self) -> Result<String, String> {
+        (**self).to_url_value()
+    }
+}
+
+/// Rejects URLs which cannot be connected to, like `localhost:8080`, which
+/// parses as URL with the scheme `localhost`.
+fn check_endpoint(url: &Url) -> Result<(), String> {
+    if url.has_host() {
+        Ok(())
+    } else {
+        Err(format!("{:?} has no host", url.as_str()))
+    }
+}
+
+impl TempEnvScope {
+    /// Sets a variable to the URL of an endpoint, e.g. `http://localhost:8080`.
+    ///
+    /// ```rust
+    /// let env_scope = temp_env_vars::TempEnvScope::new();
+    /// env_scope.set_url("API_BASE", "http://localhost:8080");
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if the URL is malformed or has no host. Use [`Self::try_set_url`]
+    /// to handle such input.
+    pub fn set_url(&self, key: impl AsRef<OsStr>, url: impl UrlValue) {
+        if let Err(err) = self.try_set_url(key, url) {
+            panic!("temp_env_vars: {err}");
+        }
+    }
+
+    /// Sets a variable to the URL of an endpoint. Malformed URLs or URLs
+    /// without host are reported as error.
+    pub fn try_set_url(&self, key: impl AsRef<OsStr>, url: impl UrlValue) -> Result<(), EnvError> {
+        let key = key.as_ref();
+        let value = url
+            .to_url_value()
+            .map_err(|message| EnvError::InvalidValue {
+                key: key.to_os_string(),
+                message,
+            })?;
+        self.try_set(key, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, ResultAssertion};
+    use serial_test::serial;
+    use url::Url;
+
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_valid_urls_are_set() {
+        let env_scope = TempEnvScope::new();
+
+        env_scope.set_url("URL_TEXT", "http://localhost:8080");
+        env_scope.set_url("URL_PARSED", Url::parse("https://example.com").unwrap());
+
+        assert_that!(std::env::var("URL_TEXT")).has_ok("http://localhost:8080".to_string());
+        assert_that!(std::env::var("URL_PARSED")).has_ok("https://example.com/".to_string());
+    }
+
+    #[test]
+    #[serial]
+    fn test_invalid_urls_are_rejected() {
+        let env_scope = TempEnvScope::new();
+
+        assert_that!(env_scope.try_set_url("URL_INVALID", "http://")).is_err();
+        assert_that!(env_scope.try_set_url("URL_INVALID", "localhost:8080")).is_err();
+        assert_that!(std::env::var("URL_INVALID")).is_err();
+    }
+}
@@ -0,0 +1,168 @@
+//! Conversion of typed values into the text of environment variables.
+
+use std::{
+    borrow::Cow,
+    ffi::{OsStr, OsString},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6},
+    num::{
+        NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroU16, NonZeroU32, NonZeroU64,
+        NonZeroU8, NonZeroUsize,
+    },
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// A value which can be stored in an environment variable, used by
+/// [`crate::TempEnvScope::set_typed`].
+///
+/// Implemented for numbers, `bool`, `char`, strings, IP and socket addresses
+/// (all through their `Display` output), and [`Duration`]. Implement it for
+/// other types, or use [`crate::TempEnvScope::set_formatted`] to format a
+/// value differently once.
+pub trait ToEnvValue {
+    fn to_env_value(&self) -> String;
+}
+
+impl<T: ToEnvValue + ?Sized> ToEnvValue for &T {
+    fn to_env_value(&self) -> String {
+        (**self).to_env_value()
+    }
+}
+
+macro_rules! impl_to_env_value_via_display {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl ToEnvValue for $t {
+                fn to_env_value(&self) -> String {
+                    self.to_string()
+                }
+            }
+        )*
+    };
+}
+
+impl_to_env_value_via_display!(
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64,
+    bool,
+    char,
+    str,
+    String,
+    NonZeroU8,
+    NonZeroU16,
+    NonZeroU32,
+    NonZeroU64,
+    NonZeroUsize,
+    NonZeroI8,
+    NonZeroI16,
+    NonZeroI32,
+    NonZeroI64,
+    IpAddr,
+    Ipv4Addr,
+    Ipv6Addr,
+    SocketAddr,
+    SocketAddrV4,
+    SocketAddrV6,
+);
+
+/// Formats the duration in the largest unit which represents it exactly, e.g.
+/// `5s`, `1500ms` or `0s`, as understood by parsers like `humantime`.
+impl ToEnvValue for Duration {
+    fn to_env_value(&self) -> String {
+        let nanos = self.as_nanos();
+        let units = [
+            (3_600_000_000_000, "h"),
+            (60_000_000_000, "m"),
+            (1_000_000_000, "s"),
+            (1_000_000, "ms"),
+            (1_000, "us"),
+        ];
+        if nanos == 0 {
+            return "0s".to_string();
+        }
+        units
+            .iter()
+            .find(|(unit, _)| nanos % unit == 0)
+            .map(|(unit, suffix)| format!("{}{suffix}", nanos / unit))
+            .unwrap_or_else(|| format!("{nanos}ns"))
+    }
+}
+
+/// A value which can be set with [`crate::TempEnvScope::set_secret`].
+///
+/// Implemented for strings and paths, and with the `secrecy` feature for
+/// `secrecy::SecretString`, which is zeroized once the variable is set.
+pub trait SecretValue {
+    /// Returns the value to store in the environment.
+    fn expose_env_value(&self) -> &OsStr;
+}
+
+impl<T: SecretValue + ?Sized> SecretValue for &T {
+    fn expose_env_value(&self) -> &OsStr {
+        (**self).expose_env_value()
+    }
+}
+
+macro_rules! impl_secret_value_via_os_str {
+    ($($t:ty),* $(,)?) => {
+        $(
+            impl SecretValue for $t {
+                fn expose_env_value(&self) -> &OsStr {
+                    OsStr::new(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_secret_value_via_os_str!(str, String, OsStr, OsString, Path, PathBuf);
+
+impl SecretValue for Cow<'_, str> {
+    fn expose_env_value(&self) -> &OsStr {
+        OsStr::new(&**self)
+    }
+}
+
+impl SecretValue for Cow<'_, OsStr> {
+    fn expose_env_value(&self) -> &OsStr {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::Ipv4Addr, time::Duration};
+
+    use assertor::{assert_that, EqualityAssertion};
+
+    use super::ToEnvValue;
+
+    #[test]
+    fn test_display_values_are_formatted() {
+        assert_that!(8080u16.to_env_value()).is_equal_to("8080".to_string());
+        assert_that!(true.to_env_value()).is_equal_to("true".to_string());
+        assert_that!(1.5f64.to_env_value()).is_equal_to("1.5".to_string());
+        assert_that!(Ipv4Addr::LOCALHOST.to_env_value()).is_equal_to("127.0.0.1".to_string());
+    }
+
+    #[test]
+    fn test_durations_are_formatted_in_the_largest_exact_unit() {
+        assert_that!(Duration::from_secs(5).to_env_value()).is_equal_to("5s".to_string());
+        assert_that!(Duration::from_secs(120).to_env_value()).is_equal_to("2m".to_string());
+        assert_that!(Duration::from_millis(1500).to_env_value()).is_equal_to("1500ms".to_string());
+        assert_that!(Duration::from_nanos(1).to_env_value()).is_equal_to("1ns".to_string());
+        assert_that!(Duration::ZERO.to_env_value()).is_equal_to("0s".to_string());
+    }
+}
@@ -0,0 +1,205 @@
+//! Thread-local overlay on top of the process environment.
+//!
+//! Variables set in a [`VirtualEnvScope`] are only visible through the accessors
+//! of this crate ([`crate::var`], [`crate::var_os`], [`crate::StdEnv`]) on the thread
+//! which created the scope. The process environment is never modified, so tests
+//! using only virtual scopes can run in parallel without any lock.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    marker::PhantomData,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static OVERLAY: RefCell<Vec<Layer>> = const { RefCell::new(Vec::new()) };
+}
+
+#[derive(Debug)]
+struct Layer {
+    id: u64,
+    /// `None` marks a variable as removed.
+    vars: HashMap<OsString, Option<OsString>>,
+}
+
+/// A scope whose changes are only visible on the current thread through the
+/// accessors of this crate.
+///
+/// ```rust
+/// use temp_env_vars::VirtualEnvScope;
+///
+/// let env_scope = VirtualEnvScope::new();
+/// env_scope.set("FOO", "BAR");
+///
+/// assert_eq!(temp_env_vars::var("FOO").unwrap(), "BAR");
+/// assert!(std::env::var("FOO").is_err());
+/// ```
+#[derive(Debug)]
+pub struct VirtualEnvScope {
+    id: u64,
+    // The overlay lives in a thread-local, so the scope must stay on its thread
+    _not_send: PhantomData<*const ()>,
+}
+
+impl VirtualEnvScope {
+    pub fn new() -> VirtualEnvScope {
+        let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+        OVERLAY.with(|overlay| {
+            overlay.borrow_mut().push(Layer {
+                id,
+                vars: HashMap::new(),
+            })
+        });
+        VirtualEnvScope {
+            id,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Sets a variable in the overlay.
+    pub fn set(&self, key: impl AsRef<OsStr>, value: impl AsRef<OsStr>) {
+        self.update(key.as_ref(), Some(value.as_ref().to_os_string()));
+    }
+
+    /// Hides a variable, even if it is set in the process environment.
+    pub fn remove(&self, key: impl AsRef<OsStr>) {
+        self.update(key.as_ref(), None);
+    }
+
+    fn update(&self, key: &OsStr, value: Option<OsString>) {
+        OVERLAY.with(|overlay| {
+            let mut overlay = overlay.borrow_mut();
+            let layer = overlay
+                .iter_mut()
+                .find(|layer| layer.id == self.id)
+                .expect("the layer of a VirtualEnvScope lives as long as the scope");
+            layer.vars.insert(key.to_os_string(), value);
+        });
+    }
+}
+
+impl Default for VirtualEnvScope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for VirtualEnvScope {
+    fn drop(&mut self) {
+        OVERLAY.with(|overlay| overlay.borrow_mut().retain(|layer| layer.id != self.id));
+    }
+}
+
+/// Looks the variable up in the overlay of the current thread.
+///
+/// Returns `None` if the overlay does not know the variable, `Some(None)` if it
+/// was removed in the overlay.
+pub(crate) fn lookup(key: &OsStr) -> Option<Option<OsString>> {
+    OVERLAY.with(|overlay| {
+        overlay
+            .borrow()
+            .iter()
+            .rev()
+            .find_map(|layer| layer.vars.get(key).cloned())
+    })
+}
+
+/// Applies the change to the innermost overlay layer. Returns `false` if there
+/// is no active virtual scope on this thread.
+pub(crate) fn update(key: &OsStr, value: Option<&OsStr>) -> bool {
+    OVERLAY.with(|overlay| match overlay.borrow_mut().last_mut() {
+        Some(layer) => {
+            layer
+                .vars
+                .insert(key.to_os_string(), value.map(OsStr::to_os_string));
+            true
+        }
+        None => false,
+    })
+}
+
+/// Applies all layers of the current thread on top of the given variables.
+pub(crate) fn apply_overlay(vars: &mut HashMap<OsString, OsString>) {
+    OVERLAY.with(|overlay| {
+        for layer in overlay.borrow().iter() {
+            for (key, value) in &layer.vars {
+                match value {
+                    Some(value) => vars.insert(key.clone(), value.clone()),
+                    None => vars.remove(key),
+                };
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, EqualityAssertion, OptionAssertion, ResultAssertion};
+    use serial_test::parallel;
+
+    use super::VirtualEnvScope;
+    use crate::{EnvProvider, StdEnv};
+
+    #[test]
+    #[parallel]
+    fn test_overlay_is_visible_through_accessors_only() {
+        let env_scope = VirtualEnvScope::new();
+        env_scope.set("VIRTUAL_A", "1");
+
+        assert_that!(crate::var("VIRTUAL_A")).has_ok("1".to_string());
+        assert_that!(std::env::var_os("VIRTUAL_A")).is_none();
+    }
+
+    #[test]
+    #[parallel]
+    fn test_removed_variables_are_hidden() {
+        let path = std::env::var_os("PATH");
+        let env_scope = VirtualEnvScope::new();
+        env_scope.remove("PATH");
+
+        assert_that!(crate::var_os("PATH")).is_none();
+        assert_that!(std::env::var_os("PATH")).is_equal_to(path);
+    }
+
+    #[test]
+    #[parallel]
+    fn test_inner_scopes_shadow_outer_scopes_until_dropped() {
+        let outer = VirtualEnvScope::new();
+        outer.set("VIRTUAL_B", "outer");
+        {
+            let inner = VirtualEnvScope::new();
+            inner.set("VIRTUAL_B", "inner");
+            assert_that!(crate::var("VIRTUAL_B")).has_ok("inner".to_string());
+        }
+
+        assert_that!(crate::var("VIRTUAL_B")).has_ok("outer".to_string());
+    }
+
+    #[test]
+    #[parallel]
+    fn test_std_env_provider_writes_into_the_overlay() {
+        let _env_scope = VirtualEnvScope::new();
+
+        StdEnv.set("VIRTUAL_C", "1");
+
+        assert_that!(StdEnv.get("VIRTUAL_C")).has_value("1".to_string());
+        assert_that!(std::env::var_os("VIRTUAL_C")).is_none();
+    }
+
+    #[test]
+    #[parallel]
+    fn test_other_threads_do_not_see_the_overlay() {
+        let env_scope = VirtualEnvScope::new();
+        env_scope.set("VIRTUAL_D", "1");
+
+        let seen = std::thread::spawn(|| crate::var_os("VIRTUAL_D"))
+            .join()
+            .unwrap();
+
+        assert_that!(seen).is_none();
+    }
+}
@@ -0,0 +1,128 @@
+//! Watching single variables, to find out which test or fixture changes them.
+
+use std::{
+    ffi::OsStr,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Mutex, PoisonError,
+    },
+};
+
+use crate::{snapshot::EnvKey, sys};
+
+type Callback = Box<dyn FnMut(Option<&OsStr>, Option<&OsStr>) + Send>;
+
+struct Watcher {
+    id: u64,
+    key: EnvKey,
+    callback: Callback,
+}
+
+static WATCHERS: Mutex<Vec<Watcher>> = Mutex::new(Vec::new());
+/// Number of active watchers, so changes are cheap while nothing is watched.
+static ACTIVE: AtomicUsize = AtomicUsize::new(0);
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Calls `callback` with the old and the new value whenever the variable is
+/// changed through this crate, while the returned guard is alive. `None` stands
+/// for a variable which is not set.
+///
+/// All changes through scopes, restoring included, [`crate::set_var`],
+/// [`crate::remove_var`] and [`crate::GlobalEnv`] are reported. Changes made
+/// directly through `std::env` are not. The callback must not change the
+/// environment itself.
+///
+/// ```rust
+/// let _guard = temp_env_vars::watch("RUST_LOG", |old, new| {
+///     eprintln!("RUST_LOG changed from {old:?} to {new:?}");
+/// });
+/// ```
+#[must_use = "the variable is only watched while the guard is alive"]
+pub fn watch(
+    key: impl AsRef<OsStr>,
+    callback: impl FnMut(Option<&OsStr>, Option<&OsStr>) + Send + 'static,
+) -> WatchGuard {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    watchers().push(Watcher {
+        id,
+        key: EnvKey::new(key.as_ref()),
+        callback: Box::new(callback),
+    });
+    ACTIVE.fetch_add(1, Ordering::SeqCst);
+    WatchGuard { id }
+}
+
+/// Stops watching the variable when dropped, see [`watch`].
+#[derive(Debug)]
+pub struct WatchGuard {
+    id: u64,
+}
+
+impl Drop for WatchGuard {
+    fn drop(&mut self) {
+        watchers().retain(|watcher| watcher.id != self.id);
+        ACTIVE.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Runs `change` and reports it to the watchers of the variable, if any.
+pub(crate) fn changing(key: &OsStr, change: impl FnOnce()) {
+    if ACTIVE.load(Ordering::SeqCst) == 0 {
+        change();
+        return;
+    }
+    let old = sys::var_os(key);
+    change();
+    let new = sys::var_os(key);
+    if old == new {
+        return;
+    }
+    let key = EnvKey::new(key);
+    for watcher in watchers().iter_mut().filter(|watcher| watcher.key == key) {
+        (watcher.callback)(old.as_deref(), new.as_deref());
+    }
+}
+
+fn watchers() -> std::sync::MutexGuard<'static, Vec<Watcher>> {
+    WATCHERS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        ffi::OsString,
+        sync::{Arc, Mutex},
+    };
+
+    use assertor::{assert_that, EqualityAssertion};
+    use serial_test::serial;
+
+    use super::watch;
+    use crate::TempEnvScope;
+
+    #[test]
+    #[serial]
+    fn test_changes_are_reported_while_watching() {
+        let changes = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&changes);
+        let guard = watch("WATCHED", move |old, new| {
+            recorded
+                .lock()
+                .unwrap()
+                .push((old.map(OsString::from), new.map(OsString::from)));
+        });
+
+        {
+            let env_scope = TempEnvScope::new();
+            env_scope.set("WATCHED", "1");
+            env_scope.set("WATCHED", "1");
+            env_scope.set("OTHER", "1");
+        }
+        drop(guard);
+        crate::set_var("WATCHED", "2");
+        crate::remove_var("WATCHED");
+
+        assert_that!(changes.lock().unwrap().clone())
+            .is_equal_to(vec![(None, Some("1".into())), (Some("1".into()), None)]);
+    }
+}
@@ -0,0 +1,156 @@
+//! Scopes for the scenarios of BDD frameworks like `cucumber`.
+//!
+//! Attribute macros don't apply to scenarios, so [`TempEnvWorld`] is meant to be
+//! embedded in the world of the framework and started and finished from its
+//! hooks. With the `cucumber` feature, it is also a `cucumber::World` itself.
+
+use crate::{env_lock, EnvLockGuard, TempEnvScope};
+
+/// Holds the lock and a scope from the start of a scenario until its end.
+///
+/// ```rust
+/// use temp_env_vars::TempEnvWorld;
+///
+/// #[derive(Debug, Default)]
+/// struct World {
+///     env: TempEnvWorld,
+/// }
+///
+/// let mut world = World::default();
+/// // in the before hook
+/// world.env.before_scenario();
+/// world.env.scope().set("FOO", "BAR");
+/// // in the after hook
+/// world.env.after_scenario();
+///
+/// assert!(std::env::var("FOO").is_err());
+/// ```
+///
+/// The lock is reentrant per thread, and `cucumber` runs concurrent scenarios
+/// on one thread. So when calling [`Self::before_scenario`] and
+/// [`Self::after_scenario`] from the `before` and `after` hooks, the lock
+/// doesn't keep those scenarios apart: scenarios using the environment must not
+/// run concurrently, e.g. by tagging them with `@serial` or with
+/// `max_concurrent_scenarios(1)`. The `cucumber::World` implementation doesn't
+/// have this limitation.
+#[derive(Debug, Default)]
+pub struct TempEnvWorld {
+    /// The scope is dropped before the guard, so it restores under the lock.
+    active: Option<(TempEnvScope, EnvLockGuard)>,
+}
+
+impl TempEnvWorld {
+    /// Acquires the lock and captures the environment. A running scenario is
+    /// finished first.
+    pub fn before_scenario(&mut self) {
+        self.after_scenario();
+        let lock = env_lock();
+        self.active = Some((TempEnvScope::new(), lock));
+    }
+
+    /// Restores the environment and releases the lock. Does nothing if no
+    /// scenario is running.
+    pub fn after_scenario(&mut self) {
+        self.active = None;
+    }
+
+    /// Returns the scope of the running scenario.
+    ///
+    /// # Panics
+    ///
+    /// Panics if no scenario is running.
+    pub fn scope(&self) -> &TempEnvScope {
+        match &self.active {
+            Some((scope, _)) => scope,
+            None => panic!("temp_env_vars: no scenario is running, call `before_scenario` first"),
+        }
+    }
+}
+
+/// Starts a scenario for each new world, with the `cucumber` feature. The world
+/// finishes the scenario when it is dropped at the end of the scenario.
+///
+/// Unlike [`TempEnvWorld::before_scenario`], creating the world waits for the
+/// lock even if another scenario on the same thread holds it, so scenarios
+/// using the environment run one after another, also with the concurrent runner
+/// of `cucumber`. Worlds with own state can create this world from their
+/// `#[world(init = ...)]` function. Don't run `cucumber` while holding the
+/// lock, e.g. from a `#[temp_env_vars]` test, as the first scenario would wait
+/// for it forever.
+#[cfg(feature = "cucumber")]
+impl cucumber::World for TempEnvWorld {
+    type Error = std::convert::Infallible;
+
+    async fn new() -> Result<TempEnvWorld, Self::Error> {
+        let lock = crate::lock::env_lock_async_per_task().await;
+        Ok(TempEnvWorld {
+            active: Some((TempEnvScope::new(), lock)),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "cucumber")]
+    use assertor::EqualityAssertion;
+    use assertor::{assert_that, ResultAssertion};
+    use serial_test::serial;
+
+    use super::TempEnvWorld;
+
+    #[test]
+    #[serial]
+    fn test_scenario_changes_are_restored() {
+        std::env::remove_var("WORLD_VAR");
+        let mut world = TempEnvWorld::default();
+
+        world.before_scenario();
+        world.scope().set("WORLD_VAR", "1");
+        assert_that!(std::env::var("WORLD_VAR")).has_ok("1".to_string());
+        world.after_scenario();
+
+        assert_that!(std::env::var("WORLD_VAR")).is_err();
+    }
+
+    #[test]
+    #[serial]
+    fn test_dropping_the_world_finishes_the_scenario() {
+        std::env::remove_var("WORLD_VAR");
+
+        {
+            let mut world = TempEnvWorld::default();
+            world.before_scenario();
+            std::env::set_var("WORLD_VAR", "1");
+        }
+
+        assert_that!(std::env::var("WORLD_VAR")).is_err();
+    }
+
+    #[cfg(feature = "cucumber")]
+    #[tokio::test]
+    #[serial]
+    async fn test_cucumber_worlds_wait_for_each_other() {
+        use std::time::Duration;
+
+        use cucumber::World;
+
+        let first = TempEnvWorld::new().await.unwrap();
+        first.scope().set("WORLD_VAR", "1");
+        let mut second = std::pin::pin!(TempEnvWorld::new());
+
+        let waited = tokio::time::timeout(Duration::from_millis(50), second.as_mut()).await;
+        assert_that!(waited.is_err()).is_equal_to(true);
+        drop(first);
+
+        let second = second.await.unwrap();
+        assert_that!(std::env::var("WORLD_VAR")).is_err();
+        drop(second);
+    }
+
+    #[test]
+    #[serial]
+    #[should_panic(expected = "no scenario is running")]
+    fn test_scope_requires_a_running_scenario() {
+        TempEnvWorld::default().scope();
+    }
+}
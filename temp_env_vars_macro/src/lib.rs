@@ -1,11 +1,143 @@
 extern crate proc_macro;
-use quote::quote;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use quote::{quote, ToTokens};
+use syn::{punctuated::Punctuated, Token};
+
+/// Name of the config file, searched from the manifest directory of the crate
+/// up to the workspace root, so one file at the workspace root applies to all
+/// members.
+const CONFIG_FILE: &str = "temp_env_vars.toml";
+
+/// Content of the config file, applied to every test using `#[temp_env_vars]`.
+#[derive(Default, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Config {
+    /// Variables set at the start of every test.
+    defaults: BTreeMap<String, String>,
+    /// Variables removed at the start of every test.
+    denylist: Vec<String>,
+    /// Profiles which can be applied with `profile = "..."`.
+    profiles: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Config {
+    /// Finds and reads the config file, if there is one.
+    fn load() -> syn::Result<Option<(PathBuf, Config)>> {
+        let Some(dir) = std::env::var_os("CARGO_MANIFEST_DIR") else {
+            return Ok(None);
+        };
+        let Some(path) = find_config(Path::new(&dir)) else {
+            return Ok(None);
+        };
+        let config = std::fs::read_to_string(&path)
+            .map_err(|err| err.to_string())
+            .and_then(|content| toml::from_str(&content).map_err(|err| err.to_string()))
+            .map_err(|err| {
+                syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    format!("cannot load {}: {err}", path.display()),
+                )
+            })?;
+        Ok(Some((path, config)))
+    }
+}
+
+/// Searches the config file from the manifest directory upwards, up to the
+/// first directory whose `Cargo.toml` declares a `[workspace]`. Without such a
+/// directory, the crate is its own workspace and only its directory is searched.
+fn find_config(manifest_dir: &Path) -> Option<PathBuf> {
+    let root = manifest_dir
+        .ancestors()
+        .find(|dir| is_workspace_root(dir))
+        .unwrap_or(manifest_dir);
+    manifest_dir
+        .ancestors()
+        .take_while(|dir| dir.starts_with(root))
+        .map(|dir| dir.join(CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+fn is_workspace_root(dir: &Path) -> bool {
+    std::fs::read_to_string(dir.join("Cargo.toml"))
+        .ok()
+        .and_then(|content| content.parse::<toml::Table>().ok())
+        .is_some_and(|manifest| manifest.contains_key("workspace"))
+}
+
+/// Arguments given to the `#[temp_env_vars(...)]` attribute.
+#[derive(Default)]
+struct MacroArgs {
+    read_only: bool,
+    strict: bool,
+    check_unused: bool,
+    allow_threads: bool,
+    declared: Vec<syn::LitStr>,
+    profiles: Vec<syn::LitStr>,
+}
+
+impl MacroArgs {
+    fn parse(args: proc_macro::TokenStream) -> syn::Result<MacroArgs> {
+        let metas =
+            syn::parse::Parser::parse(Punctuated::<syn::Meta, Token![,]>::parse_terminated, args)?;
+
+        let mut parsed = MacroArgs::default();
+        for meta in metas {
+            match meta {
+                syn::Meta::Path(path) if path.is_ident("read_only") => parsed.read_only = true,
+                syn::Meta::Path(path) if path.is_ident("strict") => parsed.strict = true,
+                syn::Meta::Path(path) if path.is_ident("check_unused") => {
+                    parsed.check_unused = true
+                }
+                syn::Meta::Path(path) if path.is_ident("allow_threads") => {
+                    parsed.allow_threads = true
+                }
+                syn::Meta::NameValue(syn::MetaNameValue {
+                    path,
+                    value:
+                        syn::Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Str(profile),
+                            ..
+                        }),
+                    ..
+                }) if path.is_ident("profile") => parsed.profiles.push(profile),
+                syn::Meta::List(list) if list.path.is_ident("declare") => {
+                    let keys = list
+                        .parse_args_with(Punctuated::<syn::LitStr, Token![,]>::parse_terminated)?;
+                    parsed.declared.extend(keys);
+                }
+                other => return Err(syn::Error::new_spanned(
+                    other,
+                    "unknown argument, expected `read_only`, `strict`, `check_unused`, `allow_threads`, `declare(\"VAR\", ...)` or `profile = \"NAME\"`",
+                )),
+            }
+        }
+        if parsed.read_only && !parsed.profiles.is_empty() {
+            return Err(syn::Error::new(
+                proc_macro2::Span::call_site(),
+                "`profile` cannot be combined with `read_only`",
+            ));
+        }
+        Ok(parsed)
+    }
+}
 
 #[proc_macro_attribute]
 pub fn temp_env_vars(
-    _: proc_macro::TokenStream,
+    args: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
+    let args = match MacroArgs::parse(args) {
+        Ok(args) => args,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(err) => return err.to_compile_error().into(),
+    };
     let item_fn: Result<syn::ItemFn, _> = syn::parse(item.clone());
     let item_fn = match item_fn {
         Ok(item_fn) => item_fn,
@@ -31,22 +163,134 @@ pub fn temp_env_vars(
         quote! {}
     };
     let block = item_fn.block;
+    if !args.allow_threads {
+        if let Some(usage) = thread_usage(&attrs, &block) {
+            return syn::Error::new(
+                proc_macro2::Span::call_site(),
+                format!("the test uses {usage}, whose threads may change the environment outside of the lock; add `allow_threads` to `#[temp_env_vars]` if they are done before the test ends"),
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+
+    let declared = &args.declared;
+    let declaration = if declared.is_empty() {
+        quote! {}
+    } else {
+        quote! { _temp_env_vars_scope.declare([#(#declared),*]); }
+    };
+    // The config file is baked in, and included to rebuild the tests when it changes
+    let (tracking, configured) = match &config {
+        Some((path, config)) if !args.read_only => {
+            let path = path.display().to_string();
+            let denied = &config.denylist;
+            let (keys, values): (Vec<_>, Vec<_>) = config.defaults.iter().unzip();
+            (
+                quote! { const _: &[u8] = include_bytes!(#path); },
+                quote! {
+                    #(_temp_env_vars_scope.remove(#denied);)*
+                    #(_temp_env_vars_scope.set(#keys, #values);)*
+                },
+            )
+        }
+        Some((path, _)) => {
+            let path = path.display().to_string();
+            (
+                quote! { const _: &[u8] = include_bytes!(#path); },
+                quote! {},
+            )
+        }
+        None => (quote! {}, quote! {}),
+    };
+    let profiles = args.profiles.iter().map(|profile| {
+        let configured = config
+            .as_ref()
+            .and_then(|(_, config)| config.profiles.get(&profile.value()));
+        match configured {
+            Some(vars) => {
+                let (keys, values): (Vec<_>, Vec<_>) = vars.iter().unzip();
+                quote! { #(_temp_env_vars_scope.set(#keys, #values);)* }
+            }
+            None => quote! { _temp_env_vars_scope.apply_profile(#profile); },
+        }
+    });
+    let profiles = quote! { #(#profiles)* };
+    let strictness = if args.strict {
+        quote! { _temp_env_vars_scope.strict(); }
+    } else {
+        quote! {}
+    };
+    let unused_check = if args.check_unused {
+        quote! { _temp_env_vars_scope.check_unused_declarations(); }
+    } else {
+        quote! {}
+    };
+    // Executors may resume and drop the future of an async test on another thread
+    let foreign_drop = if item_fn.sig.asyncness.is_some() {
+        quote! { _temp_env_vars_scope.allow_drop_on_other_thread(); }
+    } else {
+        quote! {}
+    };
+
+    // Read-only tests share the lock with each other, all other tests get exclusive access
+    let (locking, scope) = if args.read_only {
+        (quote! { env_read_lock }, quote! { read_only })
+    } else {
+        (quote! { env_lock }, quote! { new })
+    };
+    // Async tests wait for the lock without blocking the executor, if possible
+    #[cfg(feature = "tokio")]
+    let locking = if item_fn.sig.asyncness.is_some() {
+        let locking = quote::format_ident!("{}_async", locking.to_string());
+        quote! { #locking().await }
+    } else {
+        quote! { #locking() }
+    };
+    #[cfg(not(feature = "tokio"))]
+    let locking = quote! { #locking() };
+
+    let setup = quote! {
+        let _temp_env_vars_scope_lock = temp_env_vars::#locking;
+        _temp_env_vars_scope_lock.set_holder_name(concat!(module_path!(), "::", stringify!(#name)));
+        #tracking
+        let _temp_env_vars_scope = temp_env_vars::TempEnvScope::#scope();
+        #configured
+        #profiles
+        #declaration
+        #strictness
+        #unused_check
+        #foreign_drop
+    };
+    // With "#[serial]" applied before this macro, the body is already wrapped by
+    // "serial_test". The setup goes into the wrapped test, so the lock of
+    // "serial_test" is always acquired before the lock of this crate.
+    let body = if block
+        .to_token_stream()
+        .to_string()
+        .contains("serial_test ::")
+    {
+        match wrap_serial_test(&block, &setup, item_fn.sig.asyncness.is_some()) {
+            Some(block) => quote! { #block },
+            None => {
+                return syn::Error::new(
+                    proc_macro2::Span::call_site(),
+                    "cannot combine with the expansion of `#[serial]`, apply `#[serial]` after `#[temp_env_vars]`",
+                )
+                .to_compile_error()
+                .into()
+            }
+        }
+    } else {
+        quote! { { #setup #block } }
+    };
 
     let gen = quote! {
         #(#attrs)
         *
-        #vis #asynciness fn #name () #returning {
-            let _temp_env_vars_scope_lock = temp_env_vars::TEMP_ENV_VAR_MACRO_MUTEX.lock();
-            let _temp_env_vars_scope = temp_env_vars::TempEnvScope::new();
-            #block
-        }
+        #vis #asynciness fn #name () #returning #body
     };
 
-    // The "#[serial]" macro should be applied before the "#[temp_env_vars]" macro
-    if gen.to_string().contains("serial_test ::") {
-        panic!("Apply the '#[serial]' after the '#[temp_env_vars]' macro");
-    }
-
     #[cfg(all(feature = "debug_temp_env_vars", not(test)))]
     {
         std::fs::write("target/temp_env_vars_debug.rs", gen.to_string()).unwrap();
@@ -60,3 +304,421 @@ pub fn temp_env_vars(
 
     gen.into()
 }
+
+/// Paths of common APIs running code on other threads, with a description. A
+/// path in the test matches if it starts with one of these, after resolving the
+/// `use` declarations of the test and dropping a leading `std`.
+const THREAD_APIS: &[(&[&str], &str)] = &[
+    (&["thread", "spawn"], "`thread::spawn`"),
+    (&["thread", "scope"], "`thread::scope`"),
+    (&["thread", "Builder"], "`thread::Builder`"),
+    (&["tokio", "spawn"], "`tokio::spawn`"),
+    (&["tokio", "task", "spawn"], "`task::spawn`"),
+    (&["task", "spawn"], "`task::spawn`"),
+    (&["tokio", "task", "spawn_blocking"], "`spawn_blocking`"),
+    (&["task", "spawn_blocking"], "`spawn_blocking`"),
+    (
+        &["tokio", "runtime", "Builder", "new_multi_thread"],
+        "a multi-threaded tokio runtime",
+    ),
+    (
+        &["runtime", "Builder", "new_multi_thread"],
+        "a multi-threaded tokio runtime",
+    ),
+    (
+        &["Builder", "new_multi_thread"],
+        "a multi-threaded tokio runtime",
+    ),
+    (&["rayon"], "`rayon`"),
+];
+
+/// Returns the first API running code on other threads the test uses, if any.
+fn thread_usage(attrs: &[syn::Attribute], block: &syn::Block) -> Option<&'static str> {
+    if attrs.iter().any(is_multi_thread_runtime) {
+        return Some("a multi-threaded tokio runtime");
+    }
+    let mut uses = Uses::default();
+    syn::visit::Visit::visit_block(&mut uses, block);
+    let mut usage = ThreadUsage { uses, usage: None };
+    syn::visit::Visit::visit_block(&mut usage, block);
+    usage.usage
+}
+
+/// Whether the attribute is like `#[tokio::test(flavor = "multi_thread")]`.
+fn is_multi_thread_runtime(attr: &syn::Attribute) -> bool {
+    if !matches!(attr.meta, syn::Meta::List(_)) {
+        return false;
+    }
+    let mut multi_thread = false;
+    let _ = attr.parse_nested_meta(|meta| {
+        if meta.input.peek(Token![=]) {
+            let value: syn::Expr = meta.value()?.parse()?;
+            if meta.path.is_ident("flavor") {
+                multi_thread |= matches!(
+                    value,
+                    syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Str(flavor), .. }) if flavor.value() == "multi_thread"
+                );
+            }
+        }
+        Ok(())
+    });
+    multi_thread
+}
+
+/// The `use` declarations within a test.
+#[derive(Default)]
+struct Uses {
+    /// Imported names with the path they refer to.
+    names: BTreeMap<String, Vec<String>>,
+    /// Paths imported with `*`.
+    globs: Vec<Vec<String>>,
+}
+
+impl Uses {
+    fn add(&mut self, tree: &syn::UseTree, prefix: &mut Vec<String>) {
+        match tree {
+            syn::UseTree::Path(path) => {
+                prefix.push(path.ident.to_string());
+                self.add(&path.tree, prefix);
+                prefix.pop();
+            }
+            syn::UseTree::Name(name) => self.import(prefix, &name.ident, &name.ident),
+            syn::UseTree::Rename(rename) => self.import(prefix, &rename.ident, &rename.rename),
+            syn::UseTree::Glob(_) => self.globs.push(prefix.clone()),
+            syn::UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.add(tree, prefix);
+                }
+            }
+        }
+    }
+
+    fn import(&mut self, prefix: &[String], ident: &syn::Ident, name: &syn::Ident) {
+        let mut path = prefix.to_vec();
+        if ident != "self" {
+            path.push(ident.to_string());
+        }
+        let name = if name == "self" {
+            path.last().cloned().unwrap_or_default()
+        } else {
+            name.to_string()
+        };
+        self.names.insert(name, path);
+    }
+
+    /// Returns the paths the given path might refer to.
+    fn resolve(&self, segments: &[String]) -> Vec<Vec<String>> {
+        let Some((first, rest)) = segments.split_first() else {
+            return Vec::new();
+        };
+        if let Some(path) = self.names.get(first) {
+            return vec![path.iter().chain(rest).cloned().collect()];
+        }
+        let globbed = self
+            .globs
+            .iter()
+            .map(|glob| glob.iter().chain(segments).cloned().collect());
+        std::iter::once(segments.to_vec()).chain(globbed).collect()
+    }
+}
+
+impl<'ast> syn::visit::Visit<'ast> for Uses {
+    fn visit_item_use(&mut self, item: &'ast syn::ItemUse) {
+        self.add(&item.tree, &mut Vec::new());
+    }
+}
+
+/// Finds paths to APIs running code on other threads, also within the
+/// arguments of macros like `assert_eq!`.
+struct ThreadUsage {
+    uses: Uses,
+    usage: Option<&'static str>,
+}
+
+impl ThreadUsage {
+    fn check(&mut self, segments: &[String]) {
+        if self.usage.is_some() {
+            return;
+        }
+        for path in self.uses.resolve(segments) {
+            let path = match path.split_first() {
+                Some((first, rest)) if first == "std" => rest,
+                _ => &path[..],
+            };
+            let matches = |api: &[&str]| {
+                path.len() >= api.len() && path.iter().zip(api).all(|(segment, api)| segment == api)
+            };
+            if let Some((_, usage)) = THREAD_APIS.iter().find(|(api, _)| matches(api)) {
+                self.usage = Some(usage);
+                return;
+            }
+        }
+    }
+}
+
+impl<'ast> syn::visit::Visit<'ast> for ThreadUsage {
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        let segments: Vec<String> = path
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect();
+        self.check(&segments);
+        syn::visit::visit_path(self, path);
+    }
+
+    fn visit_item_use(&mut self, item: &'ast syn::ItemUse) {
+        let mut uses = Uses::default();
+        uses.add(&item.tree, &mut Vec::new());
+        for path in uses.names.into_values().chain(uses.globs) {
+            self.check(&path);
+        }
+    }
+
+    fn visit_expr_method_call(&mut self, call: &'ast syn::ExprMethodCall) {
+        if call.method == "spawn_blocking" && self.usage.is_none() {
+            self.usage = Some("`spawn_blocking`");
+        }
+        syn::visit::visit_expr_method_call(self, call);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        let parser = Punctuated::<syn::Expr, Token![,]>::parse_terminated;
+        if let Ok(args) = mac.parse_body_with(parser) {
+            for arg in &args {
+                self.visit_expr(arg);
+            }
+        }
+        syn::visit::visit_macro(self, mac);
+    }
+}
+
+/// Moves the setup into the test wrapped by `serial_test`, which is the last
+/// argument of the call ending the body: a closure for sync tests and a future
+/// for async tests.
+fn wrap_serial_test(
+    block: &syn::Block,
+    setup: &proc_macro2::TokenStream,
+    is_async: bool,
+) -> Option<syn::Block> {
+    let mut block = block.clone();
+    let Some(syn::Stmt::Expr(expr, _)) = block.stmts.last_mut() else {
+        return None;
+    };
+    let call = match expr {
+        syn::Expr::Call(call) => call,
+        syn::Expr::Await(syn::ExprAwait { base, .. }) => match base.as_mut() {
+            syn::Expr::Call(call) => call,
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let test = call.args.last_mut()?;
+    let wrapped = match &*test {
+        syn::Expr::Closure(closure) => {
+            let body = &closure.body;
+            let mut closure = closure.clone();
+            closure.body = Box::new(syn::parse_quote! { { #setup #body } });
+            syn::Expr::Closure(closure)
+        }
+        future if is_async => syn::parse_quote! { async move { #setup (#future).await } },
+        _ => return None,
+    };
+    *test = wrapped;
+    Some(block)
+}
+
+/// Options of a field given with `#[temp_env(...)]`.
+#[derive(Default)]
+struct FieldArgs {
+    name: Option<syn::LitStr>,
+    skip: bool,
+}
+
+/// Parses the `#[temp_env(...)]` attributes, calling `on_meta` for each argument.
+fn parse_temp_env_attrs(
+    attrs: &[syn::Attribute],
+    mut on_meta: impl FnMut(syn::meta::ParseNestedMeta) -> syn::Result<()>,
+) -> syn::Result<()> {
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("temp_env")) {
+        attr.parse_nested_meta(&mut on_meta)?;
+    }
+    Ok(())
+}
+
+/// Returns whether the type is an `Option`, whose `None` removes the variable.
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+fn derive_temp_env_impl(input: syn::DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let mut prefix = String::new();
+    parse_temp_env_attrs(&input.attrs, |meta| {
+        if meta.path.is_ident("prefix") {
+            prefix = meta.value()?.parse::<syn::LitStr>()?.value();
+            Ok(())
+        } else {
+            Err(meta.error("unknown argument, expected `prefix = \"...\"`"))
+        }
+    })?;
+
+    let fields = match &input.data {
+        syn::Data::Struct(syn::DataStruct {
+            fields: syn::Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "`TempEnv` can only be derived for structs with named fields",
+            ))
+        }
+    };
+
+    let mut assignments = Vec::new();
+    for field in fields {
+        let mut args = FieldArgs::default();
+        parse_temp_env_attrs(&field.attrs, |meta| {
+            if meta.path.is_ident("name") {
+                args.name = Some(meta.value()?.parse()?);
+                Ok(())
+            } else if meta.path.is_ident("skip") {
+                args.skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("unknown argument, expected `name = \"VAR\"` or `skip`"))
+            }
+        })?;
+        if args.skip {
+            continue;
+        }
+
+        let ident = field.ident.as_ref().expect("named fields have a name");
+        let key = match args.name {
+            Some(name) => name.value(),
+            None => format!("{prefix}{}", ident.to_string().to_uppercase()),
+        };
+        assignments.push(if is_option(&field.ty) {
+            quote! {
+                match &self.#ident {
+                    Some(value) => _temp_env_vars_scope.set_typed(#key, value),
+                    None => _temp_env_vars_scope.remove(#key),
+                }
+            }
+        } else {
+            quote! { _temp_env_vars_scope.set_typed(#key, &self.#ident); }
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics #name #type_generics #where_clause {
+            /// Sets the variables of the fields within a new scope, which restores
+            /// them when dropped.
+            pub fn apply(&self) -> temp_env_vars::TempEnvScope {
+                let _temp_env_vars_scope = temp_env_vars::TempEnvScope::new();
+                #(#assignments)*
+                _temp_env_vars_scope
+            }
+        }
+    })
+}
+
+/// Derives `apply(&self) -> TempEnvScope` for a struct, setting a variable for
+/// each field through `ToEnvValue`.
+///
+/// Variables are named after the fields in upper case, optionally with the prefix
+/// given by `#[temp_env(prefix = "DB_")]` on the struct. Fields accept
+/// `#[temp_env(name = "VAR")]` for another name and `#[temp_env(skip)]`. Fields of
+/// type `Option` remove the variable if they are `None`.
+#[proc_macro_derive(TempEnv, attributes(temp_env))]
+pub fn derive_temp_env(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = syn::parse_macro_input!(input as syn::DeriveInput);
+    derive_temp_env_impl(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, EqualityAssertion};
+
+    use super::{find_config, thread_usage, CONFIG_FILE};
+
+    fn usage(attrs: &[syn::Attribute], block: syn::Block) -> Option<&'static str> {
+        thread_usage(attrs, &block)
+    }
+
+    #[test]
+    fn test_imported_thread_apis_are_found() {
+        let block = syn::parse_quote! {{
+            use std::thread;
+            thread::spawn(|| ()).join().unwrap();
+        }};
+        assert_that!(usage(&[], block)).is_equal_to(Some("`thread::spawn`"));
+
+        let block = syn::parse_quote! {{
+            use std::thread::{self as threads, spawn};
+            let handle = spawn(|| ());
+        }};
+        assert_that!(usage(&[], block)).is_equal_to(Some("`thread::spawn`"));
+
+        let block = syn::parse_quote! {{
+            use tokio::task::*;
+            assert!(spawn_blocking(|| 1).await.is_ok());
+        }};
+        assert_that!(usage(&[], block)).is_equal_to(Some("`spawn_blocking`"));
+    }
+
+    #[test]
+    fn test_multi_thread_runtimes_are_found() {
+        let attrs =
+            [syn::parse_quote! { #[tokio::test(flavor = "multi_thread", worker_threads = 2)] }];
+        assert_that!(usage(&attrs, syn::parse_quote! {{}}))
+            .is_equal_to(Some("a multi-threaded tokio runtime"));
+    }
+
+    #[test]
+    fn test_other_paths_and_strings_are_ignored() {
+        let block = syn::parse_quote! {{
+            use my_executor::task;
+            task::spawn(async {});
+            my_executor::task::spawn(async {});
+            println!("thread::spawn");
+            let name = "tokio::spawn";
+        }};
+        assert_that!(usage(&[], block)).is_equal_to(None);
+    }
+
+    #[test]
+    fn test_config_is_searched_up_to_the_workspace_root() {
+        let outside =
+            std::env::temp_dir().join(format!("temp_env_vars_macro_{}", std::process::id()));
+        let workspace = outside.join("workspace");
+        let member = workspace.join("member");
+        std::fs::create_dir_all(&member).unwrap();
+        std::fs::write(outside.join(CONFIG_FILE), "").unwrap();
+        std::fs::write(
+            workspace.join("Cargo.toml"),
+            "[workspace]\nmembers = [\"member\"]\n",
+        )
+        .unwrap();
+        std::fs::write(member.join("Cargo.toml"), "[package]\nname = \"member\"\n").unwrap();
+
+        let beyond_workspace = find_config(&member);
+        std::fs::write(workspace.join(CONFIG_FILE), "").unwrap();
+        let in_workspace = find_config(&member);
+        std::fs::remove_dir_all(&outside).unwrap();
+
+        assert_that!(beyond_workspace).is_equal_to(None);
+        assert_that!(in_workspace).is_equal_to(Some(workspace.join(CONFIG_FILE)));
+    }
+}
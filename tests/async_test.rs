@@ -0,0 +1,38 @@
+#![cfg(feature = "tokio")]
+
+use std::time::Duration;
+
+use assertor::{assert_that, ResultAssertion};
+use temp_env_vars::temp_env_vars;
+
+#[temp_env_vars]
+#[tokio::test]
+async fn test_concurrency_between_two_async_tests_work_a() {
+    assert_that!(std::env::var("ASYNC_FOO")).is_err();
+    std::env::set_var("ASYNC_FOO", "1");
+
+    // The lock is awaited, so the other test cannot override "ASYNC_FOO" meanwhile
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_that!(std::env::var("ASYNC_FOO")).has_ok("1".to_string());
+}
+
+#[temp_env_vars]
+#[tokio::test]
+async fn test_concurrency_between_two_async_tests_work_b() {
+    assert_that!(std::env::var("ASYNC_FOO")).is_err();
+    std::env::set_var("ASYNC_FOO", "2");
+
+    // The lock is awaited, so the other test cannot override "ASYNC_FOO" meanwhile
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    assert_that!(std::env::var("ASYNC_FOO")).has_ok("2".to_string());
+}
+
+#[serial_test::serial]
+#[temp_env_vars]
+#[tokio::test]
+async fn test_serial_can_be_applied_before_the_macro() {
+    assert_that!(std::env::var("ASYNC_SERIAL_ORDER")).is_err();
+    std::env::set_var("ASYNC_SERIAL_ORDER", "1");
+}
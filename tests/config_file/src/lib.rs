@@ -0,0 +1,30 @@
+//! Tests of the `temp_env_vars.toml` next to the manifest of this crate, which
+//! applies only to the tests of this crate.
+
+#[cfg(test)]
+mod tests {
+    use assertor::{assert_that, EqualityAssertion, ResultAssertion};
+    use temp_env_vars::temp_env_vars;
+
+    #[temp_env_vars(profile = "configured")]
+    fn read_configured_vars() -> [Option<String>; 3] {
+        [
+            "TEMP_ENV_VARS_DEFAULT",
+            "TEMP_ENV_VARS_DENIED",
+            "CONFIGURED_PROFILE_FOO",
+        ]
+        .map(|key| std::env::var(key).ok())
+    }
+
+    #[test]
+    fn test_config_file_is_applied_by_macro() {
+        std::env::set_var("TEMP_ENV_VARS_DENIED", "1");
+
+        let vars = read_configured_vars();
+
+        assert_that!(vars).is_equal_to([Some("1".to_string()), None, Some("1".to_string())]);
+        assert_that!(std::env::var("TEMP_ENV_VARS_DEFAULT")).is_err();
+        assert_that!(std::env::var("TEMP_ENV_VARS_DENIED")).has_ok("1".to_string());
+        std::env::remove_var("TEMP_ENV_VARS_DENIED");
+    }
+}
@@ -0,0 +1,43 @@
+use assertor::{assert_that, ResultAssertion};
+use serial_test::serial;
+use temp_env_vars::TempEnv;
+
+#[derive(TempEnv)]
+#[temp_env(prefix = "DB_")]
+struct DbEnv {
+    host: String,
+    port: u16,
+    #[temp_env(name = "DATABASE_USER")]
+    user: &'static str,
+    password: Option<String>,
+    #[temp_env(skip)]
+    #[allow(dead_code)]
+    comment: String,
+}
+
+#[test]
+#[serial]
+fn test_fields_are_applied_and_restored() {
+    std::env::set_var("DB_PASSWORD", "secret");
+    let db_env = DbEnv {
+        host: "localhost".to_string(),
+        port: 5432,
+        user: "admin",
+        password: None,
+        comment: "not a variable".to_string(),
+    };
+
+    {
+        let _env_scope = db_env.apply();
+
+        assert_that!(std::env::var("DB_HOST")).has_ok("localhost".to_string());
+        assert_that!(std::env::var("DB_PORT")).has_ok("5432".to_string());
+        assert_that!(std::env::var("DATABASE_USER")).has_ok("admin".to_string());
+        assert_that!(std::env::var("DB_PASSWORD")).is_err();
+        assert_that!(std::env::var("DB_COMMENT")).is_err();
+    }
+
+    assert_that!(std::env::var("DB_HOST")).is_err();
+    assert_that!(std::env::var("DB_PASSWORD")).has_ok("secret".to_string());
+    std::env::remove_var("DB_PASSWORD");
+}
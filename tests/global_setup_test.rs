@@ -0,0 +1,21 @@
+use std::io::Write;
+
+use assertor::{assert_that, ResultAssertion};
+use temp_env_vars::global_setup;
+
+// The base environment is applied once per process, so it has a binary of its own
+#[test]
+fn test_setup_is_applied_once() {
+    let mut dotenv = tempfile::NamedTempFile::new().unwrap();
+    writeln!(dotenv, "GLOBAL_DOTENV=from file").unwrap();
+
+    global_setup(|env| {
+        env.set("GLOBAL_VAR", "1").load_dotenv(dotenv.path());
+    });
+    global_setup(|env| {
+        env.set("GLOBAL_VAR", "2");
+    });
+
+    assert_that!(std::env::var("GLOBAL_VAR")).has_ok("1".to_string());
+    assert_that!(std::env::var("GLOBAL_DOTENV")).has_ok("from file".to_string());
+}
@@ -0,0 +1,29 @@
+use assertor::{assert_that, ResultAssertion};
+use temp_env_vars::Isolated;
+
+#[test]
+fn test_changes_stay_in_the_child_process() {
+    temp_env_vars::isolated(|| {
+        std::env::set_var("ISOLATED_FOO", "1");
+        assert_that!(std::env::var("ISOLATED_FOO")).has_ok("1".to_string());
+    });
+
+    assert_that!(std::env::var("ISOLATED_FOO")).is_err();
+}
+
+#[test]
+fn test_child_environment_can_be_controlled() {
+    Isolated::new()
+        .env_clear()
+        .env("ISOLATED_BAR", "2")
+        .run(|| {
+            assert_that!(std::env::var("ISOLATED_BAR")).has_ok("2".to_string());
+            assert_that!(std::env::var("CARGO_PKG_NAME")).is_err();
+        });
+}
+
+#[test]
+#[should_panic(expected = "isolated child of `test_failures_of_the_child_are_propagated` failed")]
+fn test_failures_of_the_child_are_propagated() {
+    temp_env_vars::isolated(|| panic!("failing in the child"));
+}
@@ -0,0 +1,38 @@
+#![cfg(all(unix, feature = "libc"))]
+
+use std::ffi::{CStr, CString, OsString};
+
+use assertor::{assert_that, OptionAssertion};
+use temp_env_vars::{LibcBackend, TempEnvScope};
+
+/// Reads a variable like C libraries do.
+fn c_getenv(key: &str) -> Option<String> {
+    let key = CString::new(key).unwrap();
+    // SAFETY: The name is NUL terminated and this binary has a single test
+    unsafe {
+        let value = libc::getenv(key.as_ptr());
+        (!value.is_null()).then(|| CStr::from_ptr(value).to_string_lossy().into_owned())
+    }
+}
+
+// The backend is installed once per process, so this binary has a single test
+#[test]
+fn test_libc_backend_changes_and_restores_are_visible_to_c() {
+    // SAFETY: This binary has a single test, which starts no threads
+    temp_env_vars::set_backend(unsafe { LibcBackend::new() });
+    temp_env_vars::set_var("LIBC_RESTORED", "original");
+
+    {
+        let env_scope = TempEnvScope::new();
+        env_scope.set("LIBC_RESTORED", "changed");
+        env_scope.set("LIBC_ADDED", "1");
+        assert_that!(c_getenv("LIBC_RESTORED")).has_value("changed".to_string());
+        assert_that!(temp_env_vars::var_os("LIBC_ADDED")).has_value(OsString::from("1"));
+    }
+
+    assert_that!(c_getenv("LIBC_RESTORED")).has_value("original".to_string());
+    assert_that!(c_getenv("LIBC_ADDED")).is_none();
+
+    temp_env_vars::remove_var("LIBC_RESTORED");
+    assert_that!(c_getenv("LIBC_RESTORED")).is_none();
+}
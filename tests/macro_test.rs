@@ -1,7 +1,7 @@
 use core::time;
 use std::thread::sleep;
 
-use assertor::{assert_that, ResultAssertion};
+use assertor::{assert_that, BooleanAssertion, OptionAssertion, ResultAssertion};
 use temp_env_vars::temp_env_vars;
 
 #[test]
@@ -29,3 +29,74 @@ fn test_concurrency_between_two_tests_work_b() {
 
     assert_that!(std::env::var("FOO")).has_ok("2".to_string());
 }
+
+#[test]
+#[temp_env_vars(read_only)]
+fn test_read_only_tests_can_read() {
+    assert_that!(std::env::var("CARGO_PKG_NAME")).is_ok();
+}
+
+#[test]
+#[temp_env_vars(read_only)]
+#[should_panic(expected = "changed within a read-only scope")]
+fn test_read_only_tests_fail_when_changing_the_env() {
+    std::env::set_var("READ_ONLY_FOO", "1");
+}
+
+#[test]
+#[temp_env_vars]
+fn test_env_lock_can_be_taken_within_macro() {
+    let guard = temp_env_vars::env_lock();
+
+    assert_that!(guard.is_exclusive()).is_true();
+}
+
+#[temp_env_vars(profile = "macro_test")]
+fn read_profiled_var() -> Result<String, std::env::VarError> {
+    std::env::var("PROFILED_FOO")
+}
+
+#[test]
+fn test_profile_is_applied_by_macro() {
+    temp_env_vars::profiles().register("macro_test", [("PROFILED_FOO", "1")]);
+
+    assert_that!(read_profiled_var()).has_ok("1".to_string());
+    assert_that!(std::env::var("PROFILED_FOO")).is_err();
+}
+
+#[test]
+#[serial_test::serial]
+#[temp_env_vars]
+fn test_serial_can_be_applied_before_the_macro() {
+    assert_that!(std::env::var("SERIAL_ORDER")).is_err();
+    std::env::set_var("SERIAL_ORDER", "1");
+}
+
+#[test]
+#[temp_env_vars]
+#[serial_test::serial]
+fn test_serial_can_be_applied_after_the_macro() {
+    assert_that!(std::env::var("SERIAL_ORDER")).is_err();
+    std::env::set_var("SERIAL_ORDER", "2");
+}
+
+#[test]
+#[temp_env_vars(allow_threads)]
+fn test_threads_can_be_allowed() {
+    std::env::set_var("ALLOWED_THREADS", "1");
+
+    let value = std::thread::spawn(|| std::env::var("ALLOWED_THREADS"))
+        .join()
+        .unwrap();
+
+    assert_that!(value).has_ok("1".to_string());
+}
+
+#[test]
+#[temp_env_vars]
+fn test_lock_holder_is_named_after_the_test() {
+    let holder = temp_env_vars::env_lock_status().holder.unwrap();
+
+    assert_that!(holder.name)
+        .has_value("macro_test::test_lock_holder_is_named_after_the_test".to_string());
+}
@@ -0,0 +1,21 @@
+#![cfg(feature = "rstest")]
+
+use std::time::Duration;
+
+use assertor::{assert_that, ResultAssertion};
+use rstest::rstest;
+use temp_env_vars::{temp_env, EnvContext};
+
+#[rstest]
+#[case("1")]
+#[case("2")]
+#[case("3")]
+fn test_cases_using_the_fixture_do_not_interfere(temp_env: EnvContext, #[case] value: &str) {
+    assert_that!(std::env::var("RSTEST_FOO")).is_err();
+    temp_env.set("RSTEST_FOO", value);
+
+    // The lock is held, so no other case can override "RSTEST_FOO" meanwhile
+    std::thread::sleep(Duration::from_millis(50));
+
+    assert_that!(std::env::var("RSTEST_FOO")).has_ok(value.to_string());
+}
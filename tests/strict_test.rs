@@ -0,0 +1,25 @@
+use assertor::{assert_that, ResultAssertion};
+use temp_env_vars::temp_env_vars;
+
+#[test]
+#[temp_env_vars(strict, declare("STRICT_FOO", "STRICT_BAR"))]
+fn test_declared_variables_can_be_read() {
+    std::env::set_var("STRICT_FOO", "1");
+
+    assert_that!(temp_env_vars::var("STRICT_FOO")).has_ok("1".to_string());
+    assert_that!(temp_env_vars::var("STRICT_BAR")).is_err();
+}
+
+#[test]
+#[temp_env_vars(strict, declare("STRICT_FOO"))]
+#[should_panic(expected = "undeclared environment variable \"STRICT_OTHER\"")]
+fn test_undeclared_variables_fail_the_test() {
+    let _ = temp_env_vars::var("STRICT_OTHER");
+}
+
+#[test]
+#[temp_env_vars(strict, check_unused, declare("STRICT_FOO", "STRICT_UNUSED"))]
+#[should_panic(expected = "declared environment variables [\"STRICT_UNUSED\"] were never read")]
+fn test_unused_declarations_fail_the_test() {
+    let _ = temp_env_vars::var("STRICT_FOO");
+}